@@ -0,0 +1,385 @@
+//!include world.h.wgsl particle.h.wgsl particle_view.h.wgsl sph.h.wgsl forces.h.wgsl grid.h.wgsl neighbor_grid_dense.h.wgsl
+
+// secondary particles after Ihmsen et al. 2012, "Unified spray, foam and air bubbles
+// for particle-based fluids". the diffuse particles live in a ring buffer and never
+// act back on the fluid.
+// the neighbours of fluid and diffuse particles come from a grid of the fluid binned once
+// per frame by bin_fluid_main, into the cells of the scene's neighbour search
+// (neighbor_grid_dense.h.wgsl or neighbor_grid_hashed.h.wgsl), hashed for all pairs.
+
+// compute_pass_diffuse.rs DiffuseParticleRaw
+struct DiffuseParticle {
+    position: vec3<f32>,
+    lifetime: f32, // seconds left, dead at 0
+    velocity: vec3<f32>,
+    kind: u32, // 0 spray, 1 foam, 2 bubble
+};
+
+// compute_pass_diffuse.rs DiffuseUniforms
+struct DiffuseUniforms {
+    time_step: f32,
+    trapped_air: f32, // particles per second at full potential
+    wave_crest: f32,
+    lifetime: f32,
+    seed: u32,
+    deterministic: u32, // spawn through spawn_slots instead of racing on the cursor
+    time: f32, // seconds simulated, animates the force fields
+    _pad: u32,
+};
+
+@group(1) @binding(0)
+var<storage, read_write> diffuse: array<DiffuseParticle>;
+
+@group(1) @binding(1)
+var<storage, read_write> spawn_cursor: atomic<u32>;
+
+// xyz: outward surface normal, w: how lopsided the neighbourhood is, 0 inside the fluid
+@group(1) @binding(2)
+var<storage, read_write> normals: array<vec4<f32>>;
+
+// x: first ring slot, y: number of particles to spawn
+@group(1) @binding(3)
+var<storage, read_write> spawn_slots: array<vec2<u32>>;
+
+// a counter per cell or bucket, cleared before bin_fluid_main
+@group(1) @binding(4)
+var<storage, read_write> cell_counts: array<atomic<u32>>;
+
+// MAX_NUM_PARTICLES_PER_CELL slots per cell or bucket
+@group(1) @binding(5)
+var<storage, read_write> cell_particles: array<u32>;
+
+@group(2) @binding(0)
+var<uniform> uniforms: DiffuseUniforms;
+
+@group(3) @binding(0)
+var<uniform> world: WorldUniforms;
+
+@group(3) @binding(3)
+var<uniform> force_fields: ForceFields;
+
+const workgroup_size_x: u32 = 256;
+
+const DIFFUSE_SPRAY: u32 = 0u;
+const DIFFUSE_FOAM: u32 = 1u;
+const DIFFUSE_BUBBLE: u32 = 2u;
+
+// potentials are mapped to [0, 1] between these bounds
+const TRAPPED_AIR_RANGE = vec2<f32>(5.0, 20.0);
+const WAVE_CREST_RANGE = vec2<f32>(2.0, 8.0);
+const KINETIC_ENERGY_RANGE = vec2<f32>(0.5, 5.0); // per unit mass
+const MAX_SPAWN_PER_PARTICLE: u32 = 4u;
+
+// fewer fluid neighbours than this is spray, more than BUBBLE_NEIGHBOURS a bubble
+const SPRAY_NEIGHBOURS: u32 = 6u;
+const BUBBLE_NEIGHBOURS: u32 = 20u;
+const BUOYANCY: f32 = 2.0;
+const BUBBLE_DRAG: f32 = 0.5;
+
+// the particles within the support radius of a position, filled by gather_neighbours
+var<private> neighbours: array<u32, MAX_NUM_NEIGHBORS>;
+
+fn get_particle_id(gid: vec3<u32>, num_workgroups: vec3<u32>) -> u32 {
+    return gid.x + gid.y * workgroup_size_x * num_workgroups.x;
+}
+
+@compute
+@workgroup_size(workgroup_size_x, 1, 1)
+fn bin_fluid_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    let id = get_particle_id(gid, num_workgroups);
+    if id >= num_particles() {
+        return;
+    }
+
+    let bucket = grid_bucket(grid_cell(particle_position(id)));
+    let slot = atomicAdd(&cell_counts[bucket], 1u);
+    if slot < MAX_NUM_PARTICLES_PER_CELL {
+        cell_particles[bucket * MAX_NUM_PARTICLES_PER_CELL + slot] = id;
+    }
+}
+
+// fill `neighbours` with the particles closer than the support radius to `position`,
+// except `skip`, and return how many. sorted by index like the cached lists of
+// neighbors_list.h.wgsl, so the sums don't depend on the order of the binning, and a full
+// list keeps the lowest indices
+fn gather_neighbours(position: vec3<f32>, skip: u32) -> u32 {
+    let center = grid_cell(position);
+    var count = 0u;
+    for (var z = -1; z <= 1; z += 1) {
+        for (var y = -1; y <= 1; y += 1) {
+            for (var x = -1; x <= 1; x += 1) {
+                let cell = center + vec3<i32>(x, y, z);
+                if !grid_cell_valid(cell) {
+                    continue;
+                }
+                let bucket = grid_bucket(cell);
+                let bucket_count = min(atomicLoad(&cell_counts[bucket]), MAX_NUM_PARTICLES_PER_CELL);
+                for (var c = 0u; c < bucket_count; c += 1u) {
+                    let pj = cell_particles[bucket * MAX_NUM_PARTICLES_PER_CELL + c];
+                    let pj_position = particle_position(pj);
+                    // another cell in the same bucket, or a bucket shared by two of the 27
+                    if GRID_HASHED && any(grid_cell(pj_position) != cell) {
+                        continue;
+                    }
+                    if pj == skip || length(position - pj_position) >= world.dh {
+                        continue;
+                    }
+                    var n = count;
+                    if count == MAX_NUM_NEIGHBORS {
+                        if pj > neighbours[count - 1u] {
+                            continue;
+                        }
+                        n -= 1u;
+                    } else {
+                        count += 1u;
+                    }
+                    while n > 0u && neighbours[n - 1u] > pj {
+                        neighbours[n] = neighbours[n - 1u];
+                        n -= 1u;
+                    }
+                    neighbours[n] = pj;
+                }
+            }
+        }
+    }
+    return count;
+}
+
+@compute
+@workgroup_size(workgroup_size_x, 1, 1)
+fn surface_normal_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    let id = get_particle_id(gid, num_workgroups);
+    if id >= num_particles() {
+        return;
+    }
+
+    let p = load_particle(id);
+    var offset = vec3<f32>(0.0);
+    var weight_sum = 0.0;
+    let count = gather_neighbours(p.position, id);
+    for (var k = 0u; k < count; k += 1u) {
+        let x_ab = p.position - particle_position(neighbours[k]);
+        let w = falloff(length(x_ab));
+        offset += w * x_ab;
+        weight_sum += w;
+    }
+
+    let lopsided = length(offset) / max(weight_sum, 1e-6);
+    var normal = vec3<f32>(0.0);
+    if lopsided > 1e-6 {
+        normal = offset / length(offset);
+    }
+    normals[id] = vec4<f32>(normal, lopsided);
+}
+
+@compute
+@workgroup_size(workgroup_size_x, 1, 1)
+fn spawn_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    let id = get_particle_id(gid, num_workgroups);
+    if id >= num_particles() {
+        return;
+    }
+
+    var rng = spawn_rng(id);
+    let count = spawn_count(id, &rng);
+    if uniforms.deterministic != 0u {
+        // emitted by emit_main once assign_slots_main handed out the slots
+        spawn_slots[id] = vec2<u32>(0u, count);
+        return;
+    }
+
+    for (var i: u32 = 0; i < count; i += 1u) {
+        emit(id, atomicAdd(&spawn_cursor, 1u), &rng);
+    }
+}
+
+// deterministic mode: hand out the ring slots in particle order
+@compute
+@workgroup_size(1, 1, 1)
+fn assign_slots_main() {
+    var cursor = atomicLoad(&spawn_cursor);
+    for (var id: u32 = 0; id < num_particles(); id += 1u) {
+        spawn_slots[id].x = cursor;
+        cursor += spawn_slots[id].y;
+    }
+    atomicStore(&spawn_cursor, cursor);
+}
+
+@compute
+@workgroup_size(workgroup_size_x, 1, 1)
+fn emit_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    let id = get_particle_id(gid, num_workgroups);
+    if id >= num_particles() {
+        return;
+    }
+
+    // replay the random sequence of spawn_main
+    var rng = spawn_rng(id);
+    random(&rng);
+    let slots = spawn_slots[id];
+    for (var i: u32 = 0; i < slots.y; i += 1u) {
+        emit(id, slots.x + i, &rng);
+    }
+}
+
+fn spawn_rng(id: u32) -> u32 {
+    return hash(id ^ hash(uniforms.seed));
+}
+
+// number of diffuse particles fluid particle `id` spawns this step, draws one random number
+fn spawn_count(id: u32, rng: ptr<function, u32>) -> u32 {
+    let p = load_particle(id);
+    if p.ptype != 0 { return 0u; }
+
+    let kinetic = clamp_potential(0.5 * dot(p.velocity, p.velocity), KINETIC_ENERGY_RANGE);
+    if kinetic <= 0.0 { return 0u; }
+
+    let count = gather_neighbours(p.position, id);
+    let trapped_air = clamp_potential(trapped_air_potential(id, count), TRAPPED_AIR_RANGE);
+    let wave_crest = clamp_potential(wave_crest_potential(id, count), WAVE_CREST_RANGE);
+    let expected = kinetic * (uniforms.trapped_air * trapped_air + uniforms.wave_crest * wave_crest)
+        * uniforms.time_step;
+
+    return min(u32(expected + random(rng)), MAX_SPAWN_PER_PARTICLE);
+}
+
+fn emit(id: u32, cursor: u32, rng: ptr<function, u32>) {
+    let p = load_particle(id);
+    let slot = cursor % arrayLength(&diffuse);
+    let jitter = vec3<f32>(random(rng), random(rng), random(rng)) * 2.0 - 1.0;
+
+    var d: DiffuseParticle;
+    d.position = p.position + world.dx * jitter;
+    d.velocity = p.velocity;
+    d.lifetime = uniforms.lifetime * (0.5 + random(rng));
+    d.kind = DIFFUSE_SPRAY;
+    diffuse[slot] = d;
+}
+
+@compute
+@workgroup_size(workgroup_size_x, 1, 1)
+fn advect_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    let id = get_particle_id(gid, num_workgroups);
+    if id >= arrayLength(&diffuse) {
+        return;
+    }
+
+    var d = diffuse[id];
+    if d.lifetime <= 0.0 { return; }
+
+    // kernel weighted fluid velocity around the diffuse particle
+    var fluid_neighbours = 0u;
+    var fluid_velocity = vec3<f32>(0.0);
+    var weight_sum = 0.0;
+    let count = gather_neighbours(d.position, num_particles());
+    for (var k = 0u; k < count; k += 1u) {
+        let p = load_particle(neighbours[k]);
+        if p.ptype != 0 { continue; }
+
+        let w = falloff(length(d.position - p.position));
+        fluid_neighbours += 1u;
+        fluid_velocity += w * p.velocity;
+        weight_sum += w;
+    }
+    if weight_sum > 0.0 {
+        fluid_velocity /= weight_sum;
+    }
+
+    let dt = uniforms.time_step;
+    if fluid_neighbours < SPRAY_NEIGHBOURS {
+        d.kind = DIFFUSE_SPRAY;
+        d.velocity += dt * external_acceleration(d.position, d.velocity, uniforms.time);
+    } else if fluid_neighbours > BUBBLE_NEIGHBOURS {
+        d.kind = DIFFUSE_BUBBLE;
+        d.velocity += -BUOYANCY * dt * force_fields.gravity + BUBBLE_DRAG * (fluid_velocity - d.velocity);
+    } else {
+        d.kind = DIFFUSE_FOAM;
+        d.velocity = fluid_velocity;
+    }
+    let position = clamp(
+        to_container_space(d.position + dt * d.velocity),
+        world.container.lower,
+        world.container.upper,
+    );
+    d.position = from_container_space(position);
+    d.lifetime = max(d.lifetime - dt, 0.0);
+
+    diffuse[id] = d;
+}
+
+// sum of |v_ab| (1 - cos) over the `count` gathered neighbours, large where streams collide
+fn trapped_air_potential(pi: u32, count: u32) -> f32 {
+    let p = load_particle(pi);
+    var potential = 0.0;
+    for (var k = 0u; k < count; k += 1u) {
+        let p_other = load_particle(neighbours[k]);
+        let x_ab = p.position - p_other.position;
+        let v_ab = p.velocity - p_other.velocity;
+        let r_ab = length(x_ab);
+        let speed = length(v_ab);
+        if r_ab < world.dh && r_ab > 1e-6 && speed > 1e-6 {
+            potential += speed * (1.0 - dot(v_ab / speed, x_ab / r_ab)) * falloff(r_ab);
+        }
+    }
+    return potential;
+}
+
+// curvature of convex surface regions moving along their normal, over the `count`
+// gathered neighbours
+fn wave_crest_potential(pi: u32, count: u32) -> f32 {
+    let p = load_particle(pi);
+    let n = normals[pi];
+    // interior particles have no meaningful normal
+    if n.w < 0.5 * world.dx { return 0.0; }
+
+    let speed = length(p.velocity);
+    if speed < 1e-6 || dot(p.velocity / speed, n.xyz) < 0.6 { return 0.0; }
+
+    var curvature = 0.0;
+    for (var k = 0u; k < count; k += 1u) {
+        let pj = neighbours[k];
+        let x_ba = particle_position(pj) - p.position;
+        let r_ba = length(x_ba);
+        if r_ba < world.dh && r_ba > 1e-6 && dot(x_ba / r_ba, n.xyz) < 0.0 {
+            curvature += (1.0 - dot(n.xyz, normals[pj].xyz)) * falloff(r_ba);
+        }
+    }
+    return curvature;
+}
+
+// radially symmetric weight of the paper, 1 at the centre and 0 at the support radius
+fn falloff(r: f32) -> f32 {
+    return max(1.0 - r / world.dh, 0.0);
+}
+
+fn clamp_potential(value: f32, range: vec2<f32>) -> f32 {
+    return (clamp(value, range.x, range.y) - range.x) / (range.y - range.x);
+}
+
+// pcg hash
+fn hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// uniform in [0, 1)
+fn random(state: ptr<function, u32>) -> f32 {
+    *state = hash(*state);
+    return f32(*state >> 8u) / 16777216.0;
+}
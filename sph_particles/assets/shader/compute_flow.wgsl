@@ -0,0 +1,146 @@
+// the inlets and outlets of flow.rs on the GPU. flow_rank_main numbers the live particles
+// that stay inside the domain, keeping their order, flow_scatter_main then moves the
+// survivors of one particle buffer to their number on its second side and appends the
+// inlet particles after them. the particles are plain words here, so every buffer of
+// every layout goes through the same passes, the CPU copies the second sides back
+
+struct FlowUniforms {
+    lower: vec3<f32>,
+    outlets: u32, // bit i for an outlet on face i, see flow.rs outlet_mask
+    upper: vec3<f32>,
+    num_new: u32, // inlet particles uploaded
+    words: u32, // u32s per particle of this buffer
+    inlet_offset: u32, // u32s before this buffer's particles in `inlet`
+    row_stride: u32, // particles per row of the dispatch grid
+    _pad: u32,
+};
+
+// the particle count buffer, the solver binds `alive` as its `particle_count`
+struct ParticleCount {
+    alive: u32,
+    previous: u32, // alive before flow_rank_main
+    survivors: u32,
+    _pad: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: FlowUniforms;
+
+@group(0) @binding(1)
+var<storage, read> particles_in: array<u32>;
+
+@group(0) @binding(2)
+var<storage, read_write> particles_out: array<u32>;
+
+@group(0) @binding(3)
+var<storage, read> inlet: array<u32>;
+
+// the number of every live particle, one per slot of the buffers
+@group(0) @binding(4)
+var<storage, read_write> ranks: array<u32>;
+
+@group(0) @binding(5)
+var<storage, read_write> count: ParticleCount;
+
+const workgroup_size_x: u32 = 256u;
+
+// the rank of a particle that left
+const DEAD: u32 = 0xffffffffu;
+
+var<workgroup> kept_before: array<u32, workgroup_size_x>;
+
+// past an outlet, the position is the first three words in every layout
+fn has_left(id: u32) -> bool {
+    let base = id * uniforms.words;
+    let position = bitcast<vec3<f32>>(
+        vec3<u32>(particles_in[base], particles_in[base + 1u], particles_in[base + 2u])
+    );
+    for (var face = 0u; face < 6u; face += 1u) {
+        if (uniforms.outlets & (1u << face)) == 0u {
+            continue;
+        }
+        // the even faces are the lower ones
+        let axis = face / 2u;
+        if face % 2u == 0u && position[axis] < uniforms.lower[axis] {
+            return true;
+        }
+        if face % 2u == 1u && position[axis] > uniforms.upper[axis] {
+            return true;
+        }
+    }
+    return false;
+}
+
+// a single workgroup, every thread ranks a run of consecutive particles
+@compute
+@workgroup_size(workgroup_size_x, 1, 1)
+fn flow_rank_main(
+    @builtin(local_invocation_id) lid: vec3<u32>,
+) {
+    let alive = count.alive;
+    let run = (alive + workgroup_size_x - 1u) / workgroup_size_x;
+    let start = min(lid.x * run, alive);
+    let end = min(start + run, alive);
+
+    var kept = 0u;
+    for (var id = start; id < end; id += 1u) {
+        kept += u32(!has_left(id));
+    }
+
+    // inclusive prefix sum over the threads
+    kept_before[lid.x] = kept;
+    workgroupBarrier();
+    for (var offset = 1u; offset < workgroup_size_x; offset *= 2u) {
+        var sum = kept_before[lid.x];
+        if lid.x >= offset {
+            sum += kept_before[lid.x - offset];
+        }
+        workgroupBarrier();
+        kept_before[lid.x] = sum;
+        workgroupBarrier();
+    }
+
+    var rank = kept_before[lid.x] - kept;
+    for (var id = start; id < end; id += 1u) {
+        if has_left(id) {
+            ranks[id] = DEAD;
+        } else {
+            ranks[id] = rank;
+            rank += 1u;
+        }
+    }
+
+    // every thread read the count before the barriers, the inlets stop at the capacity
+    if lid.x == workgroup_size_x - 1u {
+        let survivors = kept_before[lid.x];
+        count.previous = alive;
+        count.survivors = survivors;
+        count.alive = survivors + min(uniforms.num_new, arrayLength(&ranks) - survivors);
+    }
+}
+
+@compute
+@workgroup_size(workgroup_size_x, 1, 1)
+fn flow_scatter_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    // the dispatch grid is filled row by row, see gpu_pass.rs workgroup_grid
+    let id = gid.x + gid.y * uniforms.row_stride;
+    let words = uniforms.words;
+
+    if id < count.previous && ranks[id] != DEAD {
+        let src = id * words;
+        let dst = ranks[id] * words;
+        for (var k = 0u; k < words; k += 1u) {
+            particles_out[dst + k] = particles_in[src + k];
+        }
+    }
+
+    if id < count.alive - count.survivors {
+        let src = uniforms.inlet_offset + id * words;
+        let dst = (count.survivors + id) * words;
+        for (var k = 0u; k < words; k += 1u) {
+            particles_out[dst + k] = inlet[src + k];
+        }
+    }
+}
@@ -1,54 +1,59 @@
-//!include world.h.wgsl math.h.wgsl particle.h.wgsl
-
-const GRAVITY: vec3<f32> = vec3<f32>(0.0, -9.8, 0.0);
-const time_step: f32 = 0.05;
+//!include world.h.wgsl math.h.wgsl particle.h.wgsl particle_aos.h.wgsl neighbors_all.h.wgsl scratch.h.wgsl sph.h.wgsl forces.h.wgsl interaction.h.wgsl velocity_filters.h.wgsl viscosity.h.wgsl heat.h.wgsl scalar.h.wgsl elastic.h.wgsl obstacle.h.wgsl flow.h.wgsl integrator.h.wgsl
 
 struct Uniforms {
-    dt: f32,
+    time_step: f32,
+    stiffness: f32, // pressure constant
+    viscosity: f32,
+    vorticity: f32, // vorticity confinement coefficient
+    mouse: MouseInteraction,
+    xsph: f32, // XSPH smoothing coefficient
+    row_stride: u32, // particles per row of the dispatch grid
+    time: f32, // seconds simulated at the start of the substep
+    integrator: u32, // INTEGRATOR_*
+    relaxing: u32, // 1 in the relaxation steps, always with the symplectic Euler
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
 };
 
-@group(0) @binding(0)
-var<storage, read_write> particles_in: array<SphParticle>;
-
-@group(1) @binding(0)
-var<storage, read_write> particles_out: array<SphParticle>;
-
 @group(2) @binding(0)
 var<uniform> uniforms: Uniforms;
 
 @group(3) @binding(0)
 var<uniform> world: WorldUniforms;
 
+@group(3) @binding(3)
+var<uniform> force_fields: ForceFields;
+
 const workgroup_size_x: u32 = 256;
 
-fn get_particle_id(gid: vec3<u32>, num_workgroups: vec3<u32>) -> u32 {
-    return gid.x + gid.y * workgroup_size_x * num_workgroups.x;
+// the dispatch grid is filled row by row, see gpu_pass.rs workgroup_grid
+fn get_particle_id(gid: vec3<u32>) -> u32 {
+    return gid.x + gid.y * uniforms.row_stride;
 }
 
 @compute
 @workgroup_size(workgroup_size_x, 1, 1)
 fn compute_density_main(
     @builtin(global_invocation_id) gid: vec3<u32>,
-    @builtin(num_workgroups) num_workgroups: vec3<u32>,
 ) {
-    let id = get_particle_id(gid, num_workgroups);
-    if id >= arrayLength(&particles_in) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
         return;
     }
 
     let p_next = calc_density(id);
 
-    particles_out[id] = p_next;
+    store_particle(id, p_next);
 }
 
 @compute
 @workgroup_size(workgroup_size_x, 1, 1)
 fn compute_non_pressure_main(
     @builtin(global_invocation_id) gid: vec3<u32>,
-    @builtin(num_workgroups) num_workgroups: vec3<u32>,
 ) {
-    let id = get_particle_id(gid, num_workgroups);
-    if id >= arrayLength(&particles_in) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
         return;
     }
 
@@ -56,55 +61,52 @@ fn compute_non_pressure_main(
     // update pressure, get prepared for pressure force
     p_next = update_pressure(p_next);
 
-    particles_out[id] = p_next;
+    store_particle(id, p_next);
 }
 
 @compute
 @workgroup_size(workgroup_size_x, 1, 1)
 fn compute_pressure_main(
     @builtin(global_invocation_id) gid: vec3<u32>,
-    @builtin(num_workgroups) num_workgroups: vec3<u32>,
 ) {
-    let id = get_particle_id(gid, num_workgroups);
-    if id >= arrayLength(&particles_in) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
         return;
     }
 
     var p_next = calc_pressure_force(id);
 
-    particles_out[id] = p_next;
+    store_particle(id, p_next);
 }
 
 @compute
 @workgroup_size(workgroup_size_x, 1, 1)
 fn advect_main(
     @builtin(global_invocation_id) gid: vec3<u32>,
-    @builtin(num_workgroups) num_workgroups: vec3<u32>,
 ) {
-    let id = get_particle_id(gid, num_workgroups);
-    if id >= arrayLength(&particles_in) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
         return;
     }
 
     var p_next = advect(id);
 
-    particles_out[id] = p_next;
+    store_particle(id, p_next);
 }
 
 @compute
 @workgroup_size(workgroup_size_x, 1, 1)
 fn empty_copy_main(
     @builtin(global_invocation_id) gid: vec3<u32>,
-    @builtin(num_workgroups) num_workgroups: vec3<u32>,
 ) {
-    let id = get_particle_id(gid, num_workgroups);
-    if id >= arrayLength(&particles_in) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
         return;
     }
 
-    var p_next = particles_in[id];
+    var p_next = load_particle(id);
 
-    particles_out[id] = p_next;
+    store_particle(id, p_next);
 }
 
 
@@ -112,17 +114,8 @@ fn empty_copy_main(
 //  WCSPH implementation
 
 const dim = 2.0; // dimension
-const rho_0 = 1000.0; // reference density
-const viscosity: f32 = 0.05;
 const c_s: f32 = 100.0;
 const gamma: f32 = 7.0;
-const stiffness: f32 = 50.0;//rho_0 * c_s * c_s / gamma; // pressure constant
-
-// particle volume 2D
-fn get_m_V() -> f32 {
-    let diameter = 2.0 * world.dx;
-    return 0.8 * diameter * diameter;
-}
 
 fn density_kernel(r: vec3<f32>, h: f32) -> f32 {
     return cubicKernel2D(r, h);
@@ -134,17 +127,17 @@ fn density_grad(r: vec3<f32>, h: f32) -> vec3<f32> {
 
 
 fn calc_density(pi: u32) -> SphParticle {
-    let p_in = particles_in[pi];
+    let p_in = load_particle(pi);
     var p_out = p_in;
 
     let m_V = get_m_V();
 
     p_out.density = 0.0;
-    for (var pj: u32 = 0; pj < arrayLength(&particles_in); pj += 1u) {
+    for (var n: u32 = 0; n < neighbor_count(pi); n += 1u) {
+        let pj = neighbor_at(pi, n);
         if pj == pi { continue; }
 
-        let p_other: SphParticle = particles_in[pj];
-        let x_ij = p_in.position - p_other.position;
+        let x_ij = p_in.position - particle_position(pj);
 
         if length(x_ij) < world.dh {
             p_out.density += m_V * density_kernel(x_ij, world.dh);
@@ -156,7 +149,7 @@ fn calc_density(pi: u32) -> SphParticle {
 }
 
 fn calc_non_pressure_force(pi: u32) -> SphParticle {
-    let p_in = particles_in[pi];
+    let p_in = load_particle(pi);
     var p_out = p_in;
 
     let m_V = get_m_V();
@@ -164,22 +157,24 @@ fn calc_non_pressure_force(pi: u32) -> SphParticle {
     if p_in.ptype != 0 {return p_out;}
 
     var dv = vec3<f32>(0.0);
-    dv += GRAVITY;
+    // in the plane of the particles
+    dv += external_acceleration(p_in.position, p_in.velocity, uniforms.time) * vec3<f32>(1.0, 1.0, 0.0);
+    dv += mouse_force(uniforms.mouse, p_in.position);
 
-    for (var pj: u32 = 0; pj < arrayLength(&particles_in); pj += 1u) {
+    for (var n: u32 = 0; n < neighbor_count(pi); n += 1u) {
+        let pj = neighbor_at(pi, n);
         if pj == pi { continue; }
 
-        let p_other = particles_in[pj];
-        let x_ab = p_in.position - p_other.position;
-        let v_ab = p_in.velocity - p_other.velocity;
+        let x_ab = p_in.position - particle_position(pj);
+        let v_ab = p_in.velocity - particle_velocity(pj);
         let r_ab = length(x_ab);
         if r_ab < world.dh {
             let v_dot_x: f32 = dot(v_ab, x_ab);
-            dv += 2.0 * (dim + 2.0) * viscosity * ((m_V * rho_0) / p_in.density) * v_dot_x / (r_ab * r_ab + 0.01 * world.dh * world.dh) * density_grad(x_ab, world.dh);
+            dv += 2.0 * (dim + 2.0) * uniforms.viscosity * ((m_V * rho_0) / p_in.density) * v_dot_x / (r_ab * r_ab + 0.01 * world.dh * world.dh) * density_grad(x_ab, world.dh);
         }
     }
 
-    p_out.velocity += time_step * dv;
+    p_out.velocity += uniforms.time_step * dv;
     return p_out;
 }
 
@@ -188,12 +183,12 @@ fn update_pressure(p_in: SphParticle) -> SphParticle {
     var p_out = p_in;
     // hard coded free surface solution
     p_out.density = max(p_in.density, rho_0);
-    p_out.pressure = stiffness * (pow(p_out.density / rho_0, gamma) - 1.0);
+    p_out.pressure = uniforms.stiffness * (pow(p_out.density / rho_0, gamma) - 1.0);
     return p_out;
 }
 
 fn calc_pressure_force(pi: u32) -> SphParticle {
-    let p_in = particles_in[pi];
+    let p_in = load_particle(pi);
     var p_out = p_in;
 
     let m_V = get_m_V();
@@ -202,73 +197,80 @@ fn calc_pressure_force(pi: u32) -> SphParticle {
 
     var dv = vec3<f32>(0.0);
 
-    for (var pj: u32 = 0; pj < arrayLength(&particles_in); pj += 1u) {
+    for (var n: u32 = 0; n < neighbor_count(pi); n += 1u) {
+        let pj = neighbor_at(pi, n);
         if pj == pi { continue; }
 
-        let p_other = particles_in[pj];
-        let x_ab = p_in.position - p_other.position;
-        let v_ab = p_in.velocity - p_other.velocity;
+        let x_ab = p_in.position - particle_position(pj);
         let r_ab = length(x_ab);
         if r_ab < world.dh {
             let Pa = p_in.pressure;
-            let Pb = p_other.pressure;
+            let Pb = particle_pressure(pj);
             let rho_a = p_in.density;
-            let rho_b = p_other.density;
+            let rho_b = particle_density(pj);
 
             dv += - rho_0 * m_V * (Pa / (rho_a * rho_a) + Pb / (rho_b * rho_b)) * density_grad(x_ab, world.dh);
         }
     }
 
-    p_out.velocity += time_step * dv;
+    p_out.velocity += uniforms.time_step * dv;
 
     return p_out;
 }
 
 fn advect(pi: u32) -> SphParticle {
-    let p_in = particles_in[pi];
-    var p_out = p_in;
+    let p_in = load_particle(pi);
+    if p_in.ptype != 0 { return p_in; }
 
-    if p_in.ptype != 0 { return p_out; }
-
-    p_out.position += p_in.velocity * time_step;
-    p_out = solve_boundary_constraints(p_out);
-    return p_out;
+    return integrate(pi, p_in);
 }
 
 // end WCSPH
 // =========================================================
 
 fn solve_boundary_constraints(p_in: SphParticle) -> SphParticle {
-    // implement the native boundary constraint that remove the perpendicular velocity
+    // clamp to the container walls and reflect the velocity relative to the wall,
+    // so a moving container pushes the particles along. then the inlet and outlet buffer
+    // zones, and out of the obstacles
     var p_out: SphParticle = p_in;
-    var vel = p_in.velocity;
 
     if p_in.ptype == 1 { return p_out; }
 
-
-    let c_f: f32 = 0.3; // collision factor
-
-    if p_in.position.x < world.boundary_lower.x {
-        p_out.position.x = world.boundary_lower.x;
-        vel.x = (c_f - 1.0) * vel.x;
-        p_out.velocity = vel;
-    }
-    if p_in.position.x > world.boundary_upper.x {
-        p_out.position.x = world.boundary_upper.x;
-        vel.x = (c_f - 1.0) * vel.x;
-        p_out.velocity = vel;
-    }
-    if p_in.position.y < world.boundary_lower.y {
-        p_out.position.y = world.boundary_lower.y;
-        vel.y = (c_f - 1.0) * vel.y;
-        p_out.velocity = vel;
-    }
-    if p_in.position.y > world.boundary_upper.y {
-        p_out.position.y = world.boundary_upper.y;
-        vel.y = (c_f - 1.0) * vel.y;
-        p_out.velocity = vel;
+    // collision factor, grains don't bounce off the walls
+    let c_f: f32 = select(0.3, 1.0, materials[p_in.material].friction > 0.0);
+
+    let rotation = world.container.rotation;
+    let wall_velocity = container_velocity(p_in.position);
+    var position = to_container_space(p_in.position);
+    var vel = transpose(rotation) * (p_in.velocity - wall_velocity);
+    var collided = false;
+    var impulse = vec3<f32>(0.0);
+
+    // the x and y walls
+    for (var axis = 0; axis < 2; axis += 1) {
+        if position[axis] < world.container.lower[axis] {
+            let wall = world.container.lower_velocity[axis];
+            position[axis] = world.container.lower[axis];
+            let v_axis = wall + (c_f - 1.0) * (vel[axis] - wall);
+            impulse[axis] = abs(v_axis - vel[axis]);
+            vel[axis] = v_axis;
+            collided = true;
+        }
+        if position[axis] > world.container.upper[axis] {
+            let wall = world.container.upper_velocity[axis];
+            position[axis] = world.container.upper[axis];
+            let v_axis = wall + (c_f - 1.0) * (vel[axis] - wall);
+            impulse[axis] = abs(v_axis - vel[axis]);
+            vel[axis] = v_axis;
+            collided = true;
+        }
     }
 
-    return p_out;
+    if collided {
+        vel = wall_friction(materials[p_in.material].friction, vel, impulse);
+        p_out.position = from_container_space(position);
+        p_out.velocity = rotation * vel + wall_velocity;
+    }
+    return collide_obstacles(apply_flow_boundaries(p_out, true), true);
 }
 
@@ -0,0 +1,127 @@
+//!include world.h.wgsl particle.h.wgsl particle_view.h.wgsl sph.h.wgsl forces.h.wgsl
+
+// compute_pass_reduction.rs SimDiagnosticsRaw
+struct Diagnostics {
+    momentum: vec3<f32>,
+    kinetic_energy: f32,
+    potential_energy: f32,
+    max_velocity: f32,
+    density_error_sum: f32,
+    max_density_error: f32,
+    fluid_count: f32,
+    _pad: array<f32, 3>,
+}
+
+@group(1) @binding(0)
+var<uniform> world: WorldUniforms;
+
+@group(1) @binding(3)
+var<uniform> force_fields: ForceFields;
+
+// one entry per workgroup of the first stage
+@group(2) @binding(0)
+var<storage, read_write> partials: array<Diagnostics>;
+
+@group(2) @binding(1)
+var<storage, read_write> result: Diagnostics;
+
+const workgroup_size_x: u32 = 256;
+
+var<workgroup> scratch: array<Diagnostics, workgroup_size_x>;
+
+fn zero_diagnostics() -> Diagnostics {
+    var d: Diagnostics;
+    d.momentum = vec3<f32>(0.0);
+    d.kinetic_energy = 0.0;
+    d.potential_energy = 0.0;
+    d.max_velocity = 0.0;
+    d.density_error_sum = 0.0;
+    d.max_density_error = 0.0;
+    d.fluid_count = 0.0;
+    return d;
+}
+
+fn particle_diagnostics(p: SphParticle) -> Diagnostics {
+    var d = zero_diagnostics();
+    if p.ptype != 0 { return d; }
+
+    let mass = get_m_V() * rho_0;
+    let speed = length(p.velocity);
+    let density_error = abs(p.density - rho_0) / rho_0;
+
+    d.momentum = mass * p.velocity;
+    d.kinetic_energy = 0.5 * mass * speed * speed;
+    // measured from the bottom of the domain so it stays positive
+    d.potential_energy = -mass * dot(force_fields.gravity, p.position - world.boundary_lower);
+    d.max_velocity = speed;
+    d.density_error_sum = density_error;
+    d.max_density_error = density_error;
+    d.fluid_count = 1.0;
+    return d;
+}
+
+fn combine(a: Diagnostics, b: Diagnostics) -> Diagnostics {
+    var d: Diagnostics;
+    d.momentum = a.momentum + b.momentum;
+    d.kinetic_energy = a.kinetic_energy + b.kinetic_energy;
+    d.potential_energy = a.potential_energy + b.potential_energy;
+    d.max_velocity = max(a.max_velocity, b.max_velocity);
+    d.density_error_sum = a.density_error_sum + b.density_error_sum;
+    d.max_density_error = max(a.max_density_error, b.max_density_error);
+    d.fluid_count = a.fluid_count + b.fluid_count;
+    return d;
+}
+
+// tree reduction of `scratch`, the result ends up in scratch[0]
+fn reduce_scratch(lid: u32) {
+    workgroupBarrier();
+    for (var stride: u32 = workgroup_size_x / 2u; stride > 0u; stride >>= 1u) {
+        if lid < stride {
+            scratch[lid] = combine(scratch[lid], scratch[lid + stride]);
+        }
+        workgroupBarrier();
+    }
+}
+
+// stage 1: every workgroup reduces its own slice of particles
+@compute
+@workgroup_size(workgroup_size_x, 1, 1)
+fn reduce_particles_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(local_invocation_id) lid: vec3<u32>,
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    // the dispatch grid is filled row by row, see gpu_pass.rs workgroup_grid
+    let id = gid.x + gid.y * workgroup_size_x * num_workgroups.x;
+    var d = zero_diagnostics();
+    if id < num_particles() {
+        d = particle_diagnostics(load_particle(id));
+    }
+    scratch[lid.x] = d;
+
+    reduce_scratch(lid.x);
+
+    if lid.x == 0u {
+        partials[wid.x + wid.y * num_workgroups.x] = scratch[0];
+    }
+}
+
+// stage 2: a single workgroup folds all partial results
+@compute
+@workgroup_size(workgroup_size_x, 1, 1)
+fn reduce_partials_main(
+    @builtin(local_invocation_id) lid: vec3<u32>,
+) {
+    var d = zero_diagnostics();
+    for (var i: u32 = lid.x; i < arrayLength(&partials); i += workgroup_size_x) {
+        d = combine(d, partials[i]);
+    }
+    scratch[lid.x] = d;
+
+    reduce_scratch(lid.x);
+
+    if lid.x == 0u {
+        result = scratch[0];
+    }
+}
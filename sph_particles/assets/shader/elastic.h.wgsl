@@ -0,0 +1,146 @@
+// file: elastic.h
+// corotated SPH elasticity (Becker et al. 2009): the deformation gradient of every elastic
+// particle comes from the displacements of its rest shape neighbours, elastic.rs, the
+// rotation is taken out so only the strain stretches the particles back into shape.
+// the particles stay in the SPH sums, so solids and fluid push each other through the pressure.
+// the includer must declare the particle accessors (particle_aos.h.wgsl), `uniforms`,
+// `materials` (viscosity.h.wgsl) and `get_particle_id`
+
+// elastic.rs ElasticParticleRaw
+struct ElasticParticle {
+    rest_position: vec3<f32>,
+    first_neighbor: u32,
+    num_neighbors: u32, // 0 for the particles of fluid blocks
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+    rotation: vec4<f32>, // quaternion, warm starts the next rotation extraction
+    stress: mat3x3<f32>, // first Piola-Kirchhoff
+};
+
+// elastic.rs ElasticNeighborRaw
+struct ElasticNeighbor {
+    gradient: vec3<f32>, // corrected rest kernel gradient towards the neighbour
+    index: u32,
+    neighbor_gradient: vec3<f32>, // the neighbour's, towards this particle
+    _pad: f32,
+};
+
+@group(3) @binding(4)
+var<storage, read_write> elastic_particles: array<ElasticParticle>;
+
+@group(3) @binding(5)
+var<storage, read> elastic_neighbors: array<ElasticNeighbor>;
+
+const ELASTIC_ROTATION_ITERATIONS: u32 = 8u;
+
+@compute
+@workgroup_size(256, 1, 1)
+fn elastic_stress_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
+        return;
+    }
+
+    let p_in = load_particle(id);
+    if p_in.ptype == 0 && elastic_particles[id].num_neighbors > 0u {
+        update_elastic_stress(id, p_in);
+    }
+    store_particle(id, p_in);
+}
+
+@compute
+@workgroup_size(256, 1, 1)
+fn elastic_force_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
+        return;
+    }
+
+    store_particle(id, apply_elastic_force(id));
+}
+
+fn update_elastic_stress(pi: u32, p_in: SphParticle) {
+    let elastic = elastic_particles[pi];
+    let material = materials[p_in.material];
+
+    // displacement gradient from the rest shape
+    let u_a = p_in.position - elastic.rest_position;
+    var deformation = mat3x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0));
+    for (var n = elastic.first_neighbor; n < elastic.first_neighbor + elastic.num_neighbors; n += 1u) {
+        let neighbor = elastic_neighbors[n];
+        let u_ab = particle_position(neighbor.index) - elastic_particles[neighbor.index].rest_position - u_a;
+        let g = neighbor.gradient;
+        deformation += mat3x3<f32>(u_ab * g.x, u_ab * g.y, u_ab * g.z);
+    }
+
+    let rotation = extract_rotation(deformation, elastic.rotation);
+    let r = quat_to_mat3(rotation);
+
+    // linear strain of the unrotated deformation
+    let unrotated = transpose(r) * deformation;
+    let identity = mat3x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0));
+    let strain = 0.5 * (unrotated + transpose(unrotated)) - identity;
+    let trace = strain[0][0] + strain[1][1] + strain[2][2];
+    let stress = 2.0 * material.shear_modulus * strain + material.lame_lambda * trace * identity;
+
+    elastic_particles[pi].rotation = rotation;
+    elastic_particles[pi].stress = r * stress;
+}
+
+// the force of the elastic energy, from the stress of the particle and of its neighbours
+fn apply_elastic_force(pi: u32) -> SphParticle {
+    let p_in = load_particle(pi);
+    var p_out = p_in;
+    let elastic = elastic_particles[pi];
+
+    if p_in.ptype != 0 || elastic.num_neighbors == 0u { return p_out; }
+
+    var force = vec3<f32>(0.0);
+    for (var n = elastic.first_neighbor; n < elastic.first_neighbor + elastic.num_neighbors; n += 1u) {
+        let neighbor = elastic_neighbors[n];
+        force += elastic.stress * neighbor.gradient
+            - elastic_particles[neighbor.index].stress * neighbor.neighbor_gradient;
+    }
+
+    p_out.velocity += uniforms.time_step * force / rho_0;
+    return p_out;
+}
+
+// rotational part of `a`, iterating from `q` (Müller et al. 2016, "A robust method to
+// extract the rotational part of deformations")
+fn extract_rotation(a: mat3x3<f32>, q_start: vec4<f32>) -> vec4<f32> {
+    var q = q_start;
+    for (var i = 0u; i < ELASTIC_ROTATION_ITERATIONS; i += 1u) {
+        let r = quat_to_mat3(q);
+        let omega = (cross(r[0], a[0]) + cross(r[1], a[1]) + cross(r[2], a[2]))
+            / (abs(dot(r[0], a[0]) + dot(r[1], a[1]) + dot(r[2], a[2])) + 1e-9);
+        let w = length(omega);
+        if w < 1e-9 {
+            break;
+        }
+        let axis_angle = vec4<f32>(sin(0.5 * w) * omega / w, cos(0.5 * w));
+        q = normalize(quat_mul(axis_angle, q));
+    }
+    return q;
+}
+
+fn quat_mul(a: vec4<f32>, b: vec4<f32>) -> vec4<f32> {
+    return vec4<f32>(a.w * b.xyz + b.w * a.xyz + cross(a.xyz, b.xyz), a.w * b.w - dot(a.xyz, b.xyz));
+}
+
+fn quat_to_mat3(q: vec4<f32>) -> mat3x3<f32> {
+    let x = q.x;
+    let y = q.y;
+    let z = q.z;
+    let w = q.w;
+    return mat3x3<f32>(
+        vec3<f32>(1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + z * w), 2.0 * (x * z - y * w)),
+        vec3<f32>(2.0 * (x * y - z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + x * w)),
+        vec3<f32>(2.0 * (x * z + y * w), 2.0 * (y * z - x * w), 1.0 - 2.0 * (x * x + y * y)),
+    );
+}
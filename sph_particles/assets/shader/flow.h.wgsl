@@ -0,0 +1,56 @@
+// file: flow.h
+// buffer zones of the inlets and outlets of flow.rs, one support radius deep inside their
+// face. inlet particles move with the inlet's velocity profile until they leave the zone,
+// outlet particles can't turn back into the domain, so pressure waves don't reflect off
+// the open faces. the particles themselves are added and deleted between frames.
+// the includer must declare `world` and the SphParticle struct
+
+const FLOW_INLET: u32 = 1u;
+const FLOW_OUTLET: u32 = 2u;
+const FLOW_PROFILE_PARABOLIC: u32 = 1u;
+
+// `planar` ignores z, the particles of the planar solver only move in x and y
+fn apply_flow_boundaries(p_in: SphParticle, planar: bool) -> SphParticle {
+    var p_out = p_in;
+    for (var i = 0u; i < 6u; i += 1u) {
+        let face = world.flow[i];
+        if face.kind == 0u {
+            continue;
+        }
+
+        // the even faces are the lower ones, their normal into the domain points along
+        // the axis
+        let axis = i / 2u;
+        let upper_face = i % 2u == 1u;
+        let inward = select(1.0, -1.0, upper_face);
+        let face_position =
+            select(world.boundary_lower[axis], world.boundary_upper[axis], upper_face);
+        let depth = inward * (p_out.position[axis] - face_position);
+        if depth > world.dh {
+            continue;
+        }
+
+        if face.kind == FLOW_INLET {
+            var inside = true;
+            var scale = 1.0;
+            for (var t = 0u; t < 3u; t += 1u) {
+                if t == axis || (planar && t == 2u) {
+                    continue;
+                }
+                let s = (p_out.position[t] - face.lower[t]) / (face.upper[t] - face.lower[t]);
+                inside = inside && s >= 0.0 && s <= 1.0;
+                if face.profile == FLOW_PROFILE_PARABOLIC {
+                    scale *= 4.0 * s * (1.0 - s);
+                }
+            }
+            if inside {
+                var velocity = vec3<f32>(0.0);
+                velocity[axis] = inward * face.speed * scale;
+                p_out.velocity = velocity;
+            }
+        } else if face.kind == FLOW_OUTLET && inward * p_out.velocity[axis] > 0.0 {
+            p_out.velocity[axis] = 0.0;
+        }
+    }
+    return p_out;
+}
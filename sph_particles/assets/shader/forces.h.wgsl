@@ -0,0 +1,110 @@
+// file: forces.h
+// gravity and the scene's external force fields, see force_field.rs.
+// the includer must declare `force_fields: ForceFields` (world bind group, binding 3)
+
+const MAX_FORCE_FIELDS: u32 = 16;
+const FORCE_TAU: f32 = 6.2831853;
+
+const FORCE_ATTRACTOR: u32 = 0;
+const FORCE_VORTEX: u32 = 1;
+const FORCE_WIND: u32 = 2;
+const FORCE_TURBULENCE: u32 = 3;
+
+// force_field.rs ForceFieldRaw
+struct ForceField {
+    center: vec3<f32>,
+    kind: u32,
+    direction: vec3<f32>, // vortex axis or wind velocity
+    strength: f32,
+    sweep: vec3<f32>, // amplitude of the centre's motion
+    sweep_frequency: f32,
+    radius: f32, // negative for the whole domain
+    falloff: f32,
+    pulse_amplitude: f32,
+    pulse_frequency: f32,
+    scale: f32, // turbulence feature size
+};
+
+struct ForceFields {
+    gravity: vec3<f32>,
+    count: u32,
+    fields: array<ForceField, MAX_FORCE_FIELDS>,
+};
+
+// acceleration at `position` `time` seconds into the simulation
+fn external_acceleration(position: vec3<f32>, velocity: vec3<f32>, time: f32) -> vec3<f32> {
+    var acceleration = force_fields.gravity;
+    for (var i: u32 = 0; i < min(force_fields.count, MAX_FORCE_FIELDS); i += 1u) {
+        acceleration += force_field_acceleration(force_fields.fields[i], position, velocity, time);
+    }
+    return acceleration;
+}
+
+fn force_field_acceleration(field: ForceField, position: vec3<f32>, velocity: vec3<f32>, time: f32) -> vec3<f32> {
+    let center = field.center + field.sweep * sin(FORCE_TAU * field.sweep_frequency * time);
+    let strength = field.strength
+        * (1.0 + field.pulse_amplitude * sin(FORCE_TAU * field.pulse_frequency * time))
+        * force_region_weight(field, center, position);
+    if strength == 0.0 {
+        return vec3<f32>(0.0);
+    }
+
+    let r = position - center;
+    if field.kind == FORCE_ATTRACTOR {
+        let r_len = length(r);
+        if r_len < 1e-4 {
+            return vec3<f32>(0.0);
+        }
+        return -strength * r / r_len;
+    } else if field.kind == FORCE_VORTEX {
+        let tangent = cross(field.direction, r);
+        let tangent_len = length(tangent);
+        if tangent_len < 1e-4 {
+            return vec3<f32>(0.0);
+        }
+        return strength * tangent / tangent_len;
+    } else if field.kind == FORCE_WIND {
+        return strength * (field.direction - velocity);
+    } else if field.kind == FORCE_TURBULENCE {
+        // the noise drifts diagonally through the domain
+        let p = position / field.scale + vec3<f32>(0.5 * time);
+        return strength * force_noise(p);
+    }
+    return vec3<f32>(0.0);
+}
+
+// 1 inside the region, fading to 0 over the `falloff` wide shell at its edge
+fn force_region_weight(field: ForceField, center: vec3<f32>, position: vec3<f32>) -> f32 {
+    if field.radius < 0.0 {
+        return 1.0;
+    }
+    let inside = (field.radius - distance(position, center)) / max(field.falloff, 1e-6);
+    return smoothstep(0.0, 1.0, clamp(inside, 0.0, 1.0));
+}
+
+// smoothly interpolated random vectors on the integer lattice, components in [-1, 1]
+fn force_noise(p: vec3<f32>) -> vec3<f32> {
+    let cell = floor(p);
+    let f = p - cell;
+    let w = f * f * (3.0 - 2.0 * f);
+    let c = vec3<i32>(cell);
+
+    let x00 = mix(force_lattice(c), force_lattice(c + vec3<i32>(1, 0, 0)), w.x);
+    let x10 = mix(force_lattice(c + vec3<i32>(0, 1, 0)), force_lattice(c + vec3<i32>(1, 1, 0)), w.x);
+    let x01 = mix(force_lattice(c + vec3<i32>(0, 0, 1)), force_lattice(c + vec3<i32>(1, 0, 1)), w.x);
+    let x11 = mix(force_lattice(c + vec3<i32>(0, 1, 1)), force_lattice(c + vec3<i32>(1, 1, 1)), w.x);
+    return mix(mix(x00, x10, w.y), mix(x01, x11, w.y), w.z);
+}
+
+fn force_lattice(c: vec3<i32>) -> vec3<f32> {
+    let h = force_hash(bitcast<u32>(c.x) ^ force_hash(bitcast<u32>(c.y) ^ force_hash(bitcast<u32>(c.z))));
+    let bits = vec3<u32>(h, h >> 10u, h >> 20u) & vec3<u32>(1023u);
+    return vec3<f32>(bits) / 511.5 - 1.0;
+}
+
+// pcg hash
+fn force_hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
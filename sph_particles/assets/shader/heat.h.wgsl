@@ -0,0 +1,67 @@
+// file: heat.h
+// heat conduction between particles and relaxation towards the scene's heat sources.
+// boundary particles keep their temperature, so a hot boundary block is a source too.
+// the includer must declare the particle and neighbour accessors (particle_aos.h.wgsl,
+// neighbors_all.h.wgsl), `uniforms`, `world`, `get_particle_id`, `density_grad` and `materials`
+
+// particles.rs HeatSourceRaw
+struct HeatSource {
+    lower: vec3<f32>,
+    temperature: f32,
+    upper: vec3<f32>,
+    rate: f32, // 1/s, 0 for the placeholder of a scene without sources
+};
+
+@group(3) @binding(2)
+var<storage, read> heat_sources: array<HeatSource>;
+
+@compute
+@workgroup_size(256, 1, 1)
+fn heat_conduction_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
+        return;
+    }
+
+    store_particle(id, conduct_heat(id));
+}
+
+// explicit step of dT/dt = div(alpha grad T) plus the sources the particle is in
+fn conduct_heat(pi: u32) -> SphParticle {
+    let p_in = load_particle(pi);
+    var p_out = p_in;
+    let m_V = get_m_V();
+
+    if p_in.ptype != 0 { return p_out; }
+
+    let alpha_a = materials[p_in.material].thermal_diffusivity;
+    var dT = 0.0;
+    for (var n: u32 = 0; n < neighbor_count(pi); n += 1u) {
+        let pj = neighbor_at(pi, n);
+        if pj == pi { continue; }
+
+        let x_ab = p_in.position - particle_position(pj);
+        let r_ab = length(x_ab);
+        if r_ab < world.dh {
+            let V_b = m_V * rho_0 / particle_density(pj);
+            // harmonic mean keeps the flux continuous across materials
+            let alpha_b = materials[particle_material(pj)].thermal_diffusivity;
+            let alpha_ab = 2.0 * alpha_a * alpha_b / max(alpha_a + alpha_b, 1e-12);
+            dT += 2.0 * V_b * alpha_ab * (p_in.temperature - particle_temperature(pj))
+                * dot(x_ab, density_grad(x_ab, world.dh))
+                / (r_ab * r_ab + 0.01 * world.dh * world.dh);
+        }
+    }
+    p_out.temperature += uniforms.time_step * dT;
+
+    for (var s: u32 = 0; s < arrayLength(&heat_sources); s += 1u) {
+        let source = heat_sources[s];
+        if all(p_in.position >= source.lower) && all(p_in.position <= source.upper) {
+            let blend = 1.0 - exp(-source.rate * uniforms.time_step);
+            p_out.temperature = mix(p_out.temperature, source.temperature, blend);
+        }
+    }
+    return p_out;
+}
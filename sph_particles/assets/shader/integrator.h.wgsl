@@ -0,0 +1,111 @@
+// file: integrator.h
+// the time integrators of scene.rs Integrator. the force passes add their accelerations
+// to the velocity as they go, the integrators take the step's acceleration as the change
+// from the velocity integrator_begin_main saved at its start. the relaxation steps at
+// init use the symplectic Euler and zero the velocities after moving the particles.
+// the includer must declare the particle accessors (particle_aos.h.wgsl), `uniforms`,
+// `scratch` (scratch.h.wgsl), `get_particle_id` and `solve_boundary_constraints`
+
+// scene.rs Integrator
+const INTEGRATOR_SYMPLECTIC_EULER: u32 = 0u;
+const INTEGRATOR_LEAPFROG: u32 = 1u;
+const INTEGRATOR_PREDICTOR_CORRECTOR: u32 = 2u;
+
+// the vec4s of a particle's state
+const INTEGRATOR_POSITION: u32 = 0u; // at the start of the step
+const INTEGRATOR_VELOCITY: u32 = 1u; // at the start of the step
+// leapfrog: half a step behind, w is 0 until the first step sets it.
+// predictor-corrector: at the predicted midpoint
+const INTEGRATOR_HALF_VELOCITY: u32 = 2u;
+const INTEGRATOR_STATE_VEC4S: u32 = 3u;
+
+fn integrator_state(id: u32, field: u32) -> u32 {
+    return scratch_index(SCRATCH_INTEGRATOR, INTEGRATOR_STATE_VEC4S * id + field);
+}
+
+@compute
+@workgroup_size(256, 1, 1)
+fn integrator_begin_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
+        return;
+    }
+
+    let p_in = load_particle(id);
+    scratch[integrator_state(id, INTEGRATOR_POSITION)] = vec4<f32>(p_in.position, 0.0);
+    scratch[integrator_state(id, INTEGRATOR_VELOCITY)] = vec4<f32>(p_in.velocity, 0.0);
+    store_particle(id, p_in);
+}
+
+// predictor-corrector: half a step with the forces at the start, the passes after it
+// evaluate the forces at the midpoint
+@compute
+@workgroup_size(256, 1, 1)
+fn predict_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
+        return;
+    }
+
+    let p_in = load_particle(id);
+    var p_out = p_in;
+    if p_in.ptype == 0 {
+        let position = scratch[integrator_state(id, INTEGRATOR_POSITION)].xyz;
+        let velocity = scratch[integrator_state(id, INTEGRATOR_VELOCITY)].xyz;
+        let dv = p_in.velocity - velocity;
+        p_out.position = position + 0.5 * uniforms.time_step * velocity;
+        p_out.velocity = velocity + 0.5 * dv;
+        p_out = solve_boundary_constraints(p_out);
+        scratch[integrator_state(id, INTEGRATOR_HALF_VELOCITY)] = vec4<f32>(p_out.velocity, 0.0);
+    }
+    store_particle(id, p_out);
+}
+
+// the fluid particle `p_in` after the step, inside the container
+fn integrate(pi: u32, p_in: SphParticle) -> SphParticle {
+    var p_out = p_in;
+    let dt = uniforms.time_step;
+
+    switch uniforms.integrator {
+        case INTEGRATOR_LEAPFROG: {
+            let velocity = scratch[integrator_state(pi, INTEGRATOR_VELOCITY)].xyz;
+            let half_velocity = scratch[integrator_state(pi, INTEGRATOR_HALF_VELOCITY)];
+            let dv = p_in.velocity - velocity;
+            // kick the half step velocity by the whole step, the first one only by half
+            var kicked = half_velocity.xyz + dv;
+            if half_velocity.w == 0.0 {
+                kicked = velocity + 0.5 * dv;
+            }
+            p_out.velocity = kicked;
+            p_out.position += kicked * dt;
+            p_out = solve_boundary_constraints(p_out);
+            scratch[integrator_state(pi, INTEGRATOR_HALF_VELOCITY)] = vec4<f32>(p_out.velocity, 1.0);
+            // the closing half kick, with this step's acceleration until the next one is known
+            p_out.velocity += 0.5 * dv;
+            return p_out;
+        }
+        case INTEGRATOR_PREDICTOR_CORRECTOR: {
+            // the whole step with the acceleration at the midpoint
+            let position = scratch[integrator_state(pi, INTEGRATOR_POSITION)].xyz;
+            let velocity = scratch[integrator_state(pi, INTEGRATOR_VELOCITY)].xyz;
+            let half_velocity = scratch[integrator_state(pi, INTEGRATOR_HALF_VELOCITY)];
+            let next_velocity = velocity + p_in.velocity - half_velocity.xyz;
+            p_out.position = position + 0.5 * dt * (velocity + next_velocity);
+            p_out.velocity = next_velocity;
+            return solve_boundary_constraints(p_out);
+        }
+        default: {
+            p_out.position += p_in.velocity * dt;
+            p_out = solve_boundary_constraints(p_out);
+            // the relaxation steps before the start only move the particles
+            if uniforms.relaxing != 0u {
+                p_out.velocity = vec3<f32>(0.0);
+            }
+            return p_out;
+        }
+    }
+}
@@ -0,0 +1,37 @@
+// file: interaction.h
+// mouse tools, the force field is centered on the ray cast from the cursor
+
+// interaction.rs MouseInteractionRaw
+struct MouseInteraction {
+    ray_origin: vec3<f32>,
+    radius: f32,
+    ray_dir: vec3<f32>,
+    strength: f32,
+    tool: u32, // 0 for none, 1 attract, 2 repel, 3 stir
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+const MOUSE_TOOL_ATTRACT: u32 = 1u;
+const MOUSE_TOOL_REPEL: u32 = 2u;
+const MOUSE_TOOL_STIR: u32 = 3u;
+
+// acceleration applied to a particle at `position`, fades out linearly towards `radius`
+fn mouse_force(mouse: MouseInteraction, position: vec3<f32>) -> vec3<f32> {
+    let along_ray = dot(position - mouse.ray_origin, mouse.ray_dir);
+    let offset = position - (mouse.ray_origin + along_ray * mouse.ray_dir);
+    let dist = length(offset);
+    if mouse.tool == 0u || dist >= mouse.radius || dist < 1e-6 {
+        return vec3<f32>(0.0);
+    }
+
+    let radial = offset / dist;
+    let magnitude = mouse.strength * (1.0 - dist / mouse.radius);
+    switch mouse.tool {
+        case MOUSE_TOOL_ATTRACT: { return -magnitude * radial; }
+        case MOUSE_TOOL_REPEL: { return magnitude * radial; }
+        case MOUSE_TOOL_STIR: { return magnitude * cross(mouse.ray_dir, radial); }
+        default: { return vec3<f32>(0.0); }
+    }
+}
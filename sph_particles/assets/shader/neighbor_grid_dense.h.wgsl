@@ -0,0 +1,24 @@
+// file: neighbor_grid_dense.h
+// the cells of neighbors_list.h.wgsl for NeighborSearch::CachedList: the domain's grid,
+// a bucket per cell. particles outside the domain go to its border cells
+
+const GRID_HASHED: bool = false;
+
+fn grid_dims() -> vec3<i32> {
+    let cells = round((world.boundary_upper - world.boundary_lower) / world.dh);
+    return max(vec3<i32>(cells), vec3<i32>(1));
+}
+
+fn grid_cell(position: vec3<f32>) -> vec3<i32> {
+    let cell = vec3<i32>(floor((position - world.boundary_lower) / world.dh));
+    return clamp(cell, vec3<i32>(0), grid_dims() - 1);
+}
+
+fn grid_cell_valid(cell: vec3<i32>) -> bool {
+    return all(cell >= vec3<i32>(0)) && all(cell < grid_dims());
+}
+
+fn grid_bucket(cell: vec3<i32>) -> u32 {
+    let dims = grid_dims();
+    return u32(cell.x + dims.x * (cell.y + dims.y * cell.z));
+}
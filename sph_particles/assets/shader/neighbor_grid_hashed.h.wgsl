@@ -0,0 +1,21 @@
+// file: neighbor_grid_hashed.h
+// the cells of neighbors_list.h.wgsl for NeighborSearch::HashedList: unbounded cells
+// hashed into the buckets of `cell_counts` (Teschner et al. 2003, "Optimized spatial
+// hashing for collision detection of deformable objects"). cells sharing a bucket are
+// told apart by the candidates' positions
+
+const GRID_HASHED: bool = true;
+
+fn grid_cell(position: vec3<f32>) -> vec3<i32> {
+    return vec3<i32>(floor((position - world.boundary_lower) / world.dh));
+}
+
+fn grid_cell_valid(cell: vec3<i32>) -> bool {
+    return true;
+}
+
+fn grid_bucket(cell: vec3<i32>) -> u32 {
+    let c = bitcast<vec3<u32>>(cell);
+    let hash = (c.x * 73856093u) ^ (c.y * 19349663u) ^ (c.z * 83492791u);
+    return hash % arrayLength(&cell_counts);
+}
@@ -0,0 +1,12 @@
+// file: neighbors_all.h
+// neighbour search over all particles, NeighborSearch::AllPairs. the neighbour loops
+// only go through these two functions, neighbors_list.h.wgsl provides the same for the
+// cached lists. self is included, the loops skip it
+
+fn neighbor_count(pi: u32) -> u32 {
+    return num_particles();
+}
+
+fn neighbor_at(pi: u32, n: u32) -> u32 {
+    return n;
+}
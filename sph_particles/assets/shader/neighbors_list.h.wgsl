@@ -0,0 +1,137 @@
+//!include grid.h.wgsl neighbor_grid_dense.h.wgsl
+// file: neighbors_list.h
+// cached neighbour lists, NeighborSearch::CachedList and HashedList. at the start of every
+// substep, and at the midpoint of the predictor-corrector, neighbor_grid_main bins the
+// particles into the support radius wide cells, then neighbor_list_main gathers the
+// particles within the support radius from the 27 cells around each one. the lists are
+// sorted by index so the sums run in the same order as over all particles. both have a
+// fixed capacity, what doesn't fit is counted in `neighbor_stats` (grid.rs NeighborStats)
+// and read back to warn about it.
+// the cells come from neighbor_grid_dense.h.wgsl or neighbor_grid_hashed.h.wgsl.
+// the includer must declare the particle accessors, `world` and `get_particle_id`.
+// the capacities come from grid.h.wgsl, which resources.rs generates from grid.rs
+
+struct NeighborStats {
+    cell_overflow: atomic<u32>,
+    neighbor_overflow: atomic<u32>,
+    candidates: atomic<u32>,
+    hash_collisions: atomic<u32>,
+};
+
+// a counter per cell or bucket, cleared before every build of the lists
+@group(2) @binding(2)
+var<storage, read_write> cell_counts: array<atomic<u32>>;
+
+// MAX_NUM_PARTICLES_PER_CELL slots per cell or bucket
+@group(2) @binding(3)
+var<storage, read_write> cell_particles: array<u32>;
+
+// NEIGHBOR_LIST_STRIDE per particle
+@group(2) @binding(4)
+var<storage, read_write> neighbor_lists: array<u32>;
+
+@group(2) @binding(5)
+var<storage, read_write> neighbor_stats: NeighborStats;
+
+fn neighbor_count(pi: u32) -> u32 {
+    return neighbor_lists[pi * NEIGHBOR_LIST_STRIDE];
+}
+
+fn neighbor_at(pi: u32, n: u32) -> u32 {
+    return neighbor_lists[pi * NEIGHBOR_LIST_STRIDE + 1u + n];
+}
+
+@compute
+@workgroup_size(256, 1, 1)
+fn neighbor_grid_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
+        return;
+    }
+
+    let p_in = load_particle(id);
+    let bucket = grid_bucket(grid_cell(p_in.position));
+    // the slots go to the particles in whatever order the atomics resolve, only the
+    // lists built from them are sorted. a full cell drops different particles from run
+    // to run, which SimParams::deterministic can't prevent
+    let slot = atomicAdd(&cell_counts[bucket], 1u);
+    if slot < MAX_NUM_PARTICLES_PER_CELL {
+        cell_particles[bucket * MAX_NUM_PARTICLES_PER_CELL + slot] = id;
+    } else {
+        atomicAdd(&neighbor_stats.cell_overflow, 1u);
+    }
+    store_particle(id, p_in);
+}
+
+@compute
+@workgroup_size(256, 1, 1)
+fn neighbor_list_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
+        return;
+    }
+
+    let p_in = load_particle(id);
+    let center = grid_cell(p_in.position);
+    let list_start = id * NEIGHBOR_LIST_STRIDE + 1u;
+    var count = 0u;
+    var truncated = false;
+    var candidates = 0u;
+    var collisions = 0u;
+    for (var z = -1; z <= 1; z += 1) {
+        for (var y = -1; y <= 1; y += 1) {
+            for (var x = -1; x <= 1; x += 1) {
+                let cell = center + vec3<i32>(x, y, z);
+                if !grid_cell_valid(cell) {
+                    continue;
+                }
+                let bucket = grid_bucket(cell);
+                let bucket_count =
+                    min(atomicLoad(&cell_counts[bucket]), MAX_NUM_PARTICLES_PER_CELL);
+                candidates += bucket_count;
+                for (var c = 0u; c < bucket_count; c += 1u) {
+                    let pj = cell_particles[bucket * MAX_NUM_PARTICLES_PER_CELL + c];
+                    // another cell in the same bucket, also keeps a bucket shared by two of
+                    // the 27 cells from adding its particles twice
+                    if GRID_HASHED && any(grid_cell(particle_position(pj)) != cell) {
+                        collisions += 1u;
+                        continue;
+                    }
+                    if pj == id || length(p_in.position - particle_position(pj)) >= world.dh {
+                        continue;
+                    }
+
+                    // insertion by index, a full list drops the highest
+                    var n = count;
+                    if count == MAX_NUM_NEIGHBORS {
+                        truncated = true;
+                        if pj > neighbor_lists[list_start + count - 1u] {
+                            continue;
+                        }
+                        n -= 1u;
+                    } else {
+                        count += 1u;
+                    }
+                    while n > 0u && neighbor_lists[list_start + n - 1u] > pj {
+                        neighbor_lists[list_start + n] = neighbor_lists[list_start + n - 1u];
+                        n -= 1u;
+                    }
+                    neighbor_lists[list_start + n] = pj;
+                }
+            }
+        }
+    }
+    neighbor_lists[list_start - 1u] = count;
+    if truncated {
+        atomicAdd(&neighbor_stats.neighbor_overflow, 1u);
+    }
+    atomicAdd(&neighbor_stats.candidates, candidates);
+    if collisions > 0u {
+        atomicAdd(&neighbor_stats.hash_collisions, collisions);
+    }
+    store_particle(id, p_in);
+}
@@ -0,0 +1,121 @@
+// file: obstacle.h
+// analytic obstacles as signed distance fields in their own frame. particles inside one are
+// pushed out to its surface, the velocity relative to the obstacle loses its inward part
+// and bounces back by the restitution, with Coulomb friction along the surface.
+// the includer must declare `materials` and the SphParticle struct
+
+// obstacle.rs ObstacleRaw
+struct Obstacle {
+    rotation: mat3x3<f32>, // obstacle to world
+    position: vec3<f32>,
+    shape: u32, // 0 empty slot, 1 box, 2 sphere, 3 capsule, 4 cylinder
+    size: vec3<f32>, // box: half extents. others: radius, half length along y
+    friction: f32,
+    velocity: vec3<f32>, // of the centre
+    restitution: f32,
+    angular_velocity: vec3<f32>,
+};
+
+@group(3) @binding(6)
+var<storage, read> obstacles: array<Obstacle>;
+
+const OBSTACLE_BOX: u32 = 1u;
+const OBSTACLE_SPHERE: u32 = 2u;
+const OBSTACLE_CAPSULE: u32 = 3u;
+const OBSTACLE_CYLINDER: u32 = 4u;
+
+// step of the central differences of the normal
+const OBSTACLE_NORMAL_EPSILON: f32 = 1e-4;
+
+// signed distance to the surface of `obstacle` at `p` in its frame, negative inside
+fn obstacle_distance(obstacle: Obstacle, p: vec3<f32>) -> f32 {
+    let size = obstacle.size;
+    switch obstacle.shape {
+        case OBSTACLE_BOX: {
+            let q = abs(p) - size;
+            return length(max(q, vec3<f32>(0.0))) + min(max(q.x, max(q.y, q.z)), 0.0);
+        }
+        case OBSTACLE_SPHERE: {
+            return length(p) - size.x;
+        }
+        case OBSTACLE_CAPSULE: {
+            let axis = vec3<f32>(0.0, clamp(p.y, -size.y, size.y), 0.0);
+            return length(p - axis) - size.x;
+        }
+        case OBSTACLE_CYLINDER: {
+            let d = abs(vec2<f32>(length(p.xz), p.y)) - size.xy;
+            return min(max(d.x, d.y), 0.0) + length(max(d, vec2<f32>(0.0)));
+        }
+        default: {
+            return 3.4e38;
+        }
+    }
+}
+
+// outward normal of `obstacle` at `p` in its frame
+fn obstacle_normal(obstacle: Obstacle, p: vec3<f32>) -> vec3<f32> {
+    let e = vec2<f32>(OBSTACLE_NORMAL_EPSILON, 0.0);
+    let gradient = vec3<f32>(
+        obstacle_distance(obstacle, p + e.xyy) - obstacle_distance(obstacle, p - e.xyy),
+        obstacle_distance(obstacle, p + e.yxy) - obstacle_distance(obstacle, p - e.yxy),
+        obstacle_distance(obstacle, p + e.yyx) - obstacle_distance(obstacle, p - e.yyx),
+    );
+    let len = length(gradient);
+    // the exact centre of a sphere, any direction will do
+    if len < 1e-12 {
+        return vec3<f32>(0.0, 1.0, 0.0);
+    }
+    return gradient / len;
+}
+
+// push `p_in` out of every obstacle. `planar` cuts the obstacles with the plane through
+// their centre, the particles of the planar solver only move in x and y
+fn collide_obstacles(p_in: SphParticle, planar: bool) -> SphParticle {
+    var p_out = p_in;
+    for (var i = 0u; i < arrayLength(&obstacles); i += 1u) {
+        let obstacle = obstacles[i];
+        if obstacle.shape == 0u {
+            continue;
+        }
+
+        var position = p_out.position;
+        if planar {
+            position.z = obstacle.position.z;
+        }
+        let local = transpose(obstacle.rotation) * (position - obstacle.position);
+        let distance = obstacle_distance(obstacle, local);
+        if distance >= 0.0 {
+            continue;
+        }
+
+        var normal = obstacle.rotation * obstacle_normal(obstacle, local);
+        if planar {
+            normal.z = 0.0;
+            if length(normal) < 1e-6 {
+                continue;
+            }
+            normal = normalize(normal);
+        }
+        p_out.position -= distance * normal;
+
+        let r = p_out.position - obstacle.position;
+        let obstacle_velocity = obstacle.velocity + cross(obstacle.angular_velocity, r);
+        let relative = p_out.velocity - obstacle_velocity;
+        let v_n = dot(relative, normal);
+        if v_n >= 0.0 {
+            continue;
+        }
+
+        // the normal velocity change slows the tangential velocity by up to friction times it
+        let tangential = relative - v_n * normal;
+        let speed = length(tangential);
+        let impulse = -(1.0 + obstacle.restitution) * v_n;
+        let friction = max(obstacle.friction, materials[p_in.material].friction);
+        var slowed = tangential;
+        if speed > 1e-6 {
+            slowed -= min(friction * impulse, speed) / speed * tangential;
+        }
+        p_out.velocity = obstacle_velocity + slowed - obstacle.restitution * v_n * normal;
+    }
+    return p_out;
+}
@@ -6,6 +6,8 @@ struct SphParticle {
     pressure: f32,
     ptype: u32, // 0 for fluid, 1 for boundary
     cell_id: u32,
-    _pad: array<f32, 2>,
+    material: u32, // index into `materials`
+    temperature: f32, // °C
+    scalars: vec4<f32>, // passive scalar channels, see scalar.h.wgsl
 }
 
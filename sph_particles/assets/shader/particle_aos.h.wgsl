@@ -0,0 +1,64 @@
+// file: particle_aos.h
+// interleaved particle storage for the solver, one SphParticle record per particle.
+// the kernels only go through these accessors, particle_layout.rs generates the same
+// functions for the split layouts from the fields each entry point lists in
+// solver_pass_fields, a new read or write there must be added to its list
+
+@group(0) @binding(0)
+var<storage, read_write> particles_in: array<SphParticle>;
+
+@group(1) @binding(0)
+var<storage, read_write> particles_out: array<SphParticle>;
+
+@group(0) @binding(7)
+var<uniform> particle_count: u32;
+
+// the live particles, they come first in the buffers
+fn num_particles() -> u32 {
+    return particle_count;
+}
+
+// the particles the buffers hold, the inlets add at most this many
+fn particle_capacity() -> u32 {
+    return arrayLength(&particles_in);
+}
+
+fn load_particle(id: u32) -> SphParticle {
+    return particles_in[id];
+}
+
+fn store_particle(id: u32, p: SphParticle) {
+    particles_out[id] = p;
+}
+
+fn particle_position(id: u32) -> vec3<f32> {
+    return particles_in[id].position;
+}
+
+fn particle_velocity(id: u32) -> vec3<f32> {
+    return particles_in[id].velocity;
+}
+
+fn particle_density(id: u32) -> f32 {
+    return particles_in[id].density;
+}
+
+fn particle_pressure(id: u32) -> f32 {
+    return particles_in[id].pressure;
+}
+
+fn particle_material(id: u32) -> u32 {
+    return particles_in[id].material;
+}
+
+fn particle_temperature(id: u32) -> f32 {
+    return particles_in[id].temperature;
+}
+
+fn particle_type(id: u32) -> u32 {
+    return particles_in[id].ptype;
+}
+
+fn particle_scalars(id: u32) -> vec4<f32> {
+    return particles_in[id].scalars;
+}
@@ -0,0 +1,89 @@
+// spray, foam and bubbles as billboards, alpha blended over the water surface
+
+// compute_pass_diffuse.rs DiffuseParticleRaw
+struct DiffuseParticle {
+    position: vec3<f32>,
+    lifetime: f32,
+    velocity: vec3<f32>,
+    kind: u32, // 0 spray, 1 foam, 2 bubble
+};
+
+@group(1) @binding(0)
+var<storage, read> diffuse: array<DiffuseParticle>;
+
+const particle_radius: f32 = 0.05;
+
+struct CameraUniform {
+    mat_view: mat4x4<f32>,
+    mat_proj: mat4x4<f32>,
+    mat_view_inv: mat4x4<f32>,
+    mat_proj_inv: mat4x4<f32>,
+    eye: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+var<private> positions_offset: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+    vec2<f32>(-1.0, 1.0),  // top-left
+    vec2<f32>(-1.0, -1.0), // bottom-left
+    vec2<f32>(1.0, -1.0),  // bottom-right
+    vec2<f32>(1.0, 1.0),   // top-right
+    vec2<f32>(-1.0, 1.0),  // top-left
+    vec2<f32>(1.0, -1.0)   // bottom-right
+);
+
+// bubbles are seen through the water, so they are fainter
+var<private> kind_colors: array<vec4<f32>, 3> = array<vec4<f32>, 3>(
+    vec4<f32>(1.0, 1.0, 1.0, 0.9), // spray
+    vec4<f32>(0.95, 0.97, 1.0, 0.8), // foam
+    vec4<f32>(0.7, 0.85, 1.0, 0.35) // bubble
+);
+
+//--------------------------------------------------------------
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let d = diffuse[in_vertex_index / 6u];
+    if d.lifetime <= 0.0 {
+        // free slot, outside the clip volume
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+
+    let lw = normalize(camera.eye.xyz - d.position);
+    let up = vec3<f32>(0.0, 1.0, 0.0);
+    let x_axis = normalize(cross(up, lw));
+    let y_axis = normalize(cross(lw, x_axis));
+
+    let offset = particle_radius * vec3<f32>(positions_offset[in_vertex_index % 6u], 0.0);
+    let position4f = vec4<f32>(d.position + offset.x * x_axis + offset.y * y_axis, 1.0);
+    out.clip_position = camera.mat_proj * camera.mat_view * position4f;
+    out.uv = positions_offset[in_vertex_index % 6u];
+
+    // fade out over the last second
+    out.color = kind_colors[min(d.kind, 2u)];
+    out.color.a *= clamp(d.lifetime, 0.0, 1.0);
+    return out;
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>, // [-1, 1]
+    @location(1) color: vec4<f32>,
+};
+
+//--------------------------------------------------------------
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coord_length = length(in.uv);
+    // draw a soft circle
+    if coord_length > 1.0 {
+        discard;
+    }
+    return vec4<f32>(in.color.rgb, in.color.a * (1.0 - coord_length * coord_length));
+}
@@ -0,0 +1,38 @@
+// the outlines of the scene's obstacles as lines over the water surface
+
+struct CameraUniform {
+    mat_view: mat4x4<f32>,
+    mat_proj: mat4x4<f32>,
+    mat_view_inv: mat4x4<f32>,
+    mat_proj_inv: mat4x4<f32>,
+    eye: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+const line_color: vec4<f32> = vec4<f32>(0.9, 0.6, 0.2, 0.9);
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+//--------------------------------------------------------------
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.mat_proj * camera.mat_view * vec4<f32>(in.position, 1.0);
+    return out;
+}
+
+//--------------------------------------------------------------
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return line_color;
+}
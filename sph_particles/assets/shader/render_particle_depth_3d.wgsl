@@ -1,7 +1,4 @@
-//!include particle.h.wgsl
-
-@group(1) @binding(0)
-var<storage, read> particles_in: array<SphParticle>;
+//!include particle.h.wgsl particle_view.h.wgsl
 
 const particle_radius: f32 = 0.2;
 
@@ -32,7 +29,12 @@ fn vs_main(
     @builtin(vertex_index) in_vertex_index: u32,
 ) -> VertexOutput {
     var out: VertexOutput;
-    let p = particles_in[in_vertex_index / 6u];
+    // the draw covers the capacity, the slots past the live particles collapse to a point
+    if in_vertex_index / 6u >= num_particles() {
+        out.clip_position = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        return out;
+    }
+    let p = load_particle(in_vertex_index / 6u);
     let position3f = p.position;
 
     let lw = normalize(camera.eye.xyz - position3f);
@@ -45,6 +47,8 @@ fn vs_main(
     out.v_pos = (camera.mat_view * position4f).xyz;
     out.clip_position = camera.mat_proj * camera.mat_view * position4f;
     out.uv = positions_offset[in_vertex_index % 6u];
+    out.temperature = p.temperature;
+    out.scalars = p.scalars;
 
     return out;
 }
@@ -53,6 +57,8 @@ struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) v_pos: vec3<f32>,
     @location(1) uv: vec2<f32>, // [-1, 1]
+    @location(2) @interpolate(flat) temperature: f32,
+    @location(3) @interpolate(flat) scalars: vec4<f32>,
 };
 
 //--------------------------------------------------------------
@@ -75,12 +81,16 @@ fn fs_main(in: VertexOutput) -> FragmentOutput {
     var output: FragmentOutput;
     output.zval = depth;
     output.color = vec4f(vec3f(scale_depth(depth)), 1.0);
+    output.temperature = in.temperature;
+    output.scalars = in.scalars;
     return output;
 }
 
 struct FragmentOutput {
     @builtin(frag_depth) zval: f32,
     @location(0) color: vec4<f32>,
+    @location(1) temperature: f32,
+    @location(2) scalars: vec4<f32>,
 };
 
 // better for displaying
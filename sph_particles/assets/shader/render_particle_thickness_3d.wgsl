@@ -1,7 +1,4 @@
-//!include particle.h.wgsl
-
-@group(1) @binding(0)
-var<storage, read> particles_in: array<SphParticle>;
+//!include particle.h.wgsl particle_view.h.wgsl
 
 const particle_radius: f32 = 0.5;
 
@@ -32,7 +29,12 @@ fn vs_main(
     @builtin(vertex_index) in_vertex_index: u32,
 ) -> VertexOutput {
     var out: VertexOutput;
-    let p = particles_in[in_vertex_index / 6u];
+    // the draw covers the capacity, the slots past the live particles collapse to a point
+    if in_vertex_index / 6u >= num_particles() {
+        out.clip_position = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        return out;
+    }
+    let p = load_particle(in_vertex_index / 6u);
     let position3f = p.position;
 
     let lw = normalize(camera.eye.xyz - position3f);
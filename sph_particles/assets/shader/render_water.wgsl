@@ -11,11 +11,19 @@ struct CameraUniform {
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
+// render_pass_water.rs RenderUniforms
 struct Uniforms {
     camera_intrinsic: vec4<f32>,
     screen_coordinate_znear_zfar: vec4<f32>,
+    color_mode: u32,
+    _pad: u32,
+    temperature_range: vec2<f32>,
+    scalar_colors: array<vec4<f32>, 4>, // per passive scalar channel, w unused
 }
 
+const COLOR_MODE_TEMPERATURE: u32 = 1u;
+const COLOR_MODE_DYE: u32 = 2u;
+
 @group(1) @binding(0)
 var<uniform> uniforms: Uniforms;
 
@@ -36,6 +44,12 @@ fn get_zfar() -> f32 {
 @group(2) @binding(0)
 var depth_texture: texture_2d<f32>;
 
+// temperature and passive scalars of the front most particle
+@group(3) @binding(0)
+var temperature_texture: texture_2d<f32>;
+@group(3) @binding(1)
+var scalar_texture: texture_2d<f32>;
+
 
 //----------------------------------------------------------------------
 
@@ -92,7 +106,13 @@ fn fs_main(in: VertexOutput) -> FragmentOutput {
     let specular = pow(max(0.0, dot(normal, he)), shininess) * light_specular;
     let fresnel = pow(1.0 - abs(normal.z), 5.0);
 
-    let color = water_color * (0.3 + 0.4 * diffuse + 1.2 * specular + 5.0 * fresnel);
+    var base_color = water_color;
+    if uniforms.color_mode == COLOR_MODE_TEMPERATURE {
+        base_color = temperature_color(textureLoad(temperature_texture, pixel_id, 0).x);
+    } else if uniforms.color_mode == COLOR_MODE_DYE {
+        base_color = dye_color(textureLoad(scalar_texture, pixel_id, 0));
+    }
+    let color = base_color * (0.3 + 0.4 * diffuse + 1.2 * specular + 5.0 * fresnel);
 
     var output: FragmentOutput;
     output.color = vec4f(color, 1.0);
@@ -138,6 +158,31 @@ fn compute_normal(tex_coords: vec2<i32>) -> vec3<f32> {
     return normal;
 }
 
+// blue through red to yellow over `temperature_range`
+fn temperature_color(temperature: f32) -> vec3<f32> {
+    let range = uniforms.temperature_range;
+    let t = clamp((temperature - range.x) / max(range.y - range.x, 1e-6), 0.0, 1.0);
+    let cold = vec3<f32>(0.1, 0.2, 1.0);
+    let warm = vec3<f32>(0.9, 0.1, 0.05);
+    let hot = vec3<f32>(1.0, 0.9, 0.2);
+    if t < 0.5 {
+        return mix(cold, warm, 2.0 * t);
+    }
+    return mix(warm, hot, 2.0 * t - 1.0);
+}
+
+// the channel colours weighted by their concentrations, water makes up the rest below a
+// total of 1
+fn dye_color(scalars: vec4<f32>) -> vec3<f32> {
+    let weights = clamp(scalars, vec4<f32>(0.0), vec4<f32>(1.0));
+    let total = dot(weights, vec4<f32>(1.0));
+    var color = max(1.0 - total, 0.0) * water_color;
+    for (var c = 0u; c < 4u; c += 1u) {
+        color += weights[c] * uniforms.scalar_colors[c].rgb;
+    }
+    return color / max(total, 1.0);
+}
+
 fn lerp(a: f32, b: f32, t: f32) -> f32 {
     return a + (b - a) * t;
 }
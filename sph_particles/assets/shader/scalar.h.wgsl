@@ -0,0 +1,76 @@
+// file: scalar.h
+// passive scalars of scalar.rs: every channel diffuses between neighbouring fluid
+// particles and relaxes towards the scene's sources. boundary particles don't exchange,
+// the walls hold the scalars in. the includer must declare the particle and neighbour
+// accessors (particle_aos.h.wgsl, neighbors_all.h.wgsl), `uniforms`, `world`,
+// `get_particle_id` and `density_grad`
+
+const MAX_SCALAR_SOURCES: u32 = 8u;
+
+// scalar.rs ScalarSourceRaw
+struct ScalarSource {
+    lower: vec3<f32>,
+    channel: u32,
+    upper: vec3<f32>,
+    value: f32,
+    rate: f32, // 1/s
+};
+
+// scalar.rs ScalarsRaw
+struct ScalarUniforms {
+    diffusivity: vec4<f32>, // m²/s per channel
+    count: u32,
+    sources: array<ScalarSource, MAX_SCALAR_SOURCES>,
+};
+
+@group(3) @binding(7)
+var<uniform> scalars: ScalarUniforms;
+
+@compute
+@workgroup_size(256, 1, 1)
+fn scalar_transport_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
+        return;
+    }
+
+    store_particle(id, transport_scalars(id));
+}
+
+// explicit step of dC/dt = D laplacian(C) for all channels at once, plus the sources the
+// particle is in
+fn transport_scalars(pi: u32) -> SphParticle {
+    let p_in = load_particle(pi);
+    var p_out = p_in;
+    let m_V = get_m_V();
+
+    if p_in.ptype != 0 { return p_out; }
+
+    var dC = vec4<f32>(0.0);
+    for (var n: u32 = 0; n < neighbor_count(pi); n += 1u) {
+        let pj = neighbor_at(pi, n);
+        if pj == pi || particle_type(pj) != 0 { continue; }
+
+        let x_ab = p_in.position - particle_position(pj);
+        let r_ab = length(x_ab);
+        if r_ab < world.dh {
+            let V_b = m_V * rho_0 / particle_density(pj);
+            dC += 2.0 * V_b * (p_in.scalars - particle_scalars(pj))
+                * dot(x_ab, density_grad(x_ab, world.dh))
+                / (r_ab * r_ab + 0.01 * world.dh * world.dh);
+        }
+    }
+    p_out.scalars += uniforms.time_step * scalars.diffusivity * dC;
+
+    for (var s: u32 = 0; s < min(scalars.count, MAX_SCALAR_SOURCES); s += 1u) {
+        let source = scalars.sources[s];
+        if all(p_in.position >= source.lower) && all(p_in.position <= source.upper) {
+            let blend = 1.0 - exp(-source.rate * uniforms.time_step);
+            p_out.scalars[source.channel] =
+                mix(p_out.scalars[source.channel], source.value, blend);
+        }
+    }
+    return p_out;
+}
@@ -0,0 +1,18 @@
+// file: scratch.h
+// per particle scratch space of the solver passes. one buffer so a pass stays within the
+// storage buffers it may bind: a vec4 of viscosity state per particle, one of curl, then
+// the integrator state (integrator.h.wgsl).
+// the includer must declare `particle_capacity`
+
+@group(2) @binding(1)
+var<storage, read_write> scratch: array<vec4<f32>>;
+
+// regions of `scratch`, in order
+const SCRATCH_VISCOSITY: u32 = 0u;
+const SCRATCH_CURL: u32 = 1u;
+const SCRATCH_INTEGRATOR: u32 = 2u; // the last one, it holds several vec4 per particle
+
+// index of the `i`th vec4 of `region`
+fn scratch_index(region: u32, i: u32) -> u32 {
+    return region * particle_capacity() + i;
+}
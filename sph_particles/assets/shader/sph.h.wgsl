@@ -0,0 +1,9 @@
+// file: sph.h
+// shared SPH constants, the includer must declare `world: WorldUniforms`
+
+const rho_0 = 1000.0; // reference density
+
+// particle volume, calibrated at init so the spawn lattice is at rest density
+fn get_m_V() -> f32 {
+    return world.rest_volume;
+}
@@ -0,0 +1,125 @@
+// file: velocity_filters.h
+// vorticity confinement and XSPH velocity smoothing, each step is its own pass.
+// the includer must declare the particle and neighbour accessors (particle_aos.h.wgsl,
+// neighbors_all.h.wgsl), `uniforms`, `world`, `scratch` (scratch.h.wgsl), `get_particle_id`,
+// `density_kernel` and `density_grad`
+
+// xyz: curl of the velocity, w: its length
+fn curl(id: u32) -> u32 {
+    return scratch_index(SCRATCH_CURL, id);
+}
+
+@compute
+@workgroup_size(256, 1, 1)
+fn compute_curl_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
+        return;
+    }
+
+    let omega = calc_curl(id);
+    scratch[curl(id)] = vec4<f32>(omega, length(omega));
+
+    store_particle(id, load_particle(id));
+}
+
+@compute
+@workgroup_size(256, 1, 1)
+fn vorticity_confinement_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
+        return;
+    }
+
+    store_particle(id, apply_vorticity_confinement(id));
+}
+
+@compute
+@workgroup_size(256, 1, 1)
+fn xsph_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
+        return;
+    }
+
+    store_particle(id, apply_xsph(id));
+}
+
+fn calc_curl(pi: u32) -> vec3<f32> {
+    let p_in = load_particle(pi);
+    let m_V = get_m_V();
+
+    var omega = vec3<f32>(0.0);
+    if p_in.ptype != 0 { return omega; }
+
+    for (var n: u32 = 0; n < neighbor_count(pi); n += 1u) {
+        let pj = neighbor_at(pi, n);
+        if pj == pi { continue; }
+
+        let x_ab = p_in.position - particle_position(pj);
+        if length(x_ab) < world.dh {
+            let V_b = m_V * rho_0 / particle_density(pj);
+            omega += V_b * cross(density_grad(x_ab, world.dh), particle_velocity(pj) - p_in.velocity);
+        }
+    }
+    return omega;
+}
+
+// push the flow around the curl, towards regions of larger vorticity
+fn apply_vorticity_confinement(pi: u32) -> SphParticle {
+    let p_in = load_particle(pi);
+    var p_out = p_in;
+    let m_V = get_m_V();
+
+    if p_in.ptype != 0 { return p_out; }
+
+    let omega = scratch[curl(pi)];
+    var eta = vec3<f32>(0.0);
+    for (var n: u32 = 0; n < neighbor_count(pi); n += 1u) {
+        let pj = neighbor_at(pi, n);
+        if pj == pi { continue; }
+
+        let x_ab = p_in.position - particle_position(pj);
+        if length(x_ab) < world.dh {
+            let V_b = m_V * rho_0 / particle_density(pj);
+            eta += V_b * (scratch[curl(pj)].w - omega.w) * density_grad(x_ab, world.dh);
+        }
+    }
+
+    let eta_len = length(eta);
+    if eta_len > 1e-6 {
+        let n = eta / eta_len;
+        p_out.velocity += uniforms.time_step * uniforms.vorticity * cross(n, omega.xyz);
+    }
+    return p_out;
+}
+
+// blend the velocity with the kernel-weighted neighbour average
+fn apply_xsph(pi: u32) -> SphParticle {
+    let p_in = load_particle(pi);
+    var p_out = p_in;
+    let m_V = get_m_V();
+
+    if p_in.ptype != 0 { return p_out; }
+
+    var dv = vec3<f32>(0.0);
+    for (var n: u32 = 0; n < neighbor_count(pi); n += 1u) {
+        let pj = neighbor_at(pi, n);
+        if pj == pi { continue; }
+
+        let x_ab = p_in.position - particle_position(pj);
+        if length(x_ab) < world.dh {
+            let V_b = m_V * rho_0 / particle_density(pj);
+            dv += V_b * (particle_velocity(pj) - p_in.velocity) * density_kernel(x_ab, world.dh);
+        }
+    }
+
+    p_out.velocity += uniforms.xsph * dv;
+    return p_out;
+}
@@ -0,0 +1,202 @@
+// file: viscosity.h
+// material viscosity: the apparent viscosity follows the local shear rate and the
+// velocity is diffused implicitly with Jacobi iterations, so stiff materials stay stable.
+// granular materials yield under a stress that grows with the pressure, see fluid_material.rs.
+// the includer must declare the particle and neighbour accessors (particle_aos.h.wgsl,
+// neighbors_all.h.wgsl), `uniforms`, `world`, `scratch` (scratch.h.wgsl), `get_particle_id`,
+// `density_kernel`, `density_grad`
+
+// fluid_material.rs FluidMaterialRaw
+struct FluidMaterial {
+    model: u32, // 0 inviscid, 1 newtonian, 2 power-law, 3 carreau, 4 bingham
+    viscosity: f32, // newtonian viscosity, power-law consistency, carreau zero shear or bingham plastic viscosity
+    viscosity_inf: f32,
+    relaxation_time: f32,
+    flow_index: f32,
+    yield_stress: f32,
+    max_viscosity: f32,
+    thermal_diffusivity: f32,
+    reference_temperature: f32,
+    softening: f32, // 0 when the viscosity does not depend on temperature
+    solidus_temperature: f32,
+    friction: f32, // tangent of the granular angle of repose, 0 for fluids
+    cohesion: f32,
+    lame_lambda: f32, // elastic solids, see elastic.h.wgsl
+    shear_modulus: f32,
+    _pad: f32,
+};
+
+const VISCOSITY_NEWTONIAN: u32 = 1u;
+const VISCOSITY_POWER_LAW: u32 = 2u;
+const VISCOSITY_CARREAU: u32 = 3u;
+const VISCOSITY_BINGHAM: u32 = 4u;
+
+@group(3) @binding(1)
+var<storage, read> materials: array<FluidMaterial>;
+
+// xyz: velocity before the solve, w: kinematic viscosity
+fn viscosity_state(id: u32) -> u32 {
+    return scratch_index(SCRATCH_VISCOSITY, id);
+}
+
+@compute
+@workgroup_size(256, 1, 1)
+fn viscosity_prepare_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
+        return;
+    }
+
+    let p_in = load_particle(id);
+    let material = materials[p_in.material];
+    var pressure = p_in.pressure;
+    if is_granular(material) {
+        pressure = calc_confining_pressure(id);
+    }
+    let mu = apparent_viscosity(material, calc_shear_rate(id), pressure, p_in.temperature);
+    scratch[viscosity_state(id)] = vec4<f32>(p_in.velocity, mu / rho_0);
+
+    store_particle(id, p_in);
+}
+
+@compute
+@workgroup_size(256, 1, 1)
+fn viscosity_jacobi_main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+) {
+    let id = get_particle_id(gid);
+    if id >= num_particles() {
+        return;
+    }
+
+    store_particle(id, viscosity_jacobi(id));
+}
+
+// sqrt(2 D:D) with D the symmetric part of the velocity gradient
+fn calc_shear_rate(pi: u32) -> f32 {
+    let p_in = load_particle(pi);
+    let m_V = get_m_V();
+
+    var grad_v = mat3x3<f32>(vec3<f32>(0.0), vec3<f32>(0.0), vec3<f32>(0.0));
+    for (var n: u32 = 0; n < neighbor_count(pi); n += 1u) {
+        let pj = neighbor_at(pi, n);
+        if pj == pi { continue; }
+
+        let x_ab = p_in.position - particle_position(pj);
+        if length(x_ab) < world.dh {
+            let V_b = m_V * rho_0 / particle_density(pj);
+            let dv = V_b * (particle_velocity(pj) - p_in.velocity);
+            let grad = density_grad(x_ab, world.dh);
+            grad_v += mat3x3<f32>(dv * grad.x, dv * grad.y, dv * grad.z);
+        }
+    }
+
+    let d = 0.5 * (grad_v + transpose(grad_v));
+    let d_d = dot(d[0], d[0]) + dot(d[1], d[1]) + dot(d[2], d[2]);
+    return sqrt(2.0 * d_d);
+}
+
+fn is_granular(material: FluidMaterial) -> bool {
+    return material.friction > 0.0 || material.cohesion > 0.0;
+}
+
+// kernel average of the pressure around `pi`. the free surface clamps the pressure of the top
+// layer to zero, the average still feels the weight of the grains below it
+fn calc_confining_pressure(pi: u32) -> f32 {
+    let p_in = load_particle(pi);
+    let m_V = get_m_V();
+
+    var weight_sum = m_V * rho_0 / p_in.density * density_kernel(vec3<f32>(0.0), world.dh);
+    var pressure_sum = weight_sum * p_in.pressure;
+    for (var n: u32 = 0; n < neighbor_count(pi); n += 1u) {
+        let pj = neighbor_at(pi, n);
+        if pj == pi { continue; }
+
+        let x_ab = p_in.position - particle_position(pj);
+        if length(x_ab) < world.dh {
+            let w = m_V * rho_0 / particle_density(pj) * density_kernel(x_ab, world.dh);
+            weight_sum += w;
+            pressure_sum += w * particle_pressure(pj);
+        }
+    }
+    return pressure_sum / weight_sum;
+}
+
+// dynamic viscosity of `material` at `shear_rate`, `pressure` and `temperature`
+fn apparent_viscosity(material: FluidMaterial, shear_rate: f32, pressure: f32, temperature: f32) -> f32 {
+    if temperature < material.solidus_temperature {
+        return material.max_viscosity;
+    }
+
+    // regularise the models that diverge at rest
+    let rate = max(shear_rate, 1e-3);
+    var mu = 0.0;
+    switch material.model {
+        case VISCOSITY_NEWTONIAN: {
+            mu = material.viscosity;
+        }
+        case VISCOSITY_POWER_LAW: {
+            mu = material.viscosity * pow(rate, material.flow_index - 1.0);
+        }
+        case VISCOSITY_CARREAU: {
+            let lambda_rate = material.relaxation_time * rate;
+            mu = material.viscosity_inf + (material.viscosity - material.viscosity_inf)
+                * pow(1.0 + lambda_rate * lambda_rate, 0.5 * (material.flow_index - 1.0));
+        }
+        case VISCOSITY_BINGHAM: {
+            mu = material.viscosity + material.yield_stress / rate;
+        }
+        default: {}
+    }
+    // bounded so an inviscid base model never turns into 0 * inf
+    mu *= exp(clamp(-material.softening * (temperature - material.reference_temperature), -30.0, 30.0));
+    // Drucker-Prager, the grains hold more shear the harder they are pressed together
+    mu += (material.cohesion + material.friction * max(pressure, 0.0)) / rate;
+    return clamp(mu, 0.0, material.max_viscosity);
+}
+
+// one Jacobi iteration of (1 - dt nu laplacian) v = v_pred
+fn viscosity_jacobi(pi: u32) -> SphParticle {
+    let p_in = load_particle(pi);
+    var p_out = p_in;
+    let m_V = get_m_V();
+
+    if p_in.ptype != 0 { return p_out; }
+
+    let state = scratch[viscosity_state(pi)];
+    var weight_sum = 0.0;
+    var weighted_velocity = vec3<f32>(0.0);
+    for (var n: u32 = 0; n < neighbor_count(pi); n += 1u) {
+        let pj = neighbor_at(pi, n);
+        if pj == pi { continue; }
+
+        let x_ab = p_in.position - particle_position(pj);
+        let r_ab = length(x_ab);
+        if r_ab < world.dh {
+            let V_b = m_V * rho_0 / particle_density(pj);
+            let nu_ab = 0.5 * (state.w + scratch[viscosity_state(pj)].w);
+            // the kernel gradient points against x_ab, so the weight is positive
+            let laplacian_weight = -2.0 * V_b * dot(x_ab, density_grad(x_ab, world.dh))
+                / (r_ab * r_ab + 0.01 * world.dh * world.dh);
+            let w = uniforms.time_step * nu_ab * laplacian_weight;
+            weight_sum += w;
+            weighted_velocity += w * particle_velocity(pj);
+        }
+    }
+
+    p_out.velocity = (state.xyz + weighted_velocity) / (1.0 + weight_sum);
+    return p_out;
+}
+
+// Coulomb friction against a container wall: the tangential part of `velocity`, the axes
+// without a normal velocity change in `impulse`, slows by up to `friction` times that change
+fn wall_friction(friction: f32, velocity: vec3<f32>, impulse: vec3<f32>) -> vec3<f32> {
+    let tangential = select(velocity, vec3<f32>(0.0), impulse > vec3<f32>(0.0));
+    let speed = length(tangential);
+    if friction == 0.0 || speed < 1e-6 {
+        return velocity;
+    }
+    return velocity - min(friction * length(impulse), speed) / speed * tangential;
+}
@@ -1,10 +1,48 @@
 // file: world.h
 // defines the world constants
 
+// container.rs ContainerRaw, the box holding the fluid. it rotates about `origin`,
+// container space is centred on it
+struct Container {
+    rotation: mat3x3<f32>, // container to world
+    origin: vec3<f32>,
+    lower: vec3<f32>, // walls in container space
+    upper: vec3<f32>,
+    velocity: vec3<f32>, // of the origin
+    angular_velocity: vec3<f32>,
+    lower_velocity: vec3<f32>, // of the walls in container space, non zero for a piston
+    upper_velocity: vec3<f32>,
+};
+
+// flow.rs FlowFaceRaw, an inlet or outlet on a face of the domain box, see flow.h.wgsl
+struct FlowFace {
+    lower: vec3<f32>, // of the inlet region, the whole box for an outlet
+    kind: u32, // 0 closed, 1 inlet, 2 outlet
+    upper: vec3<f32>,
+    speed: f32, // into the domain, at the centre for a parabolic profile
+    profile: u32, // 0 uniform, 1 parabolic
+};
+
 struct WorldUniforms {
-    boundary_upper: vec3<f32>,
+    boundary_upper: vec3<f32>, // static domain, the container moves inside it
     dx: f32,
     boundary_lower: vec3<f32>,
     dh: f32, // kernel radius
+    container: Container,
+    flow: array<FlowFace, 6>, // in the order of container.rs ContainerWall
+    rest_volume: f32, // m_V of sph.h.wgsl
 };
 
+fn to_container_space(position: vec3<f32>) -> vec3<f32> {
+    return transpose(world.container.rotation) * (position - world.container.origin);
+}
+
+fn from_container_space(position: vec3<f32>) -> vec3<f32> {
+    return world.container.origin + world.container.rotation * position;
+}
+
+// velocity of the container body at a world space position
+fn container_velocity(position: vec3<f32>) -> vec3<f32> {
+    let r = position - world.container.origin;
+    return world.container.velocity + cross(world.container.angular_velocity, r);
+}
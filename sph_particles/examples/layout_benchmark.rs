@@ -0,0 +1,82 @@
+//! Step time of the particle storage layouts on the headless (software) adapter, and the
+//! particle bytes a step moves per second in each, see `ParticleLayout::pass_bytes_per_particle`
+//!
+//!     cargo run --release --example layout_benchmark
+use std::time::Instant;
+
+use cgmath::Vector3;
+use sph_particles::headless::{
+    solver_pass_fields, HeadlessSim, ParticleLayout, Scene, SimParams, SpawnBlock,
+};
+
+const WARMUP_STEPS: usize = 5;
+const STEPS: usize = 40;
+
+fn scene(layout: ParticleLayout) -> Scene {
+    Scene {
+        particle_radius: 0.05,
+        support_radius: 0.2,
+        domain_cells: Vector3::new(12, 10, 6),
+        layout,
+        params: SimParams {
+            deterministic: true,
+            ..Default::default()
+        },
+        blocks: vec![SpawnBlock::fluid(
+            Vector3::new(0.1, 0.1, 0.1),
+            Vector3::new(1.0, 1.2, 1.1),
+        )],
+        ..Default::default()
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let layouts = [
+        ("interleaved", ParticleLayout::Interleaved),
+        (
+            "split",
+            ParticleLayout::Split {
+                half_precision: false,
+            },
+        ),
+        (
+            "split f16",
+            ParticleLayout::Split {
+                half_precision: true,
+            },
+        ),
+    ];
+
+    for (name, layout) in layouts {
+        let mut sim = pollster::block_on(HeadlessSim::new(&scene(layout)))?;
+        let num_particles = pollster::block_on(sim.num_particles());
+        if name == "interleaved" {
+            println!("adapter: {}", sim.adapter_info.name);
+            println!("particles: {num_particles}");
+        }
+        let step_bytes = sim
+            .solver_passes()
+            .into_iter()
+            .map(|pass| layout.pass_bytes_per_particle(solver_pass_fields(pass)))
+            .sum::<usize>()
+            * num_particles;
+        for _ in 0..WARMUP_STEPS {
+            pollster::block_on(sim.step());
+        }
+        pollster::block_on(sim.read_particles());
+
+        let start = Instant::now();
+        for _ in 0..STEPS {
+            pollster::block_on(sim.step());
+        }
+        pollster::block_on(sim.read_particles());
+        let step_ms = start.elapsed().as_secs_f64() * 1e3 / STEPS as f64;
+        let bandwidth = step_bytes as f64 / (step_ms * 1e-3) / 1e9;
+
+        println!(
+            "{name:>12}: {step_ms:8.2} ms/step {:8.2} MB/step {bandwidth:8.3} GB/s",
+            step_bytes as f64 / 1e6
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! Step time of the particle orders on the headless (software) adapter, at several
+//! particle counts. the cached neighbour lists gather their neighbours from all over the
+//! buffers, sorting by cell keeps them close in memory
+//!
+//!     cargo run --release --example order_benchmark
+use std::time::Instant;
+
+use cgmath::Vector3;
+use sph_particles::headless::{
+    HeadlessSim, NeighborSearch, ParticleOrder, Scene, SimParams, SpawnBlock,
+};
+
+const WARMUP_STEPS: usize = 5;
+const STEPS: usize = 40;
+/// frames between reorders, their readback and upload are part of the step time
+const REORDER_INTERVAL: u32 = 10;
+
+/// a dam break filling `fill` of the domain's width, so the particles have mixed by the
+/// time the steps are measured
+fn scene(fill: f32, particle_order: ParticleOrder, reorder_interval: u32) -> Scene {
+    Scene {
+        particle_radius: 0.05,
+        support_radius: 0.2,
+        domain_cells: Vector3::new(16, 10, 8),
+        neighbor_search: NeighborSearch::CachedList,
+        params: SimParams {
+            deterministic: true,
+            particle_order,
+            reorder_interval,
+            ..Default::default()
+        },
+        blocks: vec![SpawnBlock::fluid(
+            Vector3::new(0.1, 0.1, 0.1),
+            Vector3::new(0.1 + 3.0 * fill, 1.4, 1.5),
+        )],
+        ..Default::default()
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let orders = [
+        ("spawn", ParticleOrder::Linear, 0),
+        ("linear", ParticleOrder::Linear, REORDER_INTERVAL),
+        ("morton", ParticleOrder::Morton, REORDER_INTERVAL),
+    ];
+
+    for (i, fill) in [0.25, 0.5, 1.0].into_iter().enumerate() {
+        for (name, order, interval) in orders {
+            let mut sim = pollster::block_on(HeadlessSim::new(&scene(fill, order, interval)))?;
+            if i == 0 && name == "spawn" {
+                println!("adapter: {}", sim.adapter_info.name);
+            }
+            if name == "spawn" {
+                println!("particles: {}", pollster::block_on(sim.num_particles()));
+            }
+            for _ in 0..WARMUP_STEPS {
+                pollster::block_on(sim.step());
+            }
+            pollster::block_on(sim.read_particles());
+
+            let start = Instant::now();
+            for _ in 0..STEPS {
+                pollster::block_on(sim.step());
+            }
+            pollster::block_on(sim.read_particles());
+            let step_ms = start.elapsed().as_secs_f64() * 1e3 / STEPS as f64;
+
+            println!("{name:>12}: {step_ms:8.2} ms/step");
+        }
+    }
+    Ok(())
+}
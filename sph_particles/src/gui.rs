@@ -1,9 +1,18 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 use egui::util::History;
 use egui_wgpu_backend::{RenderPass, ScreenDescriptor};
 use egui_winit_platform::{Platform, PlatformDescriptor};
 
+use cgmath::{InnerSpace, Vector3};
+
+use crate::interaction::MouseInteraction;
+use crate::particle_system::force_field::{ForceField, ForceKind, MAX_FORCE_FIELDS};
+use crate::particle_system::grid::{NeighborStats, MAX_NUM_NEIGHBORS, MAX_NUM_PARTICLES_PER_CELL};
+use crate::particle_system::scene::{Integrator, ParticleOrder, SimParams};
+use crate::renderer::compute_pass_reduction::SimDiagnostics;
+use crate::renderer::render_pass_water::{ColorMode, ColorSettings};
+
 pub struct UILayer {
     pub egui_platform: Platform,
     pub egui_rpass: RenderPass,
@@ -12,6 +21,13 @@ pub struct UILayer {
     pub window_open: HashMap<String, bool>,
 
     pub frame_history: FrameHistory,
+    pub sim_stats_history: SimStatsHistory,
+    /// latest readback of the cached neighbour search, see `NeighborSearch::CachedList`
+    pub neighbor_stats: NeighborStats,
+    pub mouse_interaction: MouseInteraction,
+    pub sim_params: SimParams,
+    pub force_fields: Vec<ForceField>,
+    pub color_settings: ColorSettings,
 }
 
 fn configure_text_styles(ctx: &egui::Context) {
@@ -47,6 +63,9 @@ impl UILayer {
         surface_format: &wgpu::TextureFormat,
         size: winit::dpi::PhysicalSize<u32>,
         scale_factor: f64,
+        sim_params: SimParams,
+        force_fields: Vec<ForceField>,
+        color_settings: ColorSettings,
     ) -> Self {
         let egui_platform = Platform::new(PlatformDescriptor {
             physical_width: size.width,
@@ -65,6 +84,12 @@ impl UILayer {
             egui_rpass,
             demo_app,
             frame_history: FrameHistory::default(),
+            sim_stats_history: SimStatsHistory::default(),
+            neighbor_stats: NeighborStats::default(),
+            mouse_interaction: MouseInteraction::new(),
+            sim_params,
+            force_fields,
+            color_settings,
             display_demo: false,
             window_open: HashMap::new(),
         }
@@ -123,6 +148,35 @@ impl UILayer {
                     ui.checkbox(&mut self.display_demo, "Display Demo");
                     ui.separator();
                     self.frame_history.ui(ui);
+                    ui.separator();
+                    self.sim_stats_history.ui(ui);
+                    ui.separator();
+                    // nothing is read back with NeighborSearch::AllPairs
+                    if self.neighbor_stats.candidates > 0 {
+                        neighbor_stats_ui(&self.neighbor_stats, ui);
+                        ui.separator();
+                    }
+                    egui::CollapsingHeader::new("⚙ Parameters")
+                        .default_open(false)
+                        .show(ui, |ui| sim_params_ui(&mut self.sim_params, ui));
+                    ui.separator();
+                    egui::CollapsingHeader::new("🌀 Forces")
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            force_fields_ui(
+                                &mut self.sim_params.gravity,
+                                &mut self.force_fields,
+                                ui,
+                            )
+                        });
+                    ui.separator();
+                    egui::CollapsingHeader::new("🖱 Mouse tools")
+                        .default_open(true)
+                        .show(ui, |ui| self.mouse_interaction.ui(ui));
+                    ui.separator();
+                    egui::CollapsingHeader::new("🎨 Display")
+                        .default_open(false)
+                        .show(ui, |ui| color_settings_ui(&mut self.color_settings, ui));
                 });
             });
     }
@@ -193,6 +247,266 @@ impl UILayer {
     }
 }
 
+fn sim_params_ui(params: &mut SimParams, ui: &mut egui::Ui) {
+    egui::Grid::new("sim params grid")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Time step");
+            ui.add(
+                egui::DragValue::new(&mut params.time_step)
+                    .speed(0.0001)
+                    .clamp_range(0.0001..=0.02),
+            );
+            ui.end_row();
+            ui.label("Integrator");
+            egui::ComboBox::from_id_source("integrator")
+                .selected_text(params.integrator.name())
+                .show_ui(ui, |ui| {
+                    for integrator in Integrator::ALL {
+                        ui.selectable_value(&mut params.integrator, integrator, integrator.name());
+                    }
+                });
+            ui.end_row();
+            ui.label("Substeps")
+                .on_hover_text("Solver steps per frame, submitted together");
+            ui.add(egui::DragValue::new(&mut params.substeps).clamp_range(1..=16));
+            ui.end_row();
+            ui.label("Stiffness");
+            ui.add(egui::DragValue::new(&mut params.stiffness).clamp_range(0.0..=1.0e6));
+            ui.end_row();
+            ui.label("Viscosity");
+            ui.add(
+                egui::DragValue::new(&mut params.viscosity)
+                    .speed(0.001)
+                    .clamp_range(0.0..=0.2),
+            );
+            ui.end_row();
+            ui.label("Material viscosity iterations")
+                .on_hover_text("Jacobi iterations of the implicit solve for viscous materials");
+            ui.add(egui::DragValue::new(&mut params.viscosity_iterations).clamp_range(0..=16));
+            ui.end_row();
+            ui.label("Reorder interval")
+                .on_hover_text("Frames between sorting the particles by grid cell, 0 never sorts");
+            ui.add(egui::DragValue::new(&mut params.reorder_interval).clamp_range(0..=1000));
+            ui.end_row();
+            ui.label("Particle order");
+            ui.horizontal(|ui| {
+                for order in ParticleOrder::ALL {
+                    ui.selectable_value(&mut params.particle_order, order, order.name());
+                }
+            });
+            ui.end_row();
+
+            ui.checkbox(&mut params.vorticity_enabled, "Vorticity confinement")
+                .on_hover_text("Adds back the small swirls numerical damping removes");
+            ui.add_enabled(
+                params.vorticity_enabled,
+                egui::DragValue::new(&mut params.vorticity)
+                    .speed(0.001)
+                    .clamp_range(0.0..=1.0),
+            );
+            ui.end_row();
+            ui.checkbox(&mut params.xsph_enabled, "XSPH smoothing")
+                .on_hover_text("Blends each velocity with its neighbours to reduce jitter");
+            ui.add_enabled(
+                params.xsph_enabled,
+                egui::DragValue::new(&mut params.xsph)
+                    .speed(0.001)
+                    .clamp_range(0.0..=1.0),
+            );
+            ui.end_row();
+
+            ui.checkbox(&mut params.diffuse.enabled, "Spray and foam")
+                .on_hover_text("Secondary particles from trapped air and wave crests");
+            ui.end_row();
+            ui.add_enabled_ui(params.diffuse.enabled, |ui| ui.label("Trapped air rate"));
+            ui.add_enabled(
+                params.diffuse.enabled,
+                egui::DragValue::new(&mut params.diffuse.trapped_air).clamp_range(0.0..=500.0),
+            );
+            ui.end_row();
+            ui.add_enabled_ui(params.diffuse.enabled, |ui| ui.label("Wave crest rate"));
+            ui.add_enabled(
+                params.diffuse.enabled,
+                egui::DragValue::new(&mut params.diffuse.wave_crest).clamp_range(0.0..=500.0),
+            );
+            ui.end_row();
+            ui.add_enabled_ui(params.diffuse.enabled, |ui| ui.label("Lifetime"));
+            ui.add_enabled(
+                params.diffuse.enabled,
+                egui::DragValue::new(&mut params.diffuse.lifetime)
+                    .speed(0.1)
+                    .clamp_range(0.1..=20.0),
+            );
+            ui.end_row();
+
+            ui.checkbox(&mut params.deterministic, "Deterministic")
+                .on_hover_text("Spawn the diffuse particles in order, reproducible on one GPU");
+            ui.end_row();
+        });
+}
+
+fn vector_ui(ui: &mut egui::Ui, label: &str, value: &mut Vector3<f32>, speed: f64) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(egui::DragValue::new(&mut value.x).speed(speed).prefix("x "));
+        ui.add(egui::DragValue::new(&mut value.y).speed(speed).prefix("y "));
+        ui.add(egui::DragValue::new(&mut value.z).speed(speed).prefix("z "));
+    });
+}
+
+fn force_fields_ui(gravity: &mut Vector3<f32>, fields: &mut Vec<ForceField>, ui: &mut egui::Ui) {
+    vector_ui(ui, "Gravity", gravity, 0.1);
+    ui.separator();
+
+    let mut removed = None;
+    for (i, field) in fields.iter_mut().enumerate() {
+        ui.push_id(i, |ui| {
+            ui.horizontal(|ui| {
+                let mut kind = field.kind.index();
+                egui::ComboBox::from_label("")
+                    .selected_text(ForceKind::NAMES[kind])
+                    .show_index(ui, &mut kind, ForceKind::NAMES.len(), |k| {
+                        ForceKind::NAMES[k]
+                    });
+                if kind != field.kind.index() {
+                    field.kind = ForceKind::from_index(kind);
+                }
+                if ui.button("🗑").on_hover_text("Remove").clicked() {
+                    removed = Some(i);
+                }
+            });
+            force_field_ui(field, ui);
+        });
+        ui.separator();
+    }
+    if let Some(i) = removed {
+        fields.remove(i);
+    }
+
+    ui.add_enabled_ui(fields.len() < MAX_FORCE_FIELDS, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Add");
+            for (index, name) in ForceKind::NAMES.iter().enumerate() {
+                if ui.button(*name).clicked() {
+                    let kind = ForceKind::from_index(index);
+                    fields.push(ForceField::new(kind, Vector3::new(5.0, 5.0, 5.0), 5.0));
+                }
+            }
+        });
+    });
+}
+
+fn force_field_ui(field: &mut ForceField, ui: &mut egui::Ui) {
+    egui::Grid::new("force field grid")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Strength");
+            ui.add(egui::DragValue::new(&mut field.strength).speed(0.1));
+            ui.end_row();
+            match &mut field.kind {
+                ForceKind::Attractor => {}
+                ForceKind::Vortex { axis } => {
+                    // the vortex keeps its last axis with a direction
+                    let previous = *axis;
+                    vector_ui(ui, "Axis", axis, 0.01);
+                    if axis.magnitude2() == 0.0 {
+                        *axis = previous;
+                    }
+                    ui.end_row();
+                }
+                ForceKind::Wind { velocity } => {
+                    vector_ui(ui, "Velocity", velocity, 0.1);
+                    ui.end_row();
+                }
+                ForceKind::Turbulence { scale } => {
+                    ui.label("Scale");
+                    ui.add(
+                        egui::DragValue::new(scale)
+                            .speed(0.01)
+                            .clamp_range(0.01..=10.0),
+                    );
+                    ui.end_row();
+                }
+            }
+
+            let mut bounded = field.radius.is_finite();
+            ui.checkbox(&mut bounded, "Region")
+                .on_hover_text("Limit the force to a sphere, fading out over the falloff");
+            if bounded != field.radius.is_finite() {
+                field.radius = if bounded { 2.0 } else { f32::INFINITY };
+            }
+            ui.end_row();
+            vector_ui(ui, "Centre", &mut field.center, 0.05);
+            ui.end_row();
+            if bounded {
+                ui.label("Radius");
+                ui.add(
+                    egui::DragValue::new(&mut field.radius)
+                        .speed(0.05)
+                        .clamp_range(0.0..=100.0),
+                );
+                ui.end_row();
+                ui.label("Falloff");
+                ui.add(
+                    egui::DragValue::new(&mut field.falloff)
+                        .speed(0.05)
+                        .clamp_range(0.0..=100.0),
+                );
+                ui.end_row();
+            }
+
+            ui.label("Pulse")
+                .on_hover_text("Relative strength variation and its frequency in Hz");
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut field.pulse_amplitude)
+                        .speed(0.01)
+                        .clamp_range(0.0..=1.0),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut field.pulse_frequency)
+                        .speed(0.01)
+                        .clamp_range(0.0..=10.0)
+                        .suffix(" Hz"),
+                );
+            });
+            ui.end_row();
+            vector_ui(ui, "Sweep", &mut field.sweep, 0.05);
+            ui.add(
+                egui::DragValue::new(&mut field.sweep_frequency)
+                    .speed(0.01)
+                    .clamp_range(0.0..=10.0)
+                    .suffix(" Hz"),
+            );
+            ui.end_row();
+        });
+}
+
+fn color_settings_ui(settings: &mut ColorSettings, ui: &mut egui::Ui) {
+    ui.horizontal(|ui| {
+        for mode in ColorMode::ALL {
+            ui.selectable_value(&mut settings.mode, mode, mode.name());
+        }
+    });
+    ui.add_enabled_ui(settings.mode == ColorMode::Temperature, |ui| {
+        let [low, high] = &mut settings.temperature_range;
+        ui.horizontal(|ui| {
+            ui.label("Range °C");
+            ui.add(egui::DragValue::new(low).speed(1.0));
+            ui.add(egui::DragValue::new(high).speed(1.0));
+        });
+    });
+    ui.add_enabled_ui(settings.mode == ColorMode::Dye, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Channels");
+            for color in &mut settings.scalar_colors {
+                ui.color_edit_button_rgb(color);
+            }
+        });
+    });
+}
+
 /// struct used to record fps
 pub struct FrameHistory {
     frame_times: History<f32>,
@@ -274,7 +588,7 @@ impl FrameHistory {
 
         let rect = rect.shrink(4.0);
         let color = ui.visuals().text_color();
-        let line_stroke = Stroke::new(1.0, color);
+        let line_stroke = Stroke::new(1.0_f32, color);
 
         if let Some(pointer_pos) = response.hover_pos() {
             let y = pointer_pos.y;
@@ -319,3 +633,157 @@ impl FrameHistory {
         response
     }
 }
+
+fn neighbor_stats_ui(stats: &NeighborStats, ui: &mut egui::Ui) {
+    if stats.overflowed() {
+        ui.colored_label(ui.visuals().warn_fg_color, "⚠ Neighbour search overflow")
+            .on_hover_text("The physics of these particles misses some of their neighbours");
+        ui.label(format!(
+            "{} particles left out of full cells (capacity {})",
+            stats.cell_overflow, MAX_NUM_PARTICLES_PER_CELL
+        ));
+        ui.label(format!(
+            "{} neighbour lists truncated (capacity {})",
+            stats.neighbor_overflow, MAX_NUM_NEIGHBORS
+        ));
+    }
+    if stats.hash_collisions > 0 {
+        ui.label(format!(
+            "Hash collisions: {:.1}% of the candidates",
+            100.0 * stats.collision_rate()
+        ))
+        .on_hover_text("Candidates from other cells in the same bucket, a larger table has fewer");
+    }
+}
+
+/// plot line name and the diagnostics value it shows
+type DiagnosticsLine<'a> = (&'a str, fn(&SimDiagnostics) -> f32);
+
+/// struct used to record the simulation diagnostics reduced on the GPU
+pub struct SimStatsHistory {
+    samples: VecDeque<SimDiagnostics>,
+    max_len: usize,
+}
+
+impl Default for SimStatsHistory {
+    fn default() -> Self {
+        let max_len = 600;
+        Self {
+            samples: VecDeque::with_capacity(max_len),
+            max_len,
+        }
+    }
+}
+
+impl SimStatsHistory {
+    pub fn on_new_diagnostics(&mut self, diagnostics: SimDiagnostics) {
+        if self.samples.len() >= self.max_len {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(diagnostics);
+    }
+
+    /// total energy change relative to the oldest recorded sample
+    fn energy_drift(&self) -> Option<f32> {
+        let first = self.samples.front()?.total_energy();
+        let last = self.samples.back()?.total_energy();
+        if first.abs() < f32::EPSILON {
+            return None;
+        }
+        Some((last - first) / first.abs())
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let Some(latest) = self.samples.back() else {
+            ui.label("Waiting for simulation diagnostics...");
+            return;
+        };
+
+        egui::Grid::new("sim stats grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Solver step");
+                ui.label(format!("{}", latest.step));
+                ui.end_row();
+                ui.label("Kinetic energy");
+                ui.label(format!("{:.4e} J", latest.kinetic_energy));
+                ui.end_row();
+                ui.label("Potential energy");
+                ui.label(format!("{:.4e} J", latest.potential_energy));
+                ui.end_row();
+                ui.label("Energy drift");
+                ui.label(match self.energy_drift() {
+                    Some(drift) => format!("{:+.3}%", 100.0 * drift),
+                    None => "-".to_owned(),
+                })
+                .on_hover_text("Total energy change over the recorded history");
+                ui.end_row();
+                ui.label("Momentum");
+                ui.label(format!(
+                    "({:.2}, {:.2}, {:.2})",
+                    latest.momentum[0], latest.momentum[1], latest.momentum[2]
+                ));
+                ui.end_row();
+                ui.label("Max velocity");
+                ui.label(format!("{:.3} m/s", latest.max_velocity));
+                ui.end_row();
+                ui.label("Density error");
+                ui.label(format!(
+                    "mean {:.2}% / max {:.2}%",
+                    100.0 * latest.mean_density_error,
+                    100.0 * latest.max_density_error
+                ));
+                ui.end_row();
+            });
+
+        egui::CollapsingHeader::new("📈 Energy history")
+            .default_open(false)
+            .show(ui, |ui| {
+                self.graph(
+                    ui,
+                    "energy plot",
+                    &[
+                        ("kinetic", |d| d.kinetic_energy),
+                        ("potential", |d| d.potential_energy),
+                        ("total", SimDiagnostics::total_energy),
+                    ],
+                );
+            });
+
+        egui::CollapsingHeader::new("📈 Velocity and density history")
+            .default_open(false)
+            .show(ui, |ui| {
+                self.graph(ui, "velocity plot", &[("max velocity", |d| d.max_velocity)]);
+                self.graph(
+                    ui,
+                    "density error plot",
+                    &[
+                        ("mean density error", |d| d.mean_density_error),
+                        ("max density error", |d| d.max_density_error),
+                    ],
+                );
+            });
+    }
+
+    fn graph(&self, ui: &mut egui::Ui, id: &str, lines: &[DiagnosticsLine]) -> egui::Response {
+        use egui_plot::{Legend, Line, Plot, PlotPoints};
+
+        Plot::new(id)
+            .height(ui.spacing().slider_width)
+            .legend(Legend::default())
+            .allow_drag(false)
+            .allow_scroll(false)
+            .allow_zoom(false)
+            .show(ui, |plot_ui| {
+                for (name, value) in lines {
+                    let points: PlotPoints = self
+                        .samples
+                        .iter()
+                        .map(|d| [d.step as f64, value(d) as f64])
+                        .collect();
+                    plot_ui.line(Line::new(points).name(*name));
+                }
+            })
+            .response
+    }
+}
@@ -0,0 +1,236 @@
+//! Headless simulation path: steps the SPH solver without a window or surface.
+//! Used by the validation scenarios and the integration tests.
+use tracing::info;
+
+pub use crate::particle_system::container::{
+    ContainerAnimation, ContainerKeyframe, ContainerPose, ContainerWall, Piston, Shake,
+};
+pub use crate::particle_system::flow::{Inlet, VelocityProfile};
+pub use crate::particle_system::fluid_material::{
+    Elastic, FluidMaterial, Granular, ThermalViscosity, ViscosityModel,
+};
+pub use crate::particle_system::force_field::{ForceField, ForceKind, MAX_FORCE_FIELDS};
+pub use crate::particle_system::grid::{
+    NeighborStats, MAX_NUM_NEIGHBORS, MAX_NUM_PARTICLES_PER_CELL,
+};
+pub use crate::particle_system::obstacle::{Obstacle, ObstacleMotion, ObstacleShape};
+pub use crate::particle_system::particle_layout::{solver_pass_fields, ParticleFields, PassFields};
+pub use crate::particle_system::particles::Particle;
+pub use crate::particle_system::readback::{ParticleSnapshot, SnapshotTicket, READBACK_RING_SIZE};
+pub use crate::particle_system::scalar::{ScalarChannel, ScalarSource, MAX_SCALARS};
+pub use crate::particle_system::scene::{
+    DiffuseParams, HeatSource, Integrator, NeighborSearch, ParticleLayout, ParticleOrder, Scene,
+    SimParams, SolverMode, SpawnBlock, AMBIENT_TEMPERATURE,
+};
+pub use crate::renderer::compute_pass_diffuse::{DiffuseKind, DiffuseParticle};
+pub use crate::renderer::compute_pass_reduction::SimDiagnostics;
+
+use crate::particle_system::ParticleState;
+use crate::renderer::{
+    compute_pass_diffuse::ComputeDiffusePass, compute_pass_particle::ComputeParticlePass,
+    compute_pass_reduction::ComputeReductionPass, BindGroupLayoutCache,
+};
+
+/// no software adapter on this machine, the only error a caller may want to tell apart
+/// from a broken scene or device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoAdapter;
+
+impl std::fmt::Display for NoAdapter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no software adapter available")
+    }
+}
+
+impl std::error::Error for NoAdapter {}
+
+pub struct HeadlessSim {
+    pub adapter_info: wgpu::AdapterInfo,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+
+    particle_state: ParticleState,
+    compute_particle_pass: ComputeParticlePass,
+    compute_diffuse_pass: ComputeDiffusePass,
+    compute_reduction_pass: ComputeReductionPass,
+    /// read back from the cached neighbour search so far
+    neighbor_stats: NeighborStats,
+
+    steps: u64,
+}
+
+impl HeadlessSim {
+    /// create the simulation on a software (CPU) adapter, so results don't depend on the local GPU
+    pub async fn new(scene: &Scene) -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::default();
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: true,
+            })
+            .await
+            .ok_or(NoAdapter)?;
+        let adapter_info = adapter.get_info();
+        info!("headless adapter: {:?}", adapter_info);
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("Headless Device"),
+                    required_features: wgpu::Features::empty(),
+                    required_limits: adapter.limits(),
+                },
+                None,
+            )
+            .await?;
+
+        let bind_group_layout_cache = BindGroupLayoutCache::new(&device);
+        let mut particle_state = ParticleState::new(&device, &bind_group_layout_cache, scene)?;
+        let mut compute_particle_pass =
+            ComputeParticlePass::new(&device, &bind_group_layout_cache, scene).await;
+        compute_particle_pass
+            .relax(&device, &queue, &mut particle_state)
+            .await;
+        let compute_diffuse_pass =
+            ComputeDiffusePass::new(&device, &bind_group_layout_cache, scene).await?;
+        let compute_reduction_pass =
+            ComputeReductionPass::new(&device, &bind_group_layout_cache, scene.layout).await;
+
+        Ok(Self {
+            adapter_info,
+            device,
+            queue,
+            particle_state,
+            compute_particle_pass,
+            compute_diffuse_pass,
+            compute_reduction_pass,
+            neighbor_stats: NeighborStats::default(),
+            steps: 0,
+        })
+    }
+
+    /// advance the simulation by one frame of `SimParams::substeps` solver steps, same
+    /// passes as `Renderer::render`
+    pub async fn step(&mut self) {
+        self.compute_particle_pass.exchange_flow_particles(
+            &self.device,
+            &self.queue,
+            &mut self.particle_state,
+        );
+        self.compute_particle_pass
+            .sort_particle_data(&self.device, &self.queue, &mut self.particle_state)
+            .await;
+        self.compute_particle_pass
+            .compute_sph(&self.device, &self.queue, &mut self.particle_state);
+        self.compute_reduction_pass
+            .reduce(&self.device, &self.queue, &self.particle_state);
+        if self.particle_state.sim_params.diffuse.enabled {
+            self.compute_diffuse_pass
+                .compute(&self.device, &self.queue, &self.particle_state);
+        }
+        self.particle_state
+            .end_frame_readback(&self.device, &self.queue);
+        self.poll_neighbor_stats();
+        self.steps += 1;
+    }
+
+    fn poll_neighbor_stats(&mut self) {
+        if let Some(stats) = self.compute_particle_pass.poll_neighbor_stats(&self.device) {
+            self.neighbor_stats += stats;
+        }
+    }
+
+    /// overflow and hash collisions of the cached neighbour search since the start,
+    /// always empty with `NeighborSearch::AllPairs`. blocks until the GPU is done
+    pub fn neighbor_stats(&mut self) -> NeighborStats {
+        self.device.poll(wgpu::Maintain::Wait);
+        self.poll_neighbor_stats();
+        self.neighbor_stats
+    }
+
+    /// the diagnostics reduced since the last call, oldest first. a step is missing when
+    /// every slot of the readback ring was still in flight. blocks until the GPU is done
+    pub fn diagnostics(&mut self) -> Vec<SimDiagnostics> {
+        self.device.poll(wgpu::Maintain::Wait);
+        self.compute_reduction_pass.poll_diagnostics(&self.device)
+    }
+
+    /// volume of a particle at rest density, m³. its mass is this times the rest density
+    pub fn rest_volume(&self) -> f32 {
+        self.particle_state.world_data.rest_volume
+    }
+
+    /// the solver passes a step dispatches with the current parameters, in order, see
+    /// `solver_pass_fields`
+    pub fn solver_passes(&self) -> Vec<&'static str> {
+        self.compute_particle_pass
+            .solver_passes(&self.particle_state)
+    }
+
+    /// frames stepped, each runs `SimParams::substeps` solver steps
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// simulated time in seconds
+    pub fn time(&self) -> f32 {
+        let params = self.particle_state.sim_params;
+        self.steps as f32 * params.substeps.max(1) as f32 * params.time_step
+    }
+
+    /// the live particles, blocks until the GPU is done
+    pub async fn num_particles(&self) -> usize {
+        self.particle_state
+            .read_count(&self.device, &self.queue)
+            .await
+    }
+
+    /// read the latest particle buffer back, blocks until the GPU is done
+    pub async fn read_particles(&mut self) -> Vec<Particle> {
+        self.particle_state
+            .dump_particle_data_from_gpu(&self.device, &self.queue)
+            .await;
+        self.particle_state
+            .particle_data
+            .iter()
+            .map(Particle::from_raw)
+            .collect()
+    }
+
+    /// snapshot of the particles at the end of the next step, see `take_snapshot`
+    pub fn request_snapshot(&mut self) -> SnapshotTicket {
+        self.particle_state.readback.request()
+    }
+
+    /// the snapshot of `ticket` once it finished reading back, never blocks. it arrives a
+    /// step or two after the one it was taken at
+    pub fn take_snapshot(&mut self, ticket: SnapshotTicket) -> Option<ParticleSnapshot> {
+        self.particle_state.readback.take_snapshot(ticket)
+    }
+
+    /// steps since `snapshot` was taken
+    pub fn snapshot_age(&self, snapshot: &ParticleSnapshot) -> u64 {
+        self.particle_state.readback.age(snapshot)
+    }
+
+    /// FNV-1a hash of the raw bytes of the latest particle buffer, equal across runs of the
+    /// same scene with `SimParams::deterministic` on the same adapter
+    pub async fn particle_hash(&mut self) -> u64 {
+        self.particle_state
+            .dump_particle_data_from_gpu(&self.device, &self.queue)
+            .await;
+        bytemuck::cast_slice::<_, u8>(&self.particle_state.particle_data)
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+            })
+    }
+
+    /// live spray, foam and bubble particles, empty unless `SimParams::diffuse` is enabled
+    pub async fn read_diffuse_particles(&self) -> Vec<DiffuseParticle> {
+        self.compute_diffuse_pass
+            .read_diffuse_particles(&self.device, &self.queue)
+            .await
+    }
+}
@@ -0,0 +1,203 @@
+//! Mouse tools that push, pull and stir the fluid. The cursor is turned into a
+//! world space ray, the compute shader applies the force around it (interaction.h.wgsl)
+use cgmath::{InnerSpace, SquareMatrix, Vector4};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, MouseButton, WindowEvent},
+};
+
+use crate::camera::Camera;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MouseTool {
+    #[default]
+    Attract,
+    Repel,
+    Stir,
+}
+
+impl MouseTool {
+    pub const ALL: [MouseTool; 3] = [MouseTool::Attract, MouseTool::Repel, MouseTool::Stir];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MouseTool::Attract => "Attract",
+            MouseTool::Repel => "Repel",
+            MouseTool::Stir => "Stir",
+        }
+    }
+
+    /// interaction.h.wgsl MOUSE_TOOL_*
+    fn shader_id(&self) -> u32 {
+        match self {
+            MouseTool::Attract => 1,
+            MouseTool::Repel => 2,
+            MouseTool::Stir => 3,
+        }
+    }
+}
+
+/// interaction.h.wgsl MouseInteraction, `tool == 0` disables the force
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MouseInteractionRaw {
+    ray_origin: [f32; 3],
+    radius: f32,
+    ray_dir: [f32; 3],
+    strength: f32,
+    tool: u32,
+    _pad: [u32; 3],
+}
+
+pub struct MouseInteraction {
+    pub tool: MouseTool,
+    /// distance from the ray the force reaches
+    pub radius: f32,
+    /// acceleration on the ray, in m/s²
+    pub strength: f32,
+
+    cursor: Option<PhysicalPosition<f64>>,
+    is_pressed: bool,
+}
+
+impl MouseInteraction {
+    pub fn new() -> Self {
+        Self {
+            tool: MouseTool::default(),
+            radius: 1.5,
+            strength: 40.0,
+            cursor: None,
+            is_pressed: false,
+        }
+    }
+
+    /// only reacts to the mouse, the keyboard is left to `CameraController`
+    pub fn process_events(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some(*position);
+                false
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.is_pressed = false;
+                false
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.is_pressed = *state == ElementState::Pressed;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// cast the ray through the cursor, disabled unless the left button is held
+    pub fn uniform(&self, camera: &Camera, screen_size: PhysicalSize<u32>) -> MouseInteractionRaw {
+        let Some(cursor) = self.cursor.filter(|_| self.is_pressed) else {
+            return MouseInteractionRaw::default();
+        };
+        let Some(view_proj_inv) =
+            (camera.build_projection_matrix() * camera.build_view_matrix()).invert()
+        else {
+            return MouseInteractionRaw::default();
+        };
+
+        let ndc_x = 2.0 * cursor.x as f32 / screen_size.width as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * cursor.y as f32 / screen_size.height as f32;
+        let unproject = |ndc_z: f32| {
+            let p = view_proj_inv * Vector4::new(ndc_x, ndc_y, ndc_z, 1.0);
+            p.truncate() / p.w
+        };
+        let near = unproject(0.0);
+        let far = unproject(1.0);
+
+        MouseInteractionRaw {
+            ray_origin: near.into(),
+            radius: self.radius,
+            ray_dir: (far - near).normalize().into(),
+            strength: self.strength,
+            tool: self.tool.shader_id(),
+            _pad: [0; 3],
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            for tool in MouseTool::ALL {
+                ui.selectable_value(&mut self.tool, tool, tool.name());
+            }
+        });
+        ui.add(egui::Slider::new(&mut self.radius, 0.1..=5.0).text("Radius"));
+        ui.add(egui::Slider::new(&mut self.strength, 0.0..=200.0).text("Strength"));
+        ui.weak("Hold the left mouse button in the scene to apply");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Transform, Vector3};
+
+    use super::*;
+
+    const SCREEN: PhysicalSize<u32> = PhysicalSize::new(800, 600);
+
+    /// the ray of the left button held at pixel `x`, `y`
+    fn ray(camera: &Camera, x: f64, y: f64) -> (Vector3<f32>, Vector3<f32>) {
+        let mouse = MouseInteraction {
+            cursor: Some(PhysicalPosition::new(x, y)),
+            is_pressed: true,
+            ..MouseInteraction::new()
+        };
+        let raw = mouse.uniform(camera, SCREEN);
+        (raw.ray_origin.into(), raw.ray_dir.into())
+    }
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>, what: &str) {
+        assert!(
+            (a - b).magnitude() < 1.0e-3,
+            "{what}: {a:?} instead of {b:?}"
+        );
+    }
+
+    #[test]
+    fn centre_pixel_looks_along_the_camera_axis() {
+        let camera = Camera::new(SCREEN.width as f32 / SCREEN.height as f32);
+        let (origin, dir) = ray(&camera, 400.0, 300.0);
+        let forward = (camera.target - camera.eye).normalize();
+        assert_close(dir, forward, "direction");
+        // starts on the near plane, straight ahead of the eye
+        let eye = Vector3::new(camera.eye.x, camera.eye.y, camera.eye.z);
+        assert_close(origin, eye + camera.znear * forward, "origin");
+    }
+
+    #[test]
+    fn corner_pixel_lies_on_the_frustum_edge() {
+        let camera = Camera::new(SCREEN.width as f32 / SCREEN.height as f32);
+        let (origin, dir) = ray(&camera, 0.0, 0.0);
+        // the top left edge of the frustum in view space, the camera looks down -z
+        let half_height = (0.5 * camera.fovy).to_radians().tan();
+        let edge = Vector3::new(-camera.aspect * half_height, half_height, -1.0);
+        let view = camera.build_view_matrix();
+        assert_close(view.transform_vector(dir), edge.normalize(), "direction");
+        let origin = view.transform_point(cgmath::Point3::new(origin.x, origin.y, origin.z));
+        assert_close(
+            Vector3::new(origin.x, origin.y, origin.z),
+            camera.znear * edge,
+            "origin",
+        );
+    }
+
+    #[test]
+    fn released_button_disables_the_force() {
+        let camera = Camera::new(1.0);
+        let mouse = MouseInteraction {
+            cursor: Some(PhysicalPosition::new(10.0, 10.0)),
+            ..MouseInteraction::new()
+        };
+        assert_eq!(mouse.uniform(&camera, SCREEN).tool, 0);
+    }
+}
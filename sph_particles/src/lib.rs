@@ -2,12 +2,15 @@ mod camera;
 mod renderer;
 // mod compute_depth_filter;
 mod gui;
+pub mod headless;
+mod interaction;
 // mod materials;
 mod model;
 mod particle_system;
 mod resources;
 mod texture;
 mod timer;
+pub mod validation;
 
 use std::sync::Arc;
 
@@ -30,7 +33,11 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 
-use crate::{particle_system::ParticleState, timer::Timer};
+use crate::{
+    particle_system::{scene::Scene, ParticleState},
+    renderer::render_pass_water::ColorSettings,
+    timer::Timer,
+};
 
 struct State {
     surface: wgpu::Surface<'static>,
@@ -58,7 +65,7 @@ struct State {
 
 impl State {
     // Creating some of the wgpu types requires async code
-    async fn new(window: Arc<Window>) -> Self {
+    async fn new(window: Arc<Window>) -> anyhow::Result<Self> {
         let size = window.inner_size();
 
         let scale_factor = window.scale_factor();
@@ -107,7 +114,13 @@ impl State {
                     required_limits: if cfg!(target_arch = "wasm32") {
                         wgpu::Limits::downlevel_defaults()
                     } else {
-                        wgpu::Limits::default()
+                        // the split particle layout binds more storage buffers than the default
+                        wgpu::Limits {
+                            max_storage_buffers_per_shader_stage: adapter
+                                .limits()
+                                .max_storage_buffers_per_shader_stage,
+                            ..Default::default()
+                        }
                     },
                     label: None,
                 },
@@ -166,18 +179,33 @@ impl State {
 
         let bind_group_layout_cache = BindGroupLayoutCache::new(&device);
 
-        let renderer = Renderer::new(
+        let scene = Scene::default();
+
+        let mut renderer = Renderer::new(
             &device,
             &queue,
             &camera,
             &surface_config,
             &bind_group_layout_cache,
+            &scene,
         )
-        .await;
+        .await?;
 
-        let particle_state = ParticleState::new(&device, &bind_group_layout_cache);
+        let mut particle_state = ParticleState::new(&device, &bind_group_layout_cache, &scene)?;
+        renderer
+            .compute_particle_pass
+            .relax(&device, &queue, &mut particle_state)
+            .await;
 
-        let ui_state = UILayer::new(&device, &surface_format, size, scale_factor);
+        let ui_state = UILayer::new(
+            &device,
+            &surface_format,
+            size,
+            scale_factor,
+            scene.params,
+            scene.force_fields.clone(),
+            ColorSettings::default().with_scalar_channels(&scene.scalars),
+        );
 
         let obj_model = resources::load_model(
             "Amago0.obj",
@@ -188,7 +216,7 @@ impl State {
         .await
         .unwrap();
 
-        Self {
+        Ok(Self {
             window: window.clone(),
             surface,
             device,
@@ -203,7 +231,7 @@ impl State {
             ui_state,
             particle_state,
             bind_group_layout_cache,
-        }
+        })
     }
 
     pub fn window(&self) -> &Window {
@@ -244,24 +272,44 @@ impl State {
 
     fn input(&mut self, event: &WindowEvent) -> bool {
         self.camera_controller.process_events(event)
+            || self.ui_state.mouse_interaction.process_events(event)
     }
 
     fn update(&mut self, delta_time: f32) {
         self.camera_controller
             .update_camera_state(&mut self.camera, delta_time);
+        let mouse = self
+            .ui_state
+            .mouse_interaction
+            .uniform(&self.camera, self.screen_size);
+        self.renderer
+            .compute_particle_pass
+            .set_mouse_interaction(mouse);
+        self.particle_state.sim_params = self.ui_state.sim_params;
+        self.particle_state
+            .force_fields
+            .clone_from(&self.ui_state.force_fields);
+        self.renderer
+            .render_quad_pass
+            .set_color_settings(self.ui_state.color_settings);
         self.renderer
             .update(&self.camera, &self.surface_config, &self.queue);
     }
 
-    fn render(&mut self, dt: f32) -> Result<(), wgpu::SurfaceError> {
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         let output = self.surface.get_current_texture()?;
 
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
+        // the particle count may change, before any pass binds the particle buffers
+        self.renderer.compute_particle_pass.exchange_flow_particles(
+            &self.device,
+            &self.queue,
+            &mut self.particle_state,
+        );
         self.renderer.render(
-            dt,
             &mut self.particle_state,
             &self.device,
             &self.queue,
@@ -269,6 +317,31 @@ impl State {
             &view,
         );
 
+        for diagnostics in self
+            .renderer
+            .compute_reduction_pass
+            .poll_diagnostics(&self.device)
+        {
+            self.ui_state
+                .sim_stats_history
+                .on_new_diagnostics(diagnostics);
+        }
+        if let Some(stats) = self
+            .renderer
+            .compute_particle_pass
+            .poll_neighbor_stats(&self.device)
+        {
+            // once when it starts, the GUI shows it for as long as it lasts
+            if stats.overflowed() && !self.ui_state.neighbor_stats.overflowed() {
+                warn!(
+                    "neighbour search overflow: {} particles left out of full cells, {} \
+                     neighbour lists truncated",
+                    stats.cell_overflow, stats.neighbor_overflow
+                );
+            }
+            self.ui_state.neighbor_stats = stats;
+        }
+
         // draw gui at last
         self.ui_state
             .render(&self.device, &self.queue, &self.window, &view);
@@ -321,7 +394,13 @@ pub async fn run() {
     let mut timer = Timer::new();
 
     // Need to be created after canvas is attached
-    let mut state = State::new(window.clone()).await;
+    let mut state = match State::new(window.clone()).await {
+        Ok(state) => state,
+        Err(err) => {
+            error!("the scene does not fit on this device: {err:#}");
+            return;
+        }
+    };
     // Register callbacks
     // These are buggy shift
     #[cfg(target_arch = "wasm32")]
@@ -417,7 +496,7 @@ pub async fn run() {
                                 // Get current time on frame start
                                 timer.render_timer = Instant::now();
 
-                                match state.render(dt) {
+                                match state.render() {
                                     Ok(_) => {}
                                     // Reconfigure the surface if lost
                                     Err(wgpu::SurfaceError::Lost) => {
@@ -1,7 +1,17 @@
+pub(crate) mod container;
+pub(crate) mod elastic;
+pub(crate) mod flow;
+pub(crate) mod fluid_material;
+pub(crate) mod force_field;
 pub(crate) mod grid;
+pub(crate) mod obstacle;
+pub(crate) mod particle_layout;
 pub(crate) mod particles;
+pub(crate) mod readback;
+pub(crate) mod scalar;
 pub(crate) mod gpu_pass;
-mod utils;
+pub(crate) mod scene;
+pub(crate) mod utils;
 
 pub(crate) use particles::ParticleState;
-pub(crate) use gpu_pass::{ComputeParticle, DrawParticle};
\ No newline at end of file
+pub(crate) use gpu_pass::{workgroup_grid, ComputeParticle, DrawParticle, PARTICLE_WORKGROUP_SIZE_X};
\ No newline at end of file
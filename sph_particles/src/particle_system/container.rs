@@ -0,0 +1,309 @@
+//! Animated container: the box holding the fluid can follow translation and rotation
+//! keyframes, shake sinusoidally and drive one of its walls as a piston, walls can also be
+//! left open so the particles leave the domain through them. The pose is
+//! evaluated on the CPU every step and uploaded with the world uniforms (world.h.wgsl)
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Matrix3, Quaternion, Rad, Rotation3, Vector3, VectorSpace, Zero};
+
+/// step used for the velocities, which are central differences of the pose
+pub(crate) const VELOCITY_EPSILON: f32 = 1e-3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ContainerKeyframe {
+    /// seconds
+    pub time: f32,
+    /// offset of the box from its rest position
+    pub translation: Vector3<f32>,
+    /// about the centre of the box
+    pub rotation: Quaternion<f32>,
+}
+
+impl ContainerKeyframe {
+    /// the rest pose at `time`
+    pub fn new(time: f32) -> Self {
+        Self {
+            time,
+            translation: Vector3::zero(),
+            rotation: Quaternion::new(1.0, 0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn with_translation(mut self, translation: Vector3<f32>) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    pub fn with_rotation(mut self, axis: Vector3<f32>, angle: Rad<f32>) -> Self {
+        self.rotation = Quaternion::from_axis_angle(axis.normalize(), angle);
+        self
+    }
+}
+
+/// sinusoidal translation on top of the keyframes
+#[derive(Debug, Clone, Copy)]
+pub struct Shake {
+    pub amplitude: Vector3<f32>,
+    /// Hz
+    pub frequency: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerWall {
+    MinX,
+    MaxX,
+    MinY,
+    MaxY,
+    MinZ,
+    MaxZ,
+}
+
+/// one wall moving in and out, starting at rest
+#[derive(Debug, Clone, Copy)]
+pub struct Piston {
+    pub wall: ContainerWall,
+    /// furthest the wall moves inwards
+    pub stroke: f32,
+    /// Hz
+    pub frequency: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ContainerPose {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    /// displacement of the lower and upper walls in container space
+    pub lower_offset: Vector3<f32>,
+    pub upper_offset: Vector3<f32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ContainerAnimation {
+    /// interpolated linearly and with slerp, the first and last pose are held outside
+    pub keyframes: Vec<ContainerKeyframe>,
+    pub shake: Option<Shake>,
+    pub piston: Option<Piston>,
+    /// walls the particles pass through, see `NeighborSearch::HashedList` for a
+    /// neighbour search that follows them out of the domain
+    pub open_walls: Vec<ContainerWall>,
+}
+
+impl ContainerAnimation {
+    pub fn is_static(&self) -> bool {
+        self.keyframes.is_empty() && self.shake.is_none() && self.piston.is_none()
+    }
+
+    pub fn with_keyframes(mut self, keyframes: Vec<ContainerKeyframe>) -> Self {
+        self.keyframes = keyframes;
+        self
+    }
+
+    pub fn with_shake(mut self, shake: Shake) -> Self {
+        self.shake = Some(shake);
+        self
+    }
+
+    pub fn with_piston(mut self, piston: Piston) -> Self {
+        self.piston = Some(piston);
+        self
+    }
+
+    pub fn with_open_wall(mut self, wall: ContainerWall) -> Self {
+        self.open_walls.push(wall);
+        self
+    }
+
+    pub fn pose(&self, time: f32) -> ContainerPose {
+        let (mut translation, rotation) = keyframe_pose(&self.keyframes, time);
+
+        if let Some(shake) = self.shake {
+            translation += shake.amplitude * (2.0 * PI * shake.frequency * time).sin();
+        }
+
+        let mut lower_offset = Vector3::zero();
+        let mut upper_offset = Vector3::zero();
+        if let Some(piston) = self.piston {
+            let travel = 0.5 * piston.stroke * (1.0 - (2.0 * PI * piston.frequency * time).cos());
+            match piston.wall {
+                ContainerWall::MinX => lower_offset.x = travel,
+                ContainerWall::MaxX => upper_offset.x = -travel,
+                ContainerWall::MinY => lower_offset.y = travel,
+                ContainerWall::MaxY => upper_offset.y = -travel,
+                ContainerWall::MinZ => lower_offset.z = travel,
+                ContainerWall::MaxZ => upper_offset.z = -travel,
+            }
+        }
+
+        ContainerPose {
+            translation,
+            rotation,
+            lower_offset,
+            upper_offset,
+        }
+    }
+
+    /// world space bounds of the box `lower`..`upper` over the whole animation, from the
+    /// walls' corners sampled through the keyframes and a period of the shake and the
+    /// piston. open walls count at their rest position
+    pub fn swept_bounds(
+        &self,
+        lower: Vector3<f32>,
+        upper: Vector3<f32>,
+    ) -> (Vector3<f32>, Vector3<f32>) {
+        let center = (lower + upper) / 2.0;
+        let period = |frequency: f32| {
+            if frequency > 0.0 {
+                1.0 / frequency
+            } else {
+                0.0
+            }
+        };
+        let duration = self.keyframes.last().map_or(0.0, |k| k.time.max(0.0))
+            + self.shake.map_or(0.0, |shake| period(shake.frequency))
+            + self.piston.map_or(0.0, |piston| period(piston.frequency));
+        let samples = (duration * SWEEP_SAMPLES_PER_SECOND).ceil() as u32;
+        let times = (0..=samples)
+            .map(|i| duration * i as f32 / samples.max(1) as f32)
+            .chain(self.keyframes.iter().map(|k| k.time.max(0.0)));
+
+        let mut swept_lower = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut swept_upper = -swept_lower;
+        for time in times {
+            let pose = self.pose(time);
+            let wall_lower = lower - center + pose.lower_offset;
+            let wall_upper = upper - center + pose.upper_offset;
+            for corner in 0..8 {
+                let local = Vector3::new(
+                    if corner & 1 == 0 {
+                        wall_lower.x
+                    } else {
+                        wall_upper.x
+                    },
+                    if corner & 2 == 0 {
+                        wall_lower.y
+                    } else {
+                        wall_upper.y
+                    },
+                    if corner & 4 == 0 {
+                        wall_lower.z
+                    } else {
+                        wall_upper.z
+                    },
+                );
+                let world = center + pose.translation + pose.rotation * local;
+                for axis in 0..3 {
+                    swept_lower[axis] = swept_lower[axis].min(world[axis]);
+                    swept_upper[axis] = swept_upper[axis].max(world[axis]);
+                }
+            }
+        }
+        (swept_lower, swept_upper)
+    }
+
+    /// the box `lower`..`upper` at `time`, the rotation is about its centre
+    pub fn to_raw(&self, lower: Vector3<f32>, upper: Vector3<f32>, time: f32) -> ContainerRaw {
+        let center = (lower + upper) / 2.0;
+        let pose = self.pose(time);
+        let before = self.pose(time - VELOCITY_EPSILON);
+        let after = self.pose(time + VELOCITY_EPSILON);
+        let rate = |a: Vector3<f32>, b: Vector3<f32>| (b - a) / (2.0 * VELOCITY_EPSILON);
+
+        let angular_velocity = angular_velocity(before.rotation, after.rotation);
+
+        // an open wall is out of reach
+        let mut wall_lower = lower - center + pose.lower_offset;
+        let mut wall_upper = upper - center + pose.upper_offset;
+        for wall in &self.open_walls {
+            match wall {
+                ContainerWall::MinX => wall_lower.x = -f32::MAX,
+                ContainerWall::MaxX => wall_upper.x = f32::MAX,
+                ContainerWall::MinY => wall_lower.y = -f32::MAX,
+                ContainerWall::MaxY => wall_upper.y = f32::MAX,
+                ContainerWall::MinZ => wall_lower.z = -f32::MAX,
+                ContainerWall::MaxZ => wall_upper.z = f32::MAX,
+            }
+        }
+
+        let rotation = Matrix3::from(pose.rotation);
+        let vec4 = |v: Vector3<f32>| [v.x, v.y, v.z, 0.0];
+        ContainerRaw {
+            rotation: [vec4(rotation.x), vec4(rotation.y), vec4(rotation.z)],
+            origin: (center + pose.translation).into(),
+            lower: wall_lower.into(),
+            upper: wall_upper.into(),
+            velocity: rate(before.translation, after.translation).into(),
+            angular_velocity: angular_velocity.into(),
+            lower_velocity: rate(before.lower_offset, after.lower_offset).into(),
+            upper_velocity: rate(before.upper_offset, after.upper_offset).into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// samples per second of animation in `ContainerAnimation::swept_bounds`
+const SWEEP_SAMPLES_PER_SECOND: f32 = 240.0;
+
+/// the pose of `keyframes` at `time`, interpolated linearly and with slerp. the first and
+/// last pose are held outside
+pub(crate) fn keyframe_pose(
+    keyframes: &[ContainerKeyframe],
+    time: f32,
+) -> (Vector3<f32>, Quaternion<f32>) {
+    let (Some(first), Some(last)) = (keyframes.first(), keyframes.last()) else {
+        return (Vector3::zero(), Quaternion::new(1.0, 0.0, 0.0, 0.0));
+    };
+    if time <= first.time {
+        return (first.translation, first.rotation);
+    }
+    if time >= last.time {
+        return (last.translation, last.rotation);
+    }
+
+    let next = keyframes
+        .iter()
+        .position(|k| k.time > time)
+        .unwrap_or(keyframes.len() - 1);
+    let (a, b) = (keyframes[next - 1], keyframes[next]);
+    let t = (time - a.time) / (b.time - a.time).max(f32::EPSILON);
+    (
+        a.translation.lerp(b.translation, t),
+        a.rotation.slerp(b.rotation, t),
+    )
+}
+
+/// angular velocity turning `before` into `after`, `2 * VELOCITY_EPSILON` apart
+pub(crate) fn angular_velocity(before: Quaternion<f32>, after: Quaternion<f32>) -> Vector3<f32> {
+    // rotation from `before` to `after`, along the shorter arc
+    let mut delta = after * before.conjugate();
+    if delta.s < 0.0 {
+        delta = -delta;
+    }
+    let sin_half = delta.v.magnitude();
+    if sin_half > 1e-6 {
+        let angle = 2.0 * sin_half.atan2(delta.s);
+        delta.v / sin_half * angle / (2.0 * VELOCITY_EPSILON)
+    } else {
+        Vector3::zero()
+    }
+}
+
+/// world.h.wgsl Container
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ContainerRaw {
+    rotation: [[f32; 4]; 3],
+    origin: [f32; 3],
+    _pad0: f32,
+    lower: [f32; 3],
+    _pad1: f32,
+    upper: [f32; 3],
+    _pad2: f32,
+    velocity: [f32; 3],
+    _pad3: f32,
+    angular_velocity: [f32; 3],
+    _pad4: f32,
+    lower_velocity: [f32; 3],
+    _pad5: f32,
+    upper_velocity: [f32; 3],
+    _pad6: f32,
+}
@@ -0,0 +1,176 @@
+//! Rest shape of the elastic solids (corotated SPH elasticity, Becker et al. 2009).
+//! Every elastic spawn block is one body: at spawn time each of its particles gets the
+//! list of body particles within the support radius and kernel gradients corrected so
+//! the deformation gradient is exactly the identity in the rest shape. The solver
+//! evaluates the stress from them every step, see elastic.h.wgsl
+use std::collections::HashMap;
+use std::ops::Range;
+
+use cgmath::{InnerSpace, Matrix, Matrix3, SquareMatrix, Vector3, Zero};
+
+use super::particles::Particle;
+
+/// elastic.h.wgsl ElasticParticle
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ElasticParticleRaw {
+    rest_position: [f32; 3],
+    first_neighbor: u32,
+    num_neighbors: u32,
+    _pad: [u32; 3],
+    /// quaternion, warm starts the rotation extraction of the next step
+    rotation: [f32; 4],
+    /// first Piola-Kirchhoff stress, written by the solver
+    stress: [[f32; 4]; 3],
+}
+
+/// elastic.h.wgsl ElasticNeighbor
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ElasticNeighborRaw {
+    gradient: [f32; 3],
+    index: u32,
+    neighbor_gradient: [f32; 3],
+    _pad: f32,
+}
+
+/// rest shape data of every particle, in particle order, and the neighbour lists they
+/// point into. particles outside `bodies` get an empty list
+pub fn rest_shape(
+    particles: &[Particle],
+    bodies: &[Range<usize>],
+    support_radius: f32,
+) -> (Vec<ElasticParticleRaw>, Vec<ElasticNeighborRaw>) {
+    let mut elastic_particles = particles
+        .iter()
+        .map(|p| ElasticParticleRaw {
+            rest_position: p.position.into(),
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    let mut elastic_neighbors = vec![];
+
+    for body in bodies {
+        let neighbors = body_neighbors(&particles[body.clone()], support_radius);
+
+        // kernel gradients times the inverse of their first moment, which makes the
+        // deformation gradient of the rest shape the identity
+        let corrections = neighbors
+            .iter()
+            .enumerate()
+            .map(|(i, list)| {
+                let mut moment = Matrix3::zero();
+                for &j in list {
+                    let x_ij =
+                        particles[body.start + j].position - particles[body.start + i].position;
+                    moment += outer(x_ij, kernel_gradient(x_ij, support_radius));
+                }
+                // a planar body or a single layer has nothing to correct across its
+                // thickness, the deformation gradient stays the identity along it
+                for axis in 0..3 {
+                    if moment[axis][axis] == 0.0 {
+                        moment[axis][axis] = 1.0;
+                    }
+                }
+                moment.invert().unwrap_or(Matrix3::identity()).transpose()
+            })
+            .collect::<Vec<_>>();
+
+        for (i, list) in neighbors.iter().enumerate() {
+            let elastic = &mut elastic_particles[body.start + i];
+            elastic.first_neighbor = elastic_neighbors.len() as u32;
+            elastic.num_neighbors = list.len() as u32;
+            for &j in list {
+                let x_ij = particles[body.start + j].position - particles[body.start + i].position;
+                let gradient = corrections[i] * kernel_gradient(x_ij, support_radius);
+                let neighbor_gradient = corrections[j] * kernel_gradient(-x_ij, support_radius);
+                elastic_neighbors.push(ElasticNeighborRaw {
+                    gradient: gradient.into(),
+                    index: (body.start + j) as u32,
+                    neighbor_gradient: neighbor_gradient.into(),
+                    _pad: 0.0,
+                });
+            }
+        }
+    }
+    (elastic_particles, elastic_neighbors)
+}
+
+/// the rest shape data after the particles were reordered, `permutation[new]` being the
+/// old index of each particle. the neighbour lists stay where they are, only the indices
+/// they hold are renumbered
+pub fn reorder(
+    elastic_particles: &[ElasticParticleRaw],
+    elastic_neighbors: &mut [ElasticNeighborRaw],
+    permutation: &[u32],
+) -> Vec<ElasticParticleRaw> {
+    let mut new_index = vec![0; permutation.len()];
+    for (new, &old) in permutation.iter().enumerate() {
+        new_index[old as usize] = new as u32;
+    }
+    for neighbor in elastic_neighbors {
+        neighbor.index = new_index[neighbor.index as usize];
+    }
+    permutation
+        .iter()
+        .map(|&old| elastic_particles[old as usize])
+        .collect()
+}
+
+/// indices into `particles` of the others within `support_radius` of each
+fn body_neighbors(particles: &[Particle], support_radius: f32) -> Vec<Vec<usize>> {
+    let cell = |position: Vector3<f32>| {
+        let c = position / support_radius;
+        (c.x.floor() as i32, c.y.floor() as i32, c.z.floor() as i32)
+    };
+    let mut grid = HashMap::<(i32, i32, i32), Vec<usize>>::new();
+    for (i, p) in particles.iter().enumerate() {
+        grid.entry(cell(p.position)).or_default().push(i);
+    }
+
+    particles
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let (x, y, z) = cell(p.position);
+            let mut list = vec![];
+            for dz in -1..=1 {
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        let Some(candidates) = grid.get(&(x + dx, y + dy, z + dz)) else {
+                            continue;
+                        };
+                        list.extend(candidates.iter().copied().filter(|&j| {
+                            let r = particles[j].position - p.position;
+                            j != i && r.magnitude() < support_radius
+                        }));
+                    }
+                }
+            }
+            list.sort_unstable();
+            list
+        })
+        .collect()
+}
+
+/// gradient of the cubic spline with respect to `x_ij`, up to the normalisation which the
+/// correction cancels
+fn kernel_gradient(x_ij: Vector3<f32>, support_radius: f32) -> Vector3<f32> {
+    let r = x_ij.magnitude();
+    let q = r / support_radius;
+    if q <= 1e-9 || q > 1.0 {
+        return Vector3::zero();
+    }
+    let derivative = if q <= 0.5 {
+        q * (3.0 * q - 2.0)
+    } else {
+        -(1.0 - q) * (1.0 - q)
+    };
+    x_ij * (derivative / (r * support_radius))
+}
+
+/// `a bᵀ`
+fn outer(a: Vector3<f32>, b: Vector3<f32>) -> Matrix3<f32> {
+    Matrix3::from_cols(a * b.x, a * b.y, a * b.z)
+}
@@ -0,0 +1,366 @@
+//! Inflow and outflow boundaries on the faces of the domain box. Inlets feed particles in
+//! at a prescribed velocity profile, outlets open their face and delete the particles that
+//! leave through it, so the particle count changes as the simulation runs. The particle
+//! buffers have room for a full domain, compute_flow.wgsl appends and deletes in place.
+//! Both keep a buffer zone one support radius deep inside their face (world.h.wgsl): inlet
+//! particles move with the profile until they leave it, and outlet particles can't turn
+//! back into the domain, so pressure waves don't reflect off the faces
+use cgmath::{Vector3, Zero};
+
+use super::container::ContainerWall;
+use super::fluid_material::FluidMaterial;
+use super::particles::Particle;
+use super::scalar::MAX_SCALARS;
+use super::scene::AMBIENT_TEMPERATURE;
+
+/// frames between the outlet sweeps of a scene without inlets, inlets sweep the outlets
+/// whenever they add particles. the particles past an outlet are still simulated until then
+pub(crate) const OUTLET_SYNC_FRAMES: u32 = 16;
+
+/// room for compressed fluid in `domain_capacity`, a fraction more than the rest lattice
+const CAPACITY_MARGIN: f32 = 1.25;
+
+/// particles a full box `lower`..`upper` holds on a lattice `diameter` apart, with room to
+/// pack closer under pressure. one layer deep in the planar solver
+pub fn domain_capacity(
+    lower: Vector3<f32>,
+    upper: Vector3<f32>,
+    diameter: f32,
+    planar: bool,
+) -> usize {
+    let axes = if planar { 2 } else { 3 };
+    let lattice = (0..axes)
+        .map(|i| ((upper[i] - lower[i]) / diameter).ceil().max(1.0))
+        .product::<f32>();
+    (lattice * CAPACITY_MARGIN) as usize
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VelocityProfile {
+    #[default]
+    Uniform,
+    /// Poiseuille flow: zero at the edges of the inlet, the inlet speed at its centre
+    Parabolic,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Inlet {
+    pub wall: ContainerWall,
+    /// m/s into the domain, at the centre for a parabolic profile
+    pub speed: f32,
+    pub profile: VelocityProfile,
+    /// the part of the face that feeds particles, the coordinates along the face normal
+    /// are ignored. the whole face by default
+    pub lower: Vector3<f32>,
+    pub upper: Vector3<f32>,
+    pub material: FluidMaterial,
+    /// °C
+    pub temperature: f32,
+    /// passive scalars of the particles it feeds in, see `Scene::scalars`
+    pub scalars: [f32; MAX_SCALARS],
+}
+
+impl Inlet {
+    pub fn new(wall: ContainerWall, speed: f32) -> Self {
+        Self {
+            wall,
+            speed,
+            profile: VelocityProfile::Uniform,
+            lower: Vector3::new(f32::MIN, f32::MIN, f32::MIN),
+            upper: Vector3::new(f32::MAX, f32::MAX, f32::MAX),
+            material: FluidMaterial::default(),
+            temperature: AMBIENT_TEMPERATURE,
+            scalars: [0.0; MAX_SCALARS],
+        }
+    }
+
+    pub fn with_profile(mut self, profile: VelocityProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    pub fn with_region(mut self, lower: Vector3<f32>, upper: Vector3<f32>) -> Self {
+        self.lower = lower;
+        self.upper = upper;
+        self
+    }
+
+    pub fn with_material(mut self, material: FluidMaterial) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn with_scalar(mut self, channel: usize, value: f32) -> Self {
+        self.scalars[channel] = value;
+        self
+    }
+
+    /// the region clipped to the face of the box `lower`..`upper`
+    fn face_region(
+        &self,
+        lower: Vector3<f32>,
+        upper: Vector3<f32>,
+    ) -> (Vector3<f32>, Vector3<f32>) {
+        let (axis, _) = wall_axis(self.wall);
+        let mut region_lower = lower;
+        let mut region_upper = upper;
+        for i in (0..3).filter(|&i| i != axis) {
+            region_lower[i] = self.lower[i].clamp(lower[i], upper[i]);
+            region_upper[i] = self.upper[i].clamp(lower[i], upper[i]);
+        }
+        (region_lower, region_upper)
+    }
+
+    pub fn to_raw(&self, lower: Vector3<f32>, upper: Vector3<f32>) -> FlowFaceRaw {
+        let (lower, upper) = self.face_region(lower, upper);
+        FlowFaceRaw {
+            lower: lower.into(),
+            kind: FLOW_INLET,
+            upper: upper.into(),
+            speed: self.speed,
+            profile: match self.profile {
+                VelocityProfile::Uniform => 0,
+                VelocityProfile::Parabolic => 1,
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// the axis of the normal of `wall`, and +1 for the walls whose normal into the domain
+/// points along it
+pub(crate) fn wall_axis(wall: ContainerWall) -> (usize, f32) {
+    match wall {
+        ContainerWall::MinX => (0, 1.0),
+        ContainerWall::MaxX => (0, -1.0),
+        ContainerWall::MinY => (1, 1.0),
+        ContainerWall::MaxY => (1, -1.0),
+        ContainerWall::MinZ => (2, 1.0),
+        ContainerWall::MaxZ => (2, -1.0),
+    }
+}
+
+fn wall_index(wall: ContainerWall) -> usize {
+    let (axis, inward) = wall_axis(wall);
+    2 * axis + (inward < 0.0) as usize
+}
+
+/// one line of particles entering through an inlet, a particle joins it every time the
+/// last one has moved a particle diameter into the domain
+#[derive(Debug, Clone, Copy)]
+struct InletColumn {
+    inlet: usize,
+    /// on the face
+    position: Vector3<f32>,
+    velocity: Vector3<f32>,
+    /// distance the last particle moved since it joined
+    travel: f32,
+}
+
+/// the inlets and outlets of a scene and the state of their columns
+#[derive(Debug, Clone, Default)]
+pub struct FlowBoundaries {
+    pub inlets: Vec<Inlet>,
+    pub outlets: Vec<ContainerWall>,
+    columns: Vec<InletColumn>,
+    /// index of the first inlet material in the material table
+    first_material: u32,
+    diameter: f32,
+    /// seconds simulated at the last `advance`
+    time: f32,
+    frames_since_sync: u32,
+}
+
+impl FlowBoundaries {
+    /// the columns of every inlet on a lattice of particles `diameter` apart, on the faces
+    /// of the box `lower`..`upper`. in the planar solver they only spread along x and y
+    pub fn new(
+        inlets: &[Inlet],
+        outlets: &[ContainerWall],
+        lower: Vector3<f32>,
+        upper: Vector3<f32>,
+        diameter: f32,
+        planar: bool,
+        first_material: u32,
+    ) -> Self {
+        let mut columns = vec![];
+        for (index, inlet) in inlets.iter().enumerate() {
+            let (axis, inward) = wall_axis(inlet.wall);
+            let (region_lower, region_upper) = inlet.face_region(lower, upper);
+            let face = if inward > 0.0 {
+                lower[axis]
+            } else {
+                upper[axis]
+            };
+
+            // the lattice positions along each tangent axis, one at z = 0 for planar scenes
+            let lattice = |i: usize| -> Vec<f32> {
+                if planar && i == 2 {
+                    return vec![0.0];
+                }
+                let count = ((region_upper[i] - region_lower[i]) / diameter).floor() as usize;
+                (0..count)
+                    .map(|k| region_lower[i] + (k as f32 + 0.5) * diameter)
+                    .collect()
+            };
+            let tangents = [0, 1, 2]
+                .into_iter()
+                .filter(|&i| i != axis)
+                .collect::<Vec<_>>();
+            for a in lattice(tangents[0]) {
+                for b in lattice(tangents[1]) {
+                    let mut position = Vector3::zero();
+                    position[axis] = face;
+                    position[tangents[0]] = a;
+                    position[tangents[1]] = b;
+
+                    // the profile across the region, per tangent axis with any extent
+                    let mut scale = 1.0;
+                    if inlet.profile == VelocityProfile::Parabolic {
+                        for &i in &tangents {
+                            if planar && i == 2 {
+                                continue;
+                            }
+                            let width = region_upper[i] - region_lower[i];
+                            let s = (position[i] - region_lower[i]) / width;
+                            scale *= 4.0 * s * (1.0 - s);
+                        }
+                    }
+                    let mut velocity = Vector3::zero();
+                    velocity[axis] = inward * inlet.speed * scale;
+                    columns.push(InletColumn {
+                        inlet: index,
+                        position,
+                        velocity,
+                        travel: 0.0,
+                    });
+                }
+            }
+        }
+
+        Self {
+            inlets: inlets.to_vec(),
+            outlets: outlets.to_vec(),
+            columns,
+            first_material,
+            diameter,
+            time: 0.0,
+            frames_since_sync: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inlets.is_empty() && self.outlets.is_empty()
+    }
+
+    /// the materials of the inlets, after those of the blocks
+    pub fn materials(&self) -> impl Iterator<Item = &FluidMaterial> {
+        self.inlets.iter().map(|inlet| &inlet.material)
+    }
+
+    /// the particles filling the buffer zones of the inlets at the start, `depth` deep
+    pub fn initial_particles(&self, depth: f32) -> Vec<Particle> {
+        let layers = (depth / self.diameter).ceil().max(1.0) as usize;
+        self.columns
+            .iter()
+            .flat_map(|column| {
+                (0..layers).map(move |layer| {
+                    let offset = (layer as f32 + 0.5) * self.diameter;
+                    self.column_particle(column, offset)
+                })
+            })
+            .collect()
+    }
+
+    fn column_particle(&self, column: &InletColumn, offset: f32) -> Particle {
+        let inlet = &self.inlets[column.inlet];
+        let (axis, inward) = wall_axis(inlet.wall);
+        let mut position = column.position;
+        position[axis] += inward * offset;
+        Particle {
+            position,
+            velocity: column.velocity,
+            material: self.first_material + column.inlet as u32,
+            temperature: inlet.temperature,
+            scalars: inlet.scalars,
+            ..Default::default()
+        }
+    }
+
+    /// advance the columns to `time` seconds simulated. true when particles are due to join
+    /// them, or the outlets are due a sweep
+    pub fn advance(&mut self, time: f32) -> bool {
+        let elapsed = time - self.time;
+        self.time = time;
+        self.frames_since_sync += 1;
+        let mut due = false;
+        for column in &mut self.columns {
+            let speed = column.velocity[wall_axis(self.inlets[column.inlet].wall).0].abs();
+            column.travel += speed * elapsed;
+            due |= column.travel >= self.diameter;
+        }
+        due || (!self.outlets.is_empty() && self.frames_since_sync >= OUTLET_SYNC_FRAMES)
+    }
+
+    /// the particles that joined the columns since the last call, placed as far in as
+    /// they have moved
+    pub fn take_new_particles(&mut self) -> Vec<Particle> {
+        self.frames_since_sync = 0;
+        let mut particles = vec![];
+        for i in 0..self.columns.len() {
+            while self.columns[i].travel >= self.diameter {
+                self.columns[i].travel -= self.diameter;
+                let offset = 0.5 * self.diameter + self.columns[i].travel;
+                particles.push(self.column_particle(&self.columns[i], offset));
+            }
+        }
+        particles
+    }
+
+    /// bit i for the outlet on face i, in the order of `ContainerWall`. a particle past one
+    /// of them has left the domain
+    pub fn outlet_mask(&self) -> u32 {
+        self.outlets
+            .iter()
+            .fold(0, |mask, &wall| mask | 1 << wall_index(wall))
+    }
+
+    /// world.h.wgsl FlowFace of every face of the box `lower`..`upper`, in the order of
+    /// `ContainerWall`
+    pub fn to_raw(&self, lower: Vector3<f32>, upper: Vector3<f32>) -> [FlowFaceRaw; 6] {
+        let mut faces = [FlowFaceRaw::default(); 6];
+        for inlet in &self.inlets {
+            faces[wall_index(inlet.wall)] = inlet.to_raw(lower, upper);
+        }
+        for &wall in &self.outlets {
+            faces[wall_index(wall)] = FlowFaceRaw {
+                lower: lower.into(),
+                kind: FLOW_OUTLET,
+                upper: upper.into(),
+                ..Default::default()
+            };
+        }
+        faces
+    }
+}
+
+/// world.h.wgsl FlowFace kinds
+const FLOW_INLET: u32 = 1;
+const FLOW_OUTLET: u32 = 2;
+
+/// world.h.wgsl FlowFace
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FlowFaceRaw {
+    lower: [f32; 3],
+    kind: u32,
+    upper: [f32; 3],
+    speed: f32,
+    profile: u32,
+    _pad: [u32; 3],
+}
@@ -0,0 +1,387 @@
+//! Per spawn block fluid materials. The viscosity models are evaluated on the GPU
+//! from the local shear rate, see viscosity.h.wgsl. Granular materials add a pressure
+//! dependent yield stress on the same solver, so sand and water share one particle
+//! state and push each other around through the usual SPH forces. Elastic materials
+//! spring back to the shape they were spawned in, see elastic.rs
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ViscosityModel {
+    /// only the solver's artificial viscosity
+    #[default]
+    Inviscid,
+    /// constant dynamic viscosity, Pa·s
+    Newtonian { viscosity: f32 },
+    /// `mu = consistency * shear_rate^(flow_index - 1)`, shear thinning for `flow_index < 1`
+    PowerLaw { consistency: f32, flow_index: f32 },
+    /// `mu = mu_inf + (mu_0 - mu_inf) * (1 + (relaxation_time * shear_rate)²)^((flow_index - 1) / 2)`
+    Carreau {
+        zero_shear_viscosity: f32,
+        infinite_shear_viscosity: f32,
+        relaxation_time: f32,
+        flow_index: f32,
+    },
+    /// `mu = plastic_viscosity + yield_stress / shear_rate`, rigid below the yield stress
+    Bingham {
+        plastic_viscosity: f32,
+        yield_stress: f32,
+    },
+}
+
+/// scales the viscosity model with temperature, so the material can melt and solidify
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThermalViscosity {
+    /// temperature the viscosity model is given at, °C
+    pub reference_temperature: f32,
+    /// `mu(T) = mu * exp(-softening * (T - reference_temperature))`, 1/K
+    pub softening: f32,
+    /// below this the material is solid and takes `max_viscosity`, °C
+    pub solidus_temperature: f32,
+}
+
+/// Drucker-Prager yield on top of the viscosity model,
+/// `mu += (cohesion + pressure * tan(angle_of_repose)) / shear_rate`, so piles stand up to
+/// the angle of repose and avalanche beyond it. the grains also get Coulomb friction with
+/// the same angle against the container walls and don't bounce off them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Granular {
+    /// degrees, the internal friction angle
+    pub angle_of_repose: f32,
+    /// Pa, holds the grains together so slopes can stand steeper than the angle of repose
+    pub cohesion: f32,
+}
+
+/// corotated linear elasticity, each block of it is one solid body that keeps its
+/// spawned shape, see elastic.rs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Elastic {
+    /// Pa
+    pub youngs_modulus: f32,
+    /// from 0 up to but excluding 0.5, where the material would be incompressible and
+    /// Lamé's first parameter infinite
+    pub poisson_ratio: f32,
+}
+
+impl Elastic {
+    /// the Poisson ratio is in `[0, 0.5)`
+    pub fn has_valid_poisson_ratio(&self) -> bool {
+        (0.0..0.5).contains(&self.poisson_ratio)
+    }
+
+    /// Lamé's first parameter and the shear modulus
+    pub fn lame_parameters(&self) -> (f32, f32) {
+        let e = self.youngs_modulus;
+        let nu = self.poisson_ratio;
+        (
+            e * nu / ((1.0 + nu) * (1.0 - 2.0 * nu)),
+            e / (2.0 * (1.0 + nu)),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FluidMaterial {
+    pub viscosity: ViscosityModel,
+    /// upper bound of the apparent viscosity, keeps power-law and Bingham finite at rest
+    pub max_viscosity: f32,
+    /// m²/s, the presets are scaled up so conduction shows within seconds of simulated time
+    pub thermal_diffusivity: f32,
+    pub thermal: Option<ThermalViscosity>,
+    pub granular: Option<Granular>,
+    pub elastic: Option<Elastic>,
+}
+
+impl Default for FluidMaterial {
+    fn default() -> Self {
+        Self::water()
+    }
+}
+
+impl FluidMaterial {
+    pub fn water() -> Self {
+        Self {
+            viscosity: ViscosityModel::Inviscid,
+            max_viscosity: 0.0,
+            thermal_diffusivity: 1e-3,
+            thermal: None,
+            granular: None,
+            elastic: None,
+        }
+    }
+
+    /// mildly shear thinning
+    pub fn honey() -> Self {
+        Self {
+            viscosity: ViscosityModel::Carreau {
+                zero_shear_viscosity: 200.0,
+                infinite_shear_viscosity: 50.0,
+                relaxation_time: 0.5,
+                flow_index: 0.8,
+            },
+            max_viscosity: 200.0,
+            thermal_diffusivity: 5e-4,
+            thermal: None,
+            granular: None,
+            elastic: None,
+        }
+    }
+
+    /// strongly shear thinning
+    pub fn ketchup() -> Self {
+        Self {
+            viscosity: ViscosityModel::PowerLaw {
+                consistency: 100.0,
+                flow_index: 0.3,
+            },
+            max_viscosity: 2000.0,
+            thermal_diffusivity: 5e-4,
+            thermal: None,
+            granular: None,
+            elastic: None,
+        }
+    }
+
+    /// flows only once the yield stress is exceeded
+    pub fn mud() -> Self {
+        Self {
+            viscosity: ViscosityModel::Bingham {
+                plastic_viscosity: 20.0,
+                yield_stress: 200.0,
+            },
+            max_viscosity: 5000.0,
+            thermal_diffusivity: 5e-4,
+            thermal: None,
+            granular: None,
+            elastic: None,
+        }
+    }
+
+    /// runny when spawned around 1150 °C, crusts over below 800 °C
+    pub fn lava() -> Self {
+        Self {
+            viscosity: ViscosityModel::Newtonian { viscosity: 50.0 },
+            max_viscosity: 5000.0,
+            thermal_diffusivity: 2e-3,
+            thermal: Some(ThermalViscosity {
+                reference_temperature: 1150.0,
+                softening: 0.01,
+                solidus_temperature: 800.0,
+            }),
+            granular: None,
+            elastic: None,
+        }
+    }
+
+    /// melts above 50 °C
+    pub fn wax() -> Self {
+        Self {
+            viscosity: ViscosityModel::Newtonian { viscosity: 2.0 },
+            max_viscosity: 5000.0,
+            thermal_diffusivity: 1e-3,
+            thermal: Some(ThermalViscosity {
+                reference_temperature: 70.0,
+                softening: 0.05,
+                solidus_temperature: 50.0,
+            }),
+            granular: None,
+            elastic: None,
+        }
+    }
+
+    /// dry sand
+    pub fn sand() -> Self {
+        Self {
+            granular: Some(Granular {
+                angle_of_repose: 34.0,
+                cohesion: 0.0,
+            }),
+            max_viscosity: 1.0e5,
+            ..Self::water()
+        }
+    }
+
+    /// sticks together, stands in steeper heaps than dry sand
+    pub fn wet_sand() -> Self {
+        Self {
+            granular: Some(Granular {
+                angle_of_repose: 40.0,
+                cohesion: 200.0,
+            }),
+            max_viscosity: 1.0e5,
+            ..Self::water()
+        }
+    }
+
+    /// wobbles back into shape
+    pub fn jelly() -> Self {
+        Self {
+            elastic: Some(Elastic {
+                youngs_modulus: 2.0e4,
+                poisson_ratio: 0.3,
+            }),
+            ..Self::water()
+        }
+    }
+
+    pub fn is_viscous(&self) -> bool {
+        self.viscosity != ViscosityModel::Inviscid
+            || self.thermal.is_some()
+            || self.granular.is_some()
+    }
+
+    pub fn to_raw(&self) -> FluidMaterialRaw {
+        let mut raw = FluidMaterialRaw {
+            max_viscosity: self.max_viscosity,
+            thermal_diffusivity: self.thermal_diffusivity,
+            solidus_temperature: f32::MIN,
+            ..Default::default()
+        };
+        if let Some(thermal) = self.thermal {
+            raw.reference_temperature = thermal.reference_temperature;
+            raw.softening = thermal.softening;
+            raw.solidus_temperature = thermal.solidus_temperature;
+        }
+        if let Some(granular) = self.granular {
+            raw.friction = granular.angle_of_repose.clamp(0.0, 89.0).to_radians().tan();
+            raw.cohesion = granular.cohesion;
+        }
+        if let Some(elastic) = self.elastic {
+            (raw.lame_lambda, raw.shear_modulus) = elastic.lame_parameters();
+        }
+        match self.viscosity {
+            ViscosityModel::Inviscid => {}
+            ViscosityModel::Newtonian { viscosity } => {
+                raw.model = 1;
+                raw.viscosity = viscosity;
+                raw.max_viscosity = raw.max_viscosity.max(viscosity);
+            }
+            ViscosityModel::PowerLaw {
+                consistency,
+                flow_index,
+            } => {
+                raw.model = 2;
+                raw.viscosity = consistency;
+                raw.flow_index = flow_index;
+            }
+            ViscosityModel::Carreau {
+                zero_shear_viscosity,
+                infinite_shear_viscosity,
+                relaxation_time,
+                flow_index,
+            } => {
+                raw.model = 3;
+                raw.viscosity = zero_shear_viscosity;
+                raw.viscosity_inf = infinite_shear_viscosity;
+                raw.relaxation_time = relaxation_time;
+                raw.flow_index = flow_index;
+            }
+            ViscosityModel::Bingham {
+                plastic_viscosity,
+                yield_stress,
+            } => {
+                raw.model = 4;
+                raw.viscosity = plastic_viscosity;
+                raw.yield_stress = yield_stress;
+            }
+        }
+        raw
+    }
+}
+
+/// viscosity.h.wgsl FluidMaterial
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FluidMaterialRaw {
+    model: u32,
+    viscosity: f32,
+    viscosity_inf: f32,
+    relaxation_time: f32,
+    flow_index: f32,
+    yield_stress: f32,
+    max_viscosity: f32,
+    thermal_diffusivity: f32,
+    reference_temperature: f32,
+    softening: f32,
+    solidus_temperature: f32,
+    friction: f32,
+    cohesion: f32,
+    lame_lambda: f32,
+    shear_modulus: f32,
+    _pad: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(viscosity: ViscosityModel) -> FluidMaterialRaw {
+        FluidMaterial {
+            viscosity,
+            max_viscosity: 10.0,
+            ..FluidMaterial::water()
+        }
+        .to_raw()
+    }
+
+    #[test]
+    fn inviscid_leaves_the_model_unset() {
+        let raw = raw(ViscosityModel::Inviscid);
+        assert_eq!(raw.model, 0);
+        assert_eq!(raw.viscosity, 0.0);
+        assert_eq!(raw.max_viscosity, 10.0);
+    }
+
+    #[test]
+    fn newtonian_raises_the_cap_to_its_viscosity() {
+        let raw = raw(ViscosityModel::Newtonian { viscosity: 20.0 });
+        assert_eq!(raw.model, 1);
+        assert_eq!(raw.viscosity, 20.0);
+        assert_eq!(raw.max_viscosity, 20.0);
+    }
+
+    #[test]
+    fn power_law_maps_consistency_and_flow_index() {
+        let raw = raw(ViscosityModel::PowerLaw {
+            consistency: 3.0,
+            flow_index: 0.4,
+        });
+        assert_eq!(raw.model, 2);
+        assert_eq!(raw.viscosity, 3.0);
+        assert_eq!(raw.flow_index, 0.4);
+        assert_eq!(raw.yield_stress, 0.0);
+    }
+
+    #[test]
+    fn carreau_maps_both_viscosities_and_the_relaxation_time() {
+        let raw = raw(ViscosityModel::Carreau {
+            zero_shear_viscosity: 8.0,
+            infinite_shear_viscosity: 2.0,
+            relaxation_time: 0.5,
+            flow_index: 0.7,
+        });
+        assert_eq!(raw.model, 3);
+        assert_eq!(raw.viscosity, 8.0);
+        assert_eq!(raw.viscosity_inf, 2.0);
+        assert_eq!(raw.relaxation_time, 0.5);
+        assert_eq!(raw.flow_index, 0.7);
+    }
+
+    #[test]
+    fn bingham_maps_plastic_viscosity_and_yield_stress() {
+        let raw = raw(ViscosityModel::Bingham {
+            plastic_viscosity: 4.0,
+            yield_stress: 150.0,
+        });
+        assert_eq!(raw.model, 4);
+        assert_eq!(raw.viscosity, 4.0);
+        assert_eq!(raw.yield_stress, 150.0);
+        assert_eq!(raw.flow_index, 0.0);
+    }
+
+    #[test]
+    fn without_thermal_the_material_never_solidifies() {
+        assert_eq!(raw(ViscosityModel::Inviscid).solidus_temperature, f32::MIN);
+        let wax = FluidMaterial::wax().to_raw();
+        assert_eq!(wax.solidus_temperature, 50.0);
+        assert_eq!(wax.reference_temperature, 70.0);
+        assert_eq!(wax.softening, 0.05);
+    }
+}
@@ -0,0 +1,199 @@
+//! External force fields on top of gravity: attractors, vortices, wind and turbulence.
+//! They are uploaded every frame as a fixed size uniform array and evaluated per
+//! particle on the GPU, animation included, see forces.h.wgsl
+use cgmath::{InnerSpace, Vector3, Zero};
+
+/// length of the uniform array, fields past it are ignored
+pub const MAX_FORCE_FIELDS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForceKind {
+    /// pulls towards the centre, a negative strength pushes away
+    Attractor,
+    /// swirls around the axis through the centre, counter clockwise looking down the axis
+    Vortex { axis: Vector3<f32> },
+    /// drags particles towards the wind velocity, strength is the drag rate in 1/s
+    Wind { velocity: Vector3<f32> },
+    /// smooth noise, features are about `scale` metres wide and drift with time
+    Turbulence { scale: f32 },
+}
+
+impl ForceKind {
+    pub const NAMES: [&'static str; 4] = ["Attractor", "Vortex", "Wind", "Turbulence"];
+
+    pub fn index(&self) -> usize {
+        match self {
+            ForceKind::Attractor => 0,
+            ForceKind::Vortex { .. } => 1,
+            ForceKind::Wind { .. } => 2,
+            ForceKind::Turbulence { .. } => 3,
+        }
+    }
+
+    /// the kind at `index` of `NAMES` with default parameters
+    pub fn from_index(index: usize) -> Self {
+        match index {
+            1 => ForceKind::Vortex {
+                axis: Vector3::unit_y(),
+            },
+            2 => ForceKind::Wind {
+                velocity: Vector3::unit_x(),
+            },
+            3 => ForceKind::Turbulence { scale: 0.5 },
+            _ => ForceKind::Attractor,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ForceField {
+    pub kind: ForceKind,
+    /// m/s², or 1/s for wind
+    pub strength: f32,
+    /// centre of the region, and of attractors and vortices
+    pub center: Vector3<f32>,
+    /// radius of the spherical region, infinite covers the whole domain
+    pub radius: f32,
+    /// width of the shell inside the region edge over which the force fades out
+    pub falloff: f32,
+    /// strength scales by `1 + pulse_amplitude * sin(2π pulse_frequency t)`
+    pub pulse_amplitude: f32,
+    /// Hz
+    pub pulse_frequency: f32,
+    /// the centre moves by `sweep * sin(2π sweep_frequency t)`
+    pub sweep: Vector3<f32>,
+    /// Hz
+    pub sweep_frequency: f32,
+}
+
+impl ForceField {
+    pub fn new(kind: ForceKind, center: Vector3<f32>, strength: f32) -> Self {
+        Self {
+            kind,
+            strength,
+            center,
+            radius: f32::INFINITY,
+            falloff: 0.0,
+            pulse_amplitude: 0.0,
+            pulse_frequency: 0.0,
+            sweep: Vector3::zero(),
+            sweep_frequency: 0.0,
+        }
+    }
+
+    pub fn attractor(center: Vector3<f32>, strength: f32) -> Self {
+        Self::new(ForceKind::Attractor, center, strength)
+    }
+
+    pub fn vortex(center: Vector3<f32>, axis: Vector3<f32>, strength: f32) -> Self {
+        Self::new(ForceKind::Vortex { axis }, center, strength)
+    }
+
+    pub fn wind(velocity: Vector3<f32>, drag: f32) -> Self {
+        Self::new(ForceKind::Wind { velocity }, Vector3::zero(), drag)
+    }
+
+    pub fn turbulence(scale: f32, strength: f32) -> Self {
+        Self::new(ForceKind::Turbulence { scale }, Vector3::zero(), strength)
+    }
+
+    /// limit the field to a sphere around `center`
+    pub fn with_region(mut self, center: Vector3<f32>, radius: f32, falloff: f32) -> Self {
+        self.center = center;
+        self.radius = radius;
+        self.falloff = falloff;
+        self
+    }
+
+    pub fn with_pulse(mut self, amplitude: f32, frequency: f32) -> Self {
+        self.pulse_amplitude = amplitude;
+        self.pulse_frequency = frequency;
+        self
+    }
+
+    pub fn with_sweep(mut self, sweep: Vector3<f32>, frequency: f32) -> Self {
+        self.sweep = sweep;
+        self.sweep_frequency = frequency;
+        self
+    }
+
+    /// a vortex needs an axis with a direction, `to_raw` normalises it
+    pub fn has_valid_axis(&self) -> bool {
+        match self.kind {
+            ForceKind::Vortex { axis } => {
+                let length2 = axis.magnitude2();
+                length2 > 0.0 && length2.is_finite()
+            }
+            _ => true,
+        }
+    }
+
+    pub fn to_raw(&self) -> ForceFieldRaw {
+        let (kind, direction, scale) = match self.kind {
+            ForceKind::Attractor => (0, Vector3::zero(), 0.0),
+            ForceKind::Vortex { axis } => (1, axis.normalize(), 0.0),
+            ForceKind::Wind { velocity } => (2, velocity, 0.0),
+            ForceKind::Turbulence { scale } => (3, Vector3::zero(), scale.max(1e-3)),
+        };
+        ForceFieldRaw {
+            center: self.center.into(),
+            kind,
+            direction: direction.into(),
+            strength: self.strength,
+            sweep: self.sweep.into(),
+            sweep_frequency: self.sweep_frequency,
+            // negative for an unbounded region
+            radius: if self.radius.is_finite() {
+                self.radius
+            } else {
+                -1.0
+            },
+            falloff: self.falloff,
+            pulse_amplitude: self.pulse_amplitude,
+            pulse_frequency: self.pulse_frequency,
+            scale,
+            _pad: [0.0; 3],
+        }
+    }
+}
+
+/// forces.h.wgsl ForceField
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ForceFieldRaw {
+    center: [f32; 3],
+    kind: u32,
+    direction: [f32; 3],
+    strength: f32,
+    sweep: [f32; 3],
+    sweep_frequency: f32,
+    radius: f32,
+    falloff: f32,
+    pulse_amplitude: f32,
+    pulse_frequency: f32,
+    scale: f32,
+    _pad: [f32; 3],
+}
+
+/// forces.h.wgsl ForceFields
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ForceFieldsRaw {
+    gravity: [f32; 3],
+    count: u32,
+    fields: [ForceFieldRaw; MAX_FORCE_FIELDS],
+}
+
+impl ForceFieldsRaw {
+    pub fn new(gravity: Vector3<f32>, fields: &[ForceField]) -> Self {
+        let mut raw = Self {
+            gravity: gravity.into(),
+            count: fields.len().min(MAX_FORCE_FIELDS) as u32,
+            fields: [ForceFieldRaw::default(); MAX_FORCE_FIELDS],
+        };
+        for (raw, field) in raw.fields.iter_mut().zip(fields) {
+            *raw = field.to_raw();
+        }
+        raw
+    }
+}
@@ -1,5 +1,18 @@
 use std::ops::Range;
 
+/// threads per workgroup of the particle compute shaders (`workgroup_size_x`)
+pub const PARTICLE_WORKGROUP_SIZE_X: u32 = 256;
+
+/// smallest 2D grid of workgroups covering `num_threads`, filled row by row, so thread
+/// `gid.x + gid.y * x * PARTICLE_WORKGROUP_SIZE_X` handles item `id`. `None` when even
+/// the largest grid the device allows is too small
+pub fn workgroup_grid(num_threads: u32, max_per_dimension: u32) -> Option<(u32, u32, u32)> {
+    let workgroups = num_threads.div_ceil(PARTICLE_WORKGROUP_SIZE_X).max(1);
+    let x = workgroups.min(max_per_dimension);
+    let y = workgroups.div_ceil(x);
+    (y <= max_per_dimension).then_some((x, y, 1))
+}
+
 /// draw pass
 pub trait DrawParticle<'a> {
     fn draw_particle_instanced(
@@ -34,6 +47,7 @@ pub trait ComputeParticle<'a> {
         particle_bind_group_0: &'a wgpu::BindGroup,
         particle_bind_group_1: &'a wgpu::BindGroup,
         uniforms_bind_group: &'a wgpu::BindGroup,
+        uniforms_offset: wgpu::DynamicOffset,
         world_bind_group: &'a wgpu::BindGroup,
     );
 }
@@ -48,13 +62,44 @@ where
         particle_bind_group_0: &'a wgpu::BindGroup,
         particle_bind_group_1: &'a wgpu::BindGroup,
         uniforms_bind_group: &'a wgpu::BindGroup,
+        uniforms_offset: wgpu::DynamicOffset,
         world_bind_group: &'a wgpu::BindGroup,
     ) {
         self.set_bind_group(0, particle_bind_group_0, &[]);
         self.set_bind_group(1, particle_bind_group_1, &[]);
-        self.set_bind_group(2, uniforms_bind_group, &[]);
+        self.set_bind_group(2, uniforms_bind_group, &[uniforms_offset]);
         self.set_bind_group(3, world_bind_group, &[]);
         self.insert_debug_marker("compute particle");
         self.dispatch_workgroups(workgroup_size.0, workgroup_size.1, workgroup_size.2);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: u32 = 16;
+
+    #[test]
+    fn no_threads_still_dispatch_one_workgroup() {
+        assert_eq!(workgroup_grid(0, MAX), Some((1, 1, 1)));
+    }
+
+    #[test]
+    fn one_thread_past_a_workgroup_takes_another() {
+        assert_eq!(workgroup_grid(PARTICLE_WORKGROUP_SIZE_X, MAX), Some((1, 1, 1)));
+        assert_eq!(workgroup_grid(PARTICLE_WORKGROUP_SIZE_X + 1, MAX), Some((2, 1, 1)));
+    }
+
+    #[test]
+    fn a_full_row_spills_into_y() {
+        let threads = (MAX + 1) * PARTICLE_WORKGROUP_SIZE_X;
+        assert_eq!(workgroup_grid(threads, MAX), Some((MAX, 2, 1)));
+    }
+
+    #[test]
+    fn more_than_the_largest_grid_is_none() {
+        let threads = MAX * MAX * PARTICLE_WORKGROUP_SIZE_X;
+        assert_eq!(workgroup_grid(threads, MAX), Some((MAX, MAX, 1)));
+        assert_eq!(workgroup_grid(threads + 1, MAX), None);
+    }
+}
@@ -1,8 +1,77 @@
-// TODO: Use grid to accelerate neighbor search later
+//! The simulation domain as a grid of support radius wide cells, also the grid the
+//! cached neighbour lists are built on, see `NeighborSearch`. the hashed variant
+//! covers unbounded space with a fixed number of buckets instead. the particle buffers
+//! are sorted by its cells, see `ParticleOrder`
 use cgmath::Vector3;
 
-const MAX_NUM_PARTICLES_PER_CELL: u32 = 500;
-const MAX_NUM_NEIGHBORS: u32 = 500;
+use super::scene::ParticleOrder;
+
+// the unused placeholders these replace were 500 and 500. at 500 the lists alone take
+// 2 kB per particle, which runs past the storage buffer binding size of most devices
+// from about 60k particles on, see the check in `ParticleState::new`
+/// capacity of a grid cell or hash bucket in the cached neighbour search. a cell holds
+/// about 8 particles at rest spacing, particles past the capacity are counted in
+/// `NeighborStats::cell_overflow` and miss from their neighbours' lists
+pub const MAX_NUM_PARTICLES_PER_CELL: u32 = 64;
+/// capacity of a particle's cached neighbour list. the support sphere holds about 33
+/// particles at rest spacing, lists past the capacity are counted in
+/// `NeighborStats::neighbor_overflow` and keep their lowest indices
+pub const MAX_NUM_NEIGHBORS: u32 = 128;
+/// u32s per particle in the neighbour lists buffer, the count and then the indices
+pub const NEIGHBOR_LIST_STRIDE: u32 = MAX_NUM_NEIGHBORS + 1;
+
+/// grid.h.wgsl, the shaders' copy of the capacities above. it has no file of its own,
+/// `resources::load_shader_with_includes` generates it for every include
+pub fn shader_header() -> String {
+    format!(
+        "// file: grid.h, generated from grid.rs\n\
+         const MAX_NUM_PARTICLES_PER_CELL: u32 = {MAX_NUM_PARTICLES_PER_CELL}u;\n\
+         const MAX_NUM_NEIGHBORS: u32 = {MAX_NUM_NEIGHBORS}u;\n\
+         const NEIGHBOR_LIST_STRIDE: u32 = {NEIGHBOR_LIST_STRIDE}u;\n"
+    )
+}
+
+/// bits per axis of a Morton cell id, cell coordinates past them are clamped
+const MORTON_BITS: u32 = 10;
+
+/// neighbors_list.h.wgsl NeighborStats, counted since the last readback and summed over
+/// the substeps
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct NeighborStats {
+    /// particles left out of a full grid cell or hash bucket
+    pub cell_overflow: u32,
+    /// particles whose neighbour list was truncated
+    pub neighbor_overflow: u32,
+    /// particles looked at while building the lists
+    pub candidates: u32,
+    /// candidates skipped because their cell shares the bucket with the searched one,
+    /// only with `NeighborSearch::HashedList`
+    pub hash_collisions: u32,
+}
+
+impl NeighborStats {
+    /// some particles miss neighbours
+    pub fn overflowed(&self) -> bool {
+        self.cell_overflow > 0 || self.neighbor_overflow > 0
+    }
+
+    /// share of the candidates wasted on hash collisions
+    pub fn collision_rate(&self) -> f32 {
+        self.hash_collisions as f32 / self.candidates.max(1) as f32
+    }
+}
+
+impl std::ops::AddAssign for NeighborStats {
+    fn add_assign(&mut self, other: Self) {
+        self.cell_overflow = self.cell_overflow.saturating_add(other.cell_overflow);
+        self.neighbor_overflow = self
+            .neighbor_overflow
+            .saturating_add(other.neighbor_overflow);
+        self.candidates = self.candidates.saturating_add(other.candidates);
+        self.hash_collisions = self.hash_collisions.saturating_add(other.hash_collisions);
+    }
+}
 
 pub struct Grid {
     pub cell_nums: Vector3<u32>,
@@ -35,4 +104,29 @@ impl Grid {
             boundary_lower,
         }
     }
+
+    /// id of the cell holding `position` in `order`, positions outside the domain count
+    /// to its border cells
+    pub fn cell_id(&self, position: Vector3<f32>, order: ParticleOrder) -> u32 {
+        let cell = |axis: usize| {
+            let c = (position[axis] - self.boundary_lower[axis]) / self.cell_size[axis];
+            // NaN and negative positions end up in cell 0
+            (c as u32).min(self.cell_nums[axis].max(1) - 1)
+        };
+        let (x, y, z) = (cell(0), cell(1), cell(2));
+        match order {
+            ParticleOrder::Linear => x + self.cell_nums.x * (y + self.cell_nums.y * z),
+            ParticleOrder::Morton => spread_bits(x) | (spread_bits(y) << 1) | (spread_bits(z) << 2),
+        }
+    }
+}
+
+/// the low `MORTON_BITS` of `v`, two zero bits after each
+fn spread_bits(v: u32) -> u32 {
+    let mut v = v.min((1 << MORTON_BITS) - 1);
+    v = (v | (v << 16)) & 0x0300_00ff;
+    v = (v | (v << 8)) & 0x0300_f00f;
+    v = (v | (v << 4)) & 0x030c_30c3;
+    v = (v | (v << 2)) & 0x0924_9249;
+    v
 }
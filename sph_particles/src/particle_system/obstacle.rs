@@ -0,0 +1,310 @@
+//! Analytic obstacles: boxes, spheres, capsules and cylinders the fluid flows around.
+//! Each is a signed distance field in its own frame, posed by a transform that can follow
+//! keyframes and shake like the container. They are uploaded every step as a storage array
+//! and collided with in `solve_boundary_constraints`, see obstacle.h.wgsl
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Matrix3, Quaternion, Rad, Rotation, Rotation3, Vector3, Zero};
+
+use super::container::{
+    angular_velocity, keyframe_pose, ContainerKeyframe, Shake, VELOCITY_EPSILON,
+};
+
+/// segments of the circles of `Obstacle::wireframe`
+const WIREFRAME_SEGMENTS: usize = 24;
+
+/// in the obstacle's frame, centred on its position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObstacleShape {
+    Box {
+        half_extents: Vector3<f32>,
+    },
+    Sphere {
+        radius: f32,
+    },
+    /// along the local y axis, `half_length` from the centre to each cap's centre
+    Capsule {
+        radius: f32,
+        half_length: f32,
+    },
+    /// along the local y axis
+    Cylinder {
+        radius: f32,
+        half_height: f32,
+    },
+}
+
+/// scripted motion on top of the obstacle's pose, the keyframes offset and rotate it about
+/// its centre
+#[derive(Debug, Clone, Default)]
+pub struct ObstacleMotion {
+    /// interpolated linearly and with slerp, the first and last pose are held outside
+    pub keyframes: Vec<ContainerKeyframe>,
+    pub shake: Option<Shake>,
+}
+
+impl ObstacleMotion {
+    pub fn with_keyframes(mut self, keyframes: Vec<ContainerKeyframe>) -> Self {
+        self.keyframes = keyframes;
+        self
+    }
+
+    pub fn with_shake(mut self, shake: Shake) -> Self {
+        self.shake = Some(shake);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Obstacle {
+    pub shape: ObstacleShape,
+    /// of the centre at rest
+    pub position: Vector3<f32>,
+    /// obstacle to world at rest
+    pub rotation: Quaternion<f32>,
+    /// Coulomb friction against the fluid, the material's own friction applies if higher
+    pub friction: f32,
+    /// fraction of the normal velocity the fluid bounces off with
+    pub restitution: f32,
+    pub motion: Option<ObstacleMotion>,
+}
+
+impl Obstacle {
+    pub fn new(shape: ObstacleShape, position: Vector3<f32>) -> Self {
+        Self {
+            shape,
+            position,
+            rotation: Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            friction: 0.0,
+            restitution: 0.0,
+            motion: None,
+        }
+    }
+
+    pub fn cuboid(position: Vector3<f32>, half_extents: Vector3<f32>) -> Self {
+        Self::new(ObstacleShape::Box { half_extents }, position)
+    }
+
+    pub fn sphere(position: Vector3<f32>, radius: f32) -> Self {
+        Self::new(ObstacleShape::Sphere { radius }, position)
+    }
+
+    pub fn capsule(position: Vector3<f32>, radius: f32, half_length: f32) -> Self {
+        Self::new(
+            ObstacleShape::Capsule {
+                radius,
+                half_length,
+            },
+            position,
+        )
+    }
+
+    pub fn cylinder(position: Vector3<f32>, radius: f32, half_height: f32) -> Self {
+        Self::new(
+            ObstacleShape::Cylinder {
+                radius,
+                half_height,
+            },
+            position,
+        )
+    }
+
+    pub fn with_rotation(mut self, axis: Vector3<f32>, angle: Rad<f32>) -> Self {
+        self.rotation = Quaternion::from_axis_angle(axis.normalize(), angle);
+        self
+    }
+
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.friction = friction;
+        self
+    }
+
+    pub fn with_restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn with_motion(mut self, motion: ObstacleMotion) -> Self {
+        self.motion = Some(motion);
+        self
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.motion
+            .as_ref()
+            .is_none_or(|m| m.keyframes.is_empty() && m.shake.is_none())
+    }
+
+    /// centre and obstacle to world rotation at `time`
+    pub fn pose(&self, time: f32) -> (Vector3<f32>, Quaternion<f32>) {
+        let Some(motion) = &self.motion else {
+            return (self.position, self.rotation);
+        };
+        let (mut translation, rotation) = keyframe_pose(&motion.keyframes, time);
+        if let Some(shake) = motion.shake {
+            translation += shake.amplitude * (2.0 * PI * shake.frequency * time).sin();
+        }
+        (self.position + translation, rotation * self.rotation)
+    }
+
+    pub fn to_raw(&self, time: f32) -> ObstacleRaw {
+        let (position, rotation) = self.pose(time);
+        let (velocity, angular_velocity) = if self.is_static() {
+            (Vector3::zero(), Vector3::zero())
+        } else {
+            let before = self.pose(time - VELOCITY_EPSILON);
+            let after = self.pose(time + VELOCITY_EPSILON);
+            (
+                (after.0 - before.0) / (2.0 * VELOCITY_EPSILON),
+                angular_velocity(before.1, after.1),
+            )
+        };
+
+        let (shape, size) = match self.shape {
+            ObstacleShape::Box { half_extents } => (1, half_extents),
+            ObstacleShape::Sphere { radius } => (2, Vector3::new(radius, 0.0, 0.0)),
+            ObstacleShape::Capsule {
+                radius,
+                half_length,
+            } => (3, Vector3::new(radius, half_length, 0.0)),
+            ObstacleShape::Cylinder {
+                radius,
+                half_height,
+            } => (4, Vector3::new(radius, half_height, 0.0)),
+        };
+
+        let rotation = Matrix3::from(rotation);
+        let vec4 = |v: Vector3<f32>| [v.x, v.y, v.z, 0.0];
+        ObstacleRaw {
+            rotation: [vec4(rotation.x), vec4(rotation.y), vec4(rotation.z)],
+            position: position.into(),
+            shape,
+            size: size.into(),
+            friction: self.friction,
+            velocity: velocity.into(),
+            restitution: self.restitution,
+            angular_velocity: angular_velocity.into(),
+            _pad: 0.0,
+        }
+    }
+
+    /// the outline at `time` as a line list in world space
+    pub fn wireframe(&self, time: f32) -> Vec<Vector3<f32>> {
+        let mut lines = vec![];
+        match self.shape {
+            ObstacleShape::Box { half_extents: h } => {
+                let corner = |i: usize| {
+                    Vector3::new(
+                        if i & 1 == 0 { -h.x } else { h.x },
+                        if i & 2 == 0 { -h.y } else { h.y },
+                        if i & 4 == 0 { -h.z } else { h.z },
+                    )
+                };
+                // corners one bit apart share an edge
+                for i in 0..8 {
+                    for bit in [1, 2, 4] {
+                        if i & bit == 0 {
+                            lines.extend([corner(i), corner(i | bit)]);
+                        }
+                    }
+                }
+            }
+            ObstacleShape::Sphere { radius } => {
+                for axes in [(0, 1), (1, 2), (2, 0)] {
+                    circle(&mut lines, Vector3::zero(), radius, axes, 0.0, 2.0 * PI);
+                }
+            }
+            ObstacleShape::Capsule {
+                radius,
+                half_length,
+            } => {
+                for y in [-half_length, half_length] {
+                    circle(
+                        &mut lines,
+                        Vector3::unit_y() * y,
+                        radius,
+                        (2, 0),
+                        0.0,
+                        2.0 * PI,
+                    );
+                }
+                // the caps' outlines in the two planes through the axis
+                for axes in [(0, 1), (2, 1)] {
+                    let top = Vector3::unit_y() * half_length;
+                    circle(&mut lines, top, radius, axes, 0.0, PI);
+                    circle(&mut lines, -top, radius, axes, PI, 2.0 * PI);
+                }
+                sides(&mut lines, radius, half_length);
+            }
+            ObstacleShape::Cylinder {
+                radius,
+                half_height,
+            } => {
+                for y in [-half_height, half_height] {
+                    circle(
+                        &mut lines,
+                        Vector3::unit_y() * y,
+                        radius,
+                        (2, 0),
+                        0.0,
+                        2.0 * PI,
+                    );
+                }
+                sides(&mut lines, radius, half_height);
+            }
+        }
+
+        let (position, rotation) = self.pose(time);
+        lines
+            .into_iter()
+            .map(|p| position + rotation.rotate_vector(p))
+            .collect()
+    }
+}
+
+/// the arc of radius `radius` around `center` from angle `start` to `end`, in the plane of
+/// the local axes `axes`
+fn circle(
+    lines: &mut Vec<Vector3<f32>>,
+    center: Vector3<f32>,
+    radius: f32,
+    axes: (usize, usize),
+    start: f32,
+    end: f32,
+) {
+    let point = |i: usize| {
+        let angle = start + (end - start) * i as f32 / WIREFRAME_SEGMENTS as f32;
+        let mut p = center;
+        p[axes.0] += radius * angle.cos();
+        p[axes.1] += radius * angle.sin();
+        p
+    };
+    for i in 0..WIREFRAME_SEGMENTS {
+        lines.extend([point(i), point(i + 1)]);
+    }
+}
+
+/// four lines along the y axis joining the rims `half_height` above and below the centre
+fn sides(lines: &mut Vec<Vector3<f32>>, radius: f32, half_height: f32) {
+    for (x, z) in [(1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0)] {
+        lines.extend([
+            Vector3::new(radius * x, -half_height, radius * z),
+            Vector3::new(radius * x, half_height, radius * z),
+        ]);
+    }
+}
+
+/// obstacle.h.wgsl Obstacle, a zero `shape` is an empty slot
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ObstacleRaw {
+    rotation: [[f32; 4]; 3],
+    position: [f32; 3],
+    shape: u32,
+    size: [f32; 3],
+    friction: f32,
+    velocity: [f32; 3],
+    restitution: f32,
+    angular_velocity: [f32; 3],
+    _pad: f32,
+}
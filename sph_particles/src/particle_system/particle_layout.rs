@@ -0,0 +1,1125 @@
+//! The particle storage of each `ParticleLayout` on the GPU. the interleaved layout keeps
+//! a `ParticleRaw` record per particle and every solver pass ping-pongs the whole records.
+//! the split layouts keep a buffer per field, every solver pass binds only the buffers of
+//! the fields it reads or writes (`PassFields`) and its storage header is generated here.
+//! a written buffer ping-pongs when the pass also reads it from the neighbours, otherwise
+//! it is written in place. between steps the particles are on the first side of every
+//! buffer, which is what rendering, diagnostics and readback bind.
+//! the buffers hold a fixed capacity of particles, the live ones come first and their count
+//! is a uniform next to them (`num_particles` in the shaders), so the inlets and outlets
+//! change it without reallocating anything
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::BitOr;
+
+use bytemuck::Zeroable;
+use cgmath::Vector3;
+use wgpu::util::DeviceExt;
+
+use super::particles::{Particle, ParticleRaw};
+use super::scene::ParticleLayout;
+
+/// a set of the particle fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParticleFields(u32);
+
+impl ParticleFields {
+    pub const NONE: Self = Self(0);
+    pub const POSITION: Self = Self(1 << 0);
+    pub const VELOCITY: Self = Self(1 << 1);
+    pub const DENSITY: Self = Self(1 << 2);
+    pub const PRESSURE: Self = Self(1 << 3);
+    /// ptype, cell_id and material, they are only ever read
+    pub const KIND: Self = Self(1 << 4);
+    pub const TEMPERATURE: Self = Self(1 << 5);
+    pub const SCALARS: Self = Self(1 << 6);
+    pub const ALL: Self = Self((1 << 7) - 1);
+
+    pub fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for ParticleFields {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+/// the fields a solver pass touches: of its own particle, of the neighbours it sums over
+/// and the ones it stores. a field the shader uses must be in here, the split layouts
+/// don't bind the others and they read as zero
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassFields {
+    pub own: ParticleFields,
+    pub neighbors: ParticleFields,
+    pub written: ParticleFields,
+}
+
+impl PassFields {
+    /// every field of the own particle, nothing written. what the passes outside the
+    /// solver see
+    const VIEW: Self = Self {
+        own: ParticleFields::ALL,
+        neighbors: ParticleFields::NONE,
+        written: ParticleFields::NONE,
+    };
+}
+
+/// the entry points of the solver shaders that run on the particles, see `solver_pass_fields`
+pub const SOLVER_PASSES: [&str; 17] = [
+    "neighbor_grid_main",
+    "neighbor_list_main",
+    "integrator_begin_main",
+    "compute_density_main",
+    "compute_non_pressure_main",
+    "heat_conduction_main",
+    "scalar_transport_main",
+    "viscosity_prepare_main",
+    "viscosity_jacobi_main",
+    "compute_curl_main",
+    "vorticity_confinement_main",
+    "compute_pressure_main",
+    "elastic_stress_main",
+    "elastic_force_main",
+    "xsph_main",
+    "predict_main",
+    "advect_main",
+];
+
+/// the fields the solver pass `entry_point` touches, read off its shader. a field missing
+/// here reads as zero in the split layouts, tests/particle_layout.rs compares them with the
+/// interleaved one
+pub fn solver_pass_fields(entry_point: &str) -> PassFields {
+    const P: ParticleFields = ParticleFields::POSITION;
+    const V: ParticleFields = ParticleFields::VELOCITY;
+    const D: ParticleFields = ParticleFields::DENSITY;
+    const PR: ParticleFields = ParticleFields::PRESSURE;
+    const K: ParticleFields = ParticleFields::KIND;
+    const T: ParticleFields = ParticleFields::TEMPERATURE;
+    const S: ParticleFields = ParticleFields::SCALARS;
+    const NONE: ParticleFields = ParticleFields::NONE;
+    let (own, neighbors, written) = match entry_point {
+        "neighbor_grid_main" => (P, NONE, NONE),
+        "neighbor_list_main" => (P, P, NONE),
+        "integrator_begin_main" => (P | V, NONE, NONE),
+        "compute_density_main" => (P, P, D),
+        "compute_non_pressure_main" => (P | V | D | K, P | V, V | D | PR),
+        "heat_conduction_main" => (P | K | T, P | D | K | T, T),
+        "scalar_transport_main" => (P | K | S, P | D | K | S, S),
+        "viscosity_prepare_main" => (P | V | D | PR | K | T, P | V | D | PR, NONE),
+        "viscosity_jacobi_main" => (P | V | K, P | V | D, V),
+        "compute_curl_main" => (P | V | K, P | V | D, NONE),
+        "vorticity_confinement_main" => (P | V | K, P | D, V),
+        "compute_pressure_main" => (P | V | D | PR | K, P | D | PR, V),
+        "elastic_stress_main" => (P | K, P, NONE),
+        "elastic_force_main" => (V | K, NONE, V),
+        "xsph_main" => (P | V | K, P | V | D, V),
+        "predict_main" | "advect_main" => (P | V | K, NONE, P | V),
+        // only in the interleaved layout, evens out its ping-pong
+        "empty_copy_main" => (ParticleFields::ALL, NONE, ParticleFields::ALL),
+        _ => panic!("{entry_point} is not a solver pass"),
+    };
+    PassFields {
+        own,
+        neighbors,
+        written,
+    }
+}
+
+/// a buffer of the split layouts, named after its WGSL array
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SplitBuffer {
+    Positions,
+    Velocities,
+    Densities,
+    Pressures,
+    Kinds,
+    Temperatures,
+    Scalars,
+    /// velocity and pressure in four halves (vx vy | vz p). halves keep about three
+    /// significant digits and saturate at 65504
+    HalfVelocities,
+}
+
+const SPLIT_BUFFERS: [SplitBuffer; 7] = [
+    SplitBuffer::Positions,
+    SplitBuffer::Velocities,
+    SplitBuffer::Densities,
+    SplitBuffer::Pressures,
+    SplitBuffer::Kinds,
+    SplitBuffer::Temperatures,
+    SplitBuffer::Scalars,
+];
+
+/// binding 3 stays empty, the pressure moved in with the velocity
+const SPLIT_HALF_BUFFERS: [SplitBuffer; 6] = [
+    SplitBuffer::Positions,
+    SplitBuffer::HalfVelocities,
+    SplitBuffer::Densities,
+    SplitBuffer::Kinds,
+    SplitBuffer::Temperatures,
+    SplitBuffer::Scalars,
+];
+
+/// f16 saturates at it
+const HALF_MAX: f32 = 65504.0;
+
+/// the particle count in every particle bind group, after the split buffers
+pub const COUNT_BINDING: u32 = 7;
+
+/// bytes of the count buffer: the live particles, then what compute_flow.wgsl keeps
+/// between its passes
+const COUNT_SIZE: wgpu::BufferAddress = 16;
+
+/// the bind group layout entry of the particle count, see `COUNT_BINDING`
+pub fn count_layout_entry(visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding: COUNT_BINDING,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+impl SplitBuffer {
+    fn binding(self) -> u32 {
+        match self {
+            SplitBuffer::Positions => 0,
+            SplitBuffer::Velocities | SplitBuffer::HalfVelocities => 1,
+            SplitBuffer::Densities => 2,
+            SplitBuffer::Pressures => 3,
+            SplitBuffer::Kinds => 4,
+            SplitBuffer::Temperatures => 5,
+            SplitBuffer::Scalars => 6,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SplitBuffer::Positions => "positions",
+            SplitBuffer::Velocities | SplitBuffer::HalfVelocities => "velocities",
+            SplitBuffer::Densities => "densities",
+            SplitBuffer::Pressures => "pressures",
+            SplitBuffer::Kinds => "kinds",
+            SplitBuffer::Temperatures => "temperatures",
+            SplitBuffer::Scalars => "scalars",
+        }
+    }
+
+    /// WGSL type of an element
+    fn element(self) -> &'static str {
+        match self {
+            SplitBuffer::Positions | SplitBuffer::Velocities => "vec3<f32>",
+            SplitBuffer::Densities | SplitBuffer::Pressures | SplitBuffer::Temperatures => "f32",
+            SplitBuffer::Kinds => "ParticleKind",
+            SplitBuffer::Scalars => "vec4<f32>",
+            SplitBuffer::HalfVelocities => "vec2<u32>",
+        }
+    }
+
+    /// bytes per particle
+    fn stride(self) -> usize {
+        match self {
+            SplitBuffer::Positions | SplitBuffer::Velocities | SplitBuffer::Scalars => 16,
+            SplitBuffer::Densities | SplitBuffer::Pressures | SplitBuffer::Temperatures => 4,
+            SplitBuffer::Kinds => 12,
+            SplitBuffer::HalfVelocities => 8,
+        }
+    }
+
+    fn fields(self) -> ParticleFields {
+        match self {
+            SplitBuffer::Positions => ParticleFields::POSITION,
+            SplitBuffer::Velocities => ParticleFields::VELOCITY,
+            SplitBuffer::Densities => ParticleFields::DENSITY,
+            SplitBuffer::Pressures => ParticleFields::PRESSURE,
+            SplitBuffer::Kinds => ParticleFields::KIND,
+            SplitBuffer::Temperatures => ParticleFields::TEMPERATURE,
+            SplitBuffer::Scalars => ParticleFields::SCALARS,
+            SplitBuffer::HalfVelocities => ParticleFields::VELOCITY | ParticleFields::PRESSURE,
+        }
+    }
+
+    /// WGSL statements reading the fields of particle `id` from `array` into `p`
+    fn load(self, array: &str) -> String {
+        match self {
+            SplitBuffer::Positions => format!("p.position = {array}[id];"),
+            SplitBuffer::Velocities => format!("p.velocity = {array}[id];"),
+            SplitBuffer::Densities => format!("p.density = {array}[id];"),
+            SplitBuffer::Pressures => format!("p.pressure = {array}[id];"),
+            SplitBuffer::Kinds => format!(
+                "let kind = {array}[id];\n    \
+                 p.ptype = kind.ptype;\n    \
+                 p.cell_id = kind.cell_id;\n    \
+                 p.material = kind.material;"
+            ),
+            SplitBuffer::Temperatures => format!("p.temperature = {array}[id];"),
+            SplitBuffer::Scalars => format!("p.scalars = {array}[id];"),
+            SplitBuffer::HalfVelocities => format!(
+                "let velocity_pressure = {array}[id];\n    \
+                 let vz_p = unpack2x16float(velocity_pressure.y);\n    \
+                 p.velocity = vec3<f32>(unpack2x16float(velocity_pressure.x), vz_p.x);\n    \
+                 p.pressure = vz_p.y;"
+            ),
+        }
+    }
+
+    /// WGSL statements writing the fields of `p` to particle `id` of `array`
+    fn store(self, array: &str) -> String {
+        match self {
+            SplitBuffer::Positions => format!("{array}[id] = p.position;"),
+            SplitBuffer::Velocities => format!("{array}[id] = p.velocity;"),
+            SplitBuffer::Densities => format!("{array}[id] = p.density;"),
+            SplitBuffer::Pressures => format!("{array}[id] = p.pressure;"),
+            SplitBuffer::Kinds => {
+                format!("{array}[id] = ParticleKind(p.ptype, p.cell_id, p.material);")
+            }
+            SplitBuffer::Temperatures => format!("{array}[id] = p.temperature;"),
+            SplitBuffer::Scalars => format!("{array}[id] = p.scalars;"),
+            SplitBuffer::HalfVelocities => format!(
+                "let velocity = clamp(p.velocity, vec3<f32>(-HALF_MAX), vec3<f32>(HALF_MAX));\n    \
+                 let pressure = clamp(p.pressure, -HALF_MAX, HALF_MAX);\n    \
+                 {array}[id] = vec2<u32>(\n        \
+                 pack2x16float(velocity.xy),\n        \
+                 pack2x16float(vec2<f32>(velocity.z, pressure)),\n    \
+                 );"
+            ),
+        }
+    }
+
+    /// append the element of `particle` in the GPU's byte order
+    fn pack(self, particle: &Particle, bytes: &mut Vec<u8>) {
+        let vec3 = |v: Vector3<f32>| [v.x, v.y, v.z, 0.0];
+        match self {
+            SplitBuffer::Positions => {
+                bytes.extend_from_slice(bytemuck::bytes_of(&vec3(particle.position)))
+            }
+            SplitBuffer::Velocities => {
+                bytes.extend_from_slice(bytemuck::bytes_of(&vec3(particle.velocity)))
+            }
+            SplitBuffer::Densities => bytes.extend_from_slice(&particle.density.to_le_bytes()),
+            SplitBuffer::Pressures => bytes.extend_from_slice(&particle.pressure.to_le_bytes()),
+            SplitBuffer::Kinds => bytes.extend_from_slice(bytemuck::bytes_of(&[
+                particle.ptype,
+                particle.cell_id,
+                particle.material,
+            ])),
+            SplitBuffer::Temperatures => {
+                bytes.extend_from_slice(&particle.temperature.to_le_bytes())
+            }
+            SplitBuffer::Scalars => bytes.extend_from_slice(bytemuck::bytes_of(&particle.scalars)),
+            SplitBuffer::HalfVelocities => {
+                let [vx, vy, vz, p] = [
+                    particle.velocity.x,
+                    particle.velocity.y,
+                    particle.velocity.z,
+                    particle.pressure,
+                ]
+                .map(|value| f32_to_f16(value.clamp(-HALF_MAX, HALF_MAX)) as u32);
+                bytes.extend_from_slice(bytemuck::bytes_of(&[vx | vy << 16, vz | p << 16]));
+            }
+        }
+    }
+
+    /// read the element `bytes` into `particle`
+    fn unpack(self, bytes: &[u8], particle: &mut Particle) {
+        let f32s = |bytes: &[u8]| -> Vec<f32> { bytemuck::pod_collect_to_vec(bytes) };
+        match self {
+            SplitBuffer::Positions => {
+                let v = f32s(bytes);
+                particle.position = Vector3::new(v[0], v[1], v[2]);
+            }
+            SplitBuffer::Velocities => {
+                let v = f32s(bytes);
+                particle.velocity = Vector3::new(v[0], v[1], v[2]);
+            }
+            SplitBuffer::Densities => particle.density = f32s(bytes)[0],
+            SplitBuffer::Pressures => particle.pressure = f32s(bytes)[0],
+            SplitBuffer::Kinds => {
+                let kind: Vec<u32> = bytemuck::pod_collect_to_vec(bytes);
+                particle.ptype = kind[0];
+                particle.cell_id = kind[1];
+                particle.material = kind[2];
+            }
+            SplitBuffer::Temperatures => particle.temperature = f32s(bytes)[0],
+            SplitBuffer::Scalars => particle.scalars.copy_from_slice(&f32s(bytes)),
+            SplitBuffer::HalfVelocities => {
+                let halves: Vec<u32> = bytemuck::pod_collect_to_vec(bytes);
+                let [vx, vy, vz, p] = [
+                    halves[0] & 0xffff,
+                    halves[0] >> 16,
+                    halves[1] & 0xffff,
+                    halves[1] >> 16,
+                ]
+                .map(|half| f16_to_f32(half as u16));
+                particle.velocity = Vector3::new(vx, vy, vz);
+                particle.pressure = p;
+            }
+        }
+    }
+}
+
+/// the neighbour accessors of particle_aos.h.wgsl: name, WGSL type and SphParticle member
+const ACCESSORS: [(&str, &str, &str); 8] = [
+    ("particle_position", "vec3<f32>", "position"),
+    ("particle_velocity", "vec3<f32>", "velocity"),
+    ("particle_density", "f32", "density"),
+    ("particle_pressure", "f32", "pressure"),
+    ("particle_material", "u32", "material"),
+    ("particle_temperature", "f32", "temperature"),
+    ("particle_type", "u32", "ptype"),
+    ("particle_scalars", "vec4<f32>", "scalars"),
+];
+
+/// the field an `ACCESSORS` member belongs to
+fn member_field(member: &str) -> ParticleFields {
+    match member {
+        "position" => ParticleFields::POSITION,
+        "velocity" => ParticleFields::VELOCITY,
+        "density" => ParticleFields::DENSITY,
+        "pressure" => ParticleFields::PRESSURE,
+        "temperature" => ParticleFields::TEMPERATURE,
+        "scalars" => ParticleFields::SCALARS,
+        _ => ParticleFields::KIND,
+    }
+}
+
+/// the buffers of a split layout, in binding order
+fn split_buffers(half_precision: bool) -> &'static [SplitBuffer] {
+    if half_precision {
+        &SPLIT_HALF_BUFFERS
+    } else {
+        &SPLIT_BUFFERS
+    }
+}
+
+/// the bindings of the split layout's buffers
+pub fn split_bindings(half_precision: bool) -> impl Iterator<Item = u32> {
+    split_buffers(half_precision)
+        .iter()
+        .map(|buffer| buffer.binding())
+}
+
+/// which buffers of a split layout a pass binds
+struct PassBuffers {
+    /// bound in the input group
+    bound: Vec<SplitBuffer>,
+    /// loaded by `load_particle`, the own fields and those stored back
+    loaded: Vec<SplitBuffer>,
+    written: Vec<SplitBuffer>,
+    /// written and read from the neighbours, the pass writes them to the output group
+    ping_pong: Vec<SplitBuffer>,
+}
+
+impl PassBuffers {
+    fn new(half_precision: bool, fields: PassFields) -> Self {
+        let buffers = split_buffers(half_precision);
+        let touched = fields.own | fields.neighbors | fields.written;
+        let filter = |keep: &dyn Fn(ParticleFields) -> bool| {
+            buffers
+                .iter()
+                .copied()
+                .filter(|buffer| keep(buffer.fields()))
+                .collect::<Vec<_>>()
+        };
+        Self {
+            bound: filter(&|f| f.intersects(touched)),
+            loaded: filter(&|f| f.intersects(fields.own | fields.written)),
+            written: filter(&|f| f.intersects(fields.written)),
+            ping_pong: filter(&|f| f.intersects(fields.written) && f.intersects(fields.neighbors)),
+        }
+    }
+}
+
+impl ParticleLayout {
+    /// the storage header a solver pass of `fields` includes instead of particle_aos.h.wgsl.
+    /// `None` for the interleaved layout, which uses the file
+    pub fn pass_header(&self, entry_point: &str, fields: PassFields) -> Option<String> {
+        let ParticleLayout::Split { half_precision } = *self else {
+            return None;
+        };
+        Some(split_header(
+            &format!("the storage of {entry_point}"),
+            half_precision,
+            fields,
+            0,
+            "read_write",
+        ))
+    }
+
+    /// particle_view.h.wgsl, the particles as the passes outside the solver read them, in
+    /// bind group `group`. `read_only` for the render passes
+    pub fn view_header(&self, group: u32, read_only: bool) -> String {
+        let access = if read_only { "read" } else { "read_write" };
+        match *self {
+            ParticleLayout::Interleaved => {
+                let mut header = format!(
+                    "// file: particle_view.h, generated by particle_layout.rs\n\
+                     @group({group}) @binding(0)\n\
+                     var<storage, {access}> particles_in: array<SphParticle>;\n\n\
+                     {}\n\
+                     fn load_particle(id: u32) -> SphParticle {{\n    \
+                     return particles_in[id];\n}}\n",
+                    count_header(group, "particles_in")
+                );
+                for (name, ty, member) in ACCESSORS {
+                    let _ = write!(
+                        header,
+                        "\nfn {name}(id: u32) -> {ty} {{\n    \
+                         return particles_in[id].{member};\n}}\n"
+                    );
+                }
+                header
+            }
+            ParticleLayout::Split { half_precision } => split_header(
+                "the particles outside the solver",
+                half_precision,
+                PassFields::VIEW,
+                group,
+                access,
+            ),
+        }
+    }
+
+    /// storage buffers a solver pass of `fields` binds for the particles
+    pub fn storage_buffers(&self, fields: PassFields) -> u32 {
+        match *self {
+            ParticleLayout::Interleaved => 2,
+            ParticleLayout::Split { half_precision } => {
+                let buffers = PassBuffers::new(half_precision, fields);
+                (buffers.bound.len() + buffers.ping_pong.len()) as u32
+            }
+        }
+    }
+
+    /// bytes per particle of the largest buffer of the storage, what limits the particle
+    /// count a device can bind
+    pub fn largest_buffer_stride(&self) -> usize {
+        match *self {
+            ParticleLayout::Interleaved => std::mem::size_of::<ParticleRaw>(),
+            ParticleLayout::Split { half_precision } => split_buffers(half_precision)
+                .iter()
+                .map(|buffer| buffer.stride())
+                .max()
+                .unwrap(),
+        }
+    }
+
+    /// bytes per particle a solver pass of `fields` moves: every buffer it binds read once
+    /// and the written ones once more. the neighbours' fields mostly come from the cache,
+    /// so they count once however many neighbours read them
+    pub fn pass_bytes_per_particle(&self, fields: PassFields) -> usize {
+        match *self {
+            // every pass ping-pongs the whole records
+            ParticleLayout::Interleaved => 2 * std::mem::size_of::<ParticleRaw>(),
+            ParticleLayout::Split { half_precision } => {
+                let buffers = PassBuffers::new(half_precision, fields);
+                buffers
+                    .bound
+                    .iter()
+                    .chain(&buffers.written)
+                    .map(|buffer| buffer.stride())
+                    .sum()
+            }
+        }
+    }
+
+    /// bytes per particle of a packed copy of the storage, see `ParticleStorage::copy_to`
+    pub fn bytes_per_particle(&self) -> usize {
+        match *self {
+            ParticleLayout::Interleaved => std::mem::size_of::<ParticleRaw>(),
+            ParticleLayout::Split { half_precision } => split_buffers(half_precision)
+                .iter()
+                .map(|buffer| buffer.stride())
+                .sum(),
+        }
+    }
+
+    /// bytes of a packed copy of a storage holding `capacity` particles, see
+    /// `ParticleStorage::copy_to`
+    pub fn packed_size(&self, capacity: usize) -> wgpu::BufferAddress {
+        (self.bytes_per_particle() * capacity) as wgpu::BufferAddress + COUNT_SIZE
+    }
+
+    /// `particles` the way the storage holds them, a run of elements per buffer
+    pub fn pack(&self, particles: &[ParticleRaw]) -> Vec<u8> {
+        match *self {
+            ParticleLayout::Interleaved => bytemuck::cast_slice(particles).to_vec(),
+            ParticleLayout::Split { half_precision } => {
+                let particles = particles.iter().map(Particle::from_raw).collect::<Vec<_>>();
+                let mut bytes = Vec::with_capacity(self.bytes_per_particle() * particles.len());
+                for buffer in split_buffers(half_precision) {
+                    for particle in &particles {
+                        buffer.pack(particle, &mut bytes);
+                    }
+                }
+                bytes
+            }
+        }
+    }
+
+    /// the live particles of the copy `ParticleStorage::copy_to` made of a storage holding
+    /// `capacity` particles
+    pub fn unpack(&self, bytes: &[u8], capacity: usize) -> Vec<ParticleRaw> {
+        let count_offset = self.bytes_per_particle() * capacity;
+        let count: u32 = bytemuck::pod_read_unaligned(&bytes[count_offset..count_offset + 4]);
+        let count = count as usize;
+        match *self {
+            ParticleLayout::Interleaved => {
+                bytemuck::pod_collect_to_vec(&bytes[..std::mem::size_of::<ParticleRaw>() * count])
+            }
+            ParticleLayout::Split { half_precision } => {
+                let mut particles = vec![Particle::default(); count];
+                let mut offset = 0;
+                for buffer in split_buffers(half_precision) {
+                    let stride = buffer.stride();
+                    for (i, particle) in particles.iter_mut().enumerate() {
+                        let start = offset + i * stride;
+                        buffer.unpack(&bytes[start..start + stride], particle);
+                    }
+                    offset += stride * capacity;
+                }
+                particles.iter().map(Particle::to_raw).collect()
+            }
+        }
+    }
+}
+
+/// the count uniform in bind group `group`, `num_particles` and `particle_capacity`, the
+/// length of `array`
+fn count_header(group: u32, array: &str) -> String {
+    format!(
+        "@group({group}) @binding({COUNT_BINDING})\n\
+         var<uniform> particle_count: u32;\n\n\
+         // the live particles, they come first in the buffers\n\
+         fn num_particles() -> u32 {{\n    \
+         return particle_count;\n}}\n\n\
+         // the particles the buffers hold, the inlets add at most this many\n\
+         fn particle_capacity() -> u32 {{\n    \
+         return arrayLength(&{array});\n}}\n"
+    )
+}
+
+/// particle_soa.h: the split buffers `fields` touches, inputs in bind group `group` and the
+/// ping-pong outputs in the next one. load_particle only fills the own and the written
+/// fields, the accessors of unbound fields return zero
+fn split_header(
+    title: &str,
+    half_precision: bool,
+    fields: PassFields,
+    group: u32,
+    access: &str,
+) -> String {
+    let buffers = PassBuffers::new(half_precision, fields);
+    let mut header = format!(
+        "// file: particle_soa.h, generated by particle_layout.rs for {title}\n\n\
+         struct ParticleKind {{\n    \
+         ptype: u32,\n    \
+         cell_id: u32,\n    \
+         material: u32,\n}};\n\n\
+         const HALF_MAX: f32 = {HALF_MAX:.1};\n\n"
+    );
+    for buffer in &buffers.bound {
+        let _ = writeln!(
+            header,
+            "@group({group}) @binding({})\nvar<storage, {access}> {}_in: array<{}>;",
+            buffer.binding(),
+            buffer.name(),
+            buffer.element()
+        );
+    }
+    for buffer in &buffers.ping_pong {
+        let _ = writeln!(
+            header,
+            "@group({}) @binding({})\nvar<storage, {access}> {}_out: array<{}>;",
+            group + 1,
+            buffer.binding(),
+            buffer.name(),
+            buffer.element()
+        );
+    }
+
+    let _ = write!(
+        header,
+        "\n{}",
+        count_header(group, &format!("{}_in", buffers.bound[0].name()))
+    );
+
+    header.push_str("\nfn load_particle(id: u32) -> SphParticle {\n    var p: SphParticle;\n");
+    for buffer in &buffers.loaded {
+        let _ = writeln!(
+            header,
+            "    {}",
+            buffer.load(&format!("{}_in", buffer.name()))
+        );
+    }
+    header.push_str("    return p;\n}\n");
+
+    header.push_str("\nfn store_particle(id: u32, p: SphParticle) {\n");
+    for buffer in &buffers.written {
+        let side = if buffers.ping_pong.contains(buffer) {
+            "out"
+        } else {
+            "in"
+        };
+        let _ = writeln!(
+            header,
+            "    {}",
+            buffer.store(&format!("{}_{side}", buffer.name()))
+        );
+    }
+    header.push_str("}\n");
+
+    for (name, ty, member) in ACCESSORS {
+        let field = member_field(member);
+        let _ = write!(header, "\nfn {name}(id: u32) -> {ty} {{\n");
+        match buffers
+            .bound
+            .iter()
+            .find(|buffer| buffer.fields().intersects(field))
+        {
+            Some(buffer) => {
+                let _ = write!(
+                    header,
+                    "    var p: SphParticle;\n    {}\n    return p.{member};\n}}\n",
+                    buffer.load(&format!("{}_in", buffer.name()))
+                );
+            }
+            None => {
+                let _ = write!(header, "    return {ty}();\n}}\n");
+            }
+        }
+    }
+    header
+}
+
+/// the buffers one solver pass binds in a split layout, see `PassFields`
+pub struct SplitPass {
+    /// tells the passes apart in `SplitStorage`'s bind groups
+    key: usize,
+    /// indices into the layout's buffers, bound in group 0
+    bound: Vec<usize>,
+    /// bound in group 1 too, the pass writes their other side
+    ping_pong: Vec<usize>,
+    pub bind_group_layouts: [wgpu::BindGroupLayout; 2],
+}
+
+impl SplitPass {
+    /// `key` must be unique among the passes of a solver
+    pub fn new(
+        device: &wgpu::Device,
+        key: usize,
+        half_precision: bool,
+        fields: PassFields,
+    ) -> Self {
+        let buffers = split_buffers(half_precision);
+        let pass_buffers = PassBuffers::new(half_precision, fields);
+        let indices = |list: &[SplitBuffer]| {
+            list.iter()
+                .map(|buffer| buffers.iter().position(|b| b == buffer).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let bound = indices(&pass_buffers.bound);
+        let ping_pong = indices(&pass_buffers.ping_pong);
+
+        // the count goes with the inputs
+        let bind_group_layouts = [(&bound, true), (&ping_pong, false)].map(|(list, count)| {
+            let entries = list
+                .iter()
+                .map(|&index| wgpu::BindGroupLayoutEntry {
+                    binding: buffers[index].binding(),
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                })
+                .chain(count.then(|| count_layout_entry(wgpu::ShaderStages::COMPUTE)))
+                .collect::<Vec<_>>();
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Particle Split Pass Bind Group Layout"),
+                entries: &entries,
+            })
+        });
+        Self {
+            key,
+            bound,
+            ping_pong,
+            bind_group_layouts,
+        }
+    }
+}
+
+/// the storage of a split layout
+pub struct SplitStorage {
+    half_precision: bool,
+    /// both sides of every buffer of the layout
+    buffers: Vec<[wgpu::Buffer; 2]>,
+    /// the live particles, see `COUNT_BINDING`
+    count: wgpu::Buffer,
+    /// the group 0 and 1 bind groups of a pass, by `SplitPass::key` and the buffers that
+    /// were on their second side when it ran
+    bind_groups: HashMap<(usize, u32), [wgpu::BindGroup; 2]>,
+}
+
+/// the particle buffers of a `ParticleLayout`
+pub enum ParticleStorage {
+    /// two `ParticleRaw` buffers, a stage of the solver starts and ends on the first
+    Interleaved {
+        buffers: Box<[wgpu::Buffer; 2]>,
+        bind_groups: Box<[wgpu::BindGroup; 2]>,
+        /// the live particles, see `COUNT_BINDING`
+        count: Box<wgpu::Buffer>,
+    },
+    Split(SplitStorage),
+}
+
+impl ParticleStorage {
+    /// buffers of `capacity` particles, at least as many as `particle_data`, which are the
+    /// live ones
+    pub fn new(
+        device: &wgpu::Device,
+        compute_bind_group_layout: &wgpu::BindGroupLayout,
+        layout: ParticleLayout,
+        particle_data: &[ParticleRaw],
+        capacity: usize,
+    ) -> Self {
+        let usage = wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_SRC
+            | wgpu::BufferUsages::COPY_DST;
+        let mut particles = particle_data.to_vec();
+        particles.resize(capacity, ParticleRaw::zeroed());
+        let bytes = layout.pack(&particles);
+        let count = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Count Buffer"),
+            contents: bytemuck::bytes_of(&[particle_data.len() as u32, 0, 0, 0]),
+            usage: wgpu::BufferUsages::UNIFORM | usage,
+        });
+        match layout {
+            ParticleLayout::Interleaved => {
+                let buffers = [0, 1].map(|_| {
+                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Particle Buffer"),
+                        contents: &bytes,
+                        usage,
+                    })
+                });
+                let bind_groups = [0, 1].map(|side| {
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("Compute Particle Bind Group"),
+                        layout: compute_bind_group_layout,
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: buffers[side].as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: COUNT_BINDING,
+                                resource: count.as_entire_binding(),
+                            },
+                        ],
+                    })
+                });
+                ParticleStorage::Interleaved {
+                    buffers: Box::new(buffers),
+                    bind_groups: Box::new(bind_groups),
+                    count: Box::new(count),
+                }
+            }
+            ParticleLayout::Split { half_precision } => {
+                let mut offset = 0;
+                let buffers = split_buffers(half_precision)
+                    .iter()
+                    .map(|buffer| {
+                        let size = buffer.stride() * capacity;
+                        let contents = &bytes[offset..offset + size];
+                        offset += size;
+                        [0, 1].map(|_| {
+                            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                                label: Some("Particle Split Buffer"),
+                                contents,
+                                usage,
+                            })
+                        })
+                    })
+                    .collect();
+                ParticleStorage::Split(SplitStorage {
+                    half_precision,
+                    buffers,
+                    count,
+                    bind_groups: HashMap::new(),
+                })
+            }
+        }
+    }
+
+    /// the live particles, see `COUNT_BINDING`
+    pub fn count_buffer(&self) -> &wgpu::Buffer {
+        match self {
+            ParticleStorage::Interleaved { count, .. } => count,
+            ParticleStorage::Split(storage) => &storage.count,
+        }
+    }
+
+    /// both sides of every buffer with its bytes per particle, in the order
+    /// `ParticleLayout::pack` packs them
+    pub fn buffers(&self) -> Vec<(&[wgpu::Buffer; 2], usize)> {
+        match self {
+            ParticleStorage::Interleaved { buffers, .. } => {
+                vec![(&**buffers, std::mem::size_of::<ParticleRaw>())]
+            }
+            ParticleStorage::Split(storage) => split_buffers(storage.half_precision)
+                .iter()
+                .zip(&storage.buffers)
+                .map(|(buffer, sides)| (sides, buffer.stride()))
+                .collect(),
+        }
+    }
+
+    /// the particles as particle_view.h.wgsl binds them, with `layout` from
+    /// `BindGroupLayoutCache::particle_compute_layout` or `particle_render_layout`
+    pub fn view_bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        let mut entries = match self {
+            ParticleStorage::Interleaved { buffers, .. } => vec![wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffers[0].as_entire_binding(),
+            }],
+            ParticleStorage::Split(storage) => split_buffers(storage.half_precision)
+                .iter()
+                .zip(&storage.buffers)
+                .map(|(buffer, sides)| wgpu::BindGroupEntry {
+                    binding: buffer.binding(),
+                    resource: sides[0].as_entire_binding(),
+                })
+                .collect(),
+        };
+        entries.push(wgpu::BindGroupEntry {
+            binding: COUNT_BINDING,
+            resource: self.count_buffer().as_entire_binding(),
+        });
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Particle View Bind Group"),
+            layout,
+            entries: &entries,
+        })
+    }
+
+    /// replace the live particles with `particles`, at most the capacity
+    pub fn write(&self, queue: &wgpu::Queue, layout: ParticleLayout, particles: &[ParticleRaw]) {
+        queue.write_buffer(
+            self.count_buffer(),
+            0,
+            bytemuck::bytes_of(&(particles.len() as u32)),
+        );
+        match self {
+            ParticleStorage::Interleaved { buffers, .. } => {
+                queue.write_buffer(&buffers[0], 0, bytemuck::cast_slice(particles))
+            }
+            ParticleStorage::Split(storage) => {
+                let bytes = layout.pack(particles);
+                let mut offset = 0;
+                for (buffer, sides) in split_buffers(storage.half_precision)
+                    .iter()
+                    .zip(&storage.buffers)
+                {
+                    let size = buffer.stride() * particles.len();
+                    queue.write_buffer(&sides[0], 0, &bytes[offset..offset + size]);
+                    offset += size;
+                }
+            }
+        }
+    }
+
+    /// record a copy of every slot of the storage's `capacity` into the start of
+    /// `destination`, packed as `ParticleLayout::pack` does and followed by the count.
+    /// `ParticleLayout::packed_size` bytes, `ParticleLayout::unpack` reads the live ones
+    pub fn copy_to(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        destination: &wgpu::Buffer,
+        capacity: usize,
+    ) {
+        let mut offset = 0;
+        for (sides, stride) in self.buffers() {
+            let size = (stride * capacity) as wgpu::BufferAddress;
+            encoder.copy_buffer_to_buffer(&sides[0], 0, destination, offset, size);
+            offset += size;
+        }
+        encoder.copy_buffer_to_buffer(self.count_buffer(), 0, destination, offset, COUNT_SIZE);
+    }
+}
+
+/// the bind groups of the solver's passes in one step, see `ParticleStorage::prepare_solver`
+pub struct SolverDispatches {
+    /// `SplitStorage::bind_groups` of every pass, empty with the interleaved layout
+    keys: Vec<(usize, u32)>,
+    /// bit i: split buffer i ends the step on its second side
+    second_sides: u32,
+}
+
+impl ParticleStorage {
+    /// get the bind groups of `split_passes` ready, the passes of a step in the order they
+    /// run. with the interleaved layout the passes take turns with the sides, an even
+    /// number of them starts and ends on the first, and `split_passes` is empty
+    pub fn prepare_solver(
+        &mut self,
+        device: &wgpu::Device,
+        split_passes: &[&SplitPass],
+    ) -> SolverDispatches {
+        let ParticleStorage::Split(storage) = self else {
+            return SolverDispatches {
+                keys: vec![],
+                second_sides: 0,
+            };
+        };
+        let mut second_sides = 0u32;
+        let mut keys = Vec::with_capacity(split_passes.len());
+        for pass in split_passes {
+            let key = (pass.key, second_sides);
+            if !storage.bind_groups.contains_key(&key) {
+                let bind_groups = storage.create_bind_groups(device, pass, second_sides);
+                storage.bind_groups.insert(key, bind_groups);
+            }
+            keys.push(key);
+            for &index in &pass.ping_pong {
+                second_sides ^= 1 << index;
+            }
+        }
+        SolverDispatches { keys, second_sides }
+    }
+
+    /// the group 0 and 1 bind groups of the `index`th pass of the step
+    pub fn solver_bind_groups(
+        &self,
+        dispatches: &SolverDispatches,
+        index: usize,
+    ) -> [&wgpu::BindGroup; 2] {
+        match self {
+            ParticleStorage::Interleaved { bind_groups, .. } => {
+                [&bind_groups[index % 2], &bind_groups[(index + 1) % 2]]
+            }
+            ParticleStorage::Split(storage) => {
+                let [group_0, group_1] = &storage.bind_groups[&dispatches.keys[index]];
+                [group_0, group_1]
+            }
+        }
+    }
+
+    /// record the copies that bring the split buffers the step left on their second side
+    /// back to the first
+    pub fn finish_solver(&self, encoder: &mut wgpu::CommandEncoder, dispatches: &SolverDispatches) {
+        let ParticleStorage::Split(storage) = self else {
+            return;
+        };
+        for (index, [first, second]) in storage.buffers.iter().enumerate() {
+            if dispatches.second_sides >> index & 1 == 1 {
+                encoder.copy_buffer_to_buffer(second, 0, first, 0, first.size());
+            }
+        }
+    }
+}
+
+impl SplitStorage {
+    /// the group 0 and 1 bind groups of `pass` with the buffers in `second_sides` on their
+    /// second side
+    fn create_bind_groups(
+        &self,
+        device: &wgpu::Device,
+        pass: &SplitPass,
+        second_sides: u32,
+    ) -> [wgpu::BindGroup; 2] {
+        let buffers = split_buffers(self.half_precision);
+        let side = |index: usize| (second_sides >> index & 1) as usize;
+        let group = |list: &[usize], flip: usize, layout| {
+            let count = wgpu::BindGroupEntry {
+                binding: COUNT_BINDING,
+                resource: self.count.as_entire_binding(),
+            };
+            let entries = list
+                .iter()
+                .map(|&index| wgpu::BindGroupEntry {
+                    binding: buffers[index].binding(),
+                    resource: self.buffers[index][side(index) ^ flip].as_entire_binding(),
+                })
+                .chain((flip == 0).then_some(count))
+                .collect::<Vec<_>>();
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Particle Split Pass Bind Group"),
+                layout,
+                entries: &entries,
+            })
+        };
+        [
+            group(&pass.bound, 0, &pass.bind_group_layouts[0]),
+            group(&pass.ping_pong, 1, &pass.bind_group_layouts[1]),
+        ]
+    }
+}
+
+/// IEEE binary16 bits of `value`, rounded to the nearest even
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    // subnormal halves, from the mantissa with its implicit one
+    let (half, rest_bits) = if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        (0, (14 - half_exponent) as u32)
+    } else {
+        ((half_exponent as u32) << 10, 13)
+    };
+    let mantissa = if half_exponent <= 0 {
+        mantissa | 0x80_0000
+    } else {
+        mantissa
+    };
+    let half_mantissa = mantissa >> rest_bits;
+    let rest = mantissa & ((1 << rest_bits) - 1);
+    let halfway = 1 << (rest_bits - 1);
+    let round_up = rest > halfway || (rest == halfway && half_mantissa & 1 == 1);
+    // a carry out of the mantissa moves on into the exponent, which is still right
+    sign | (half + half_mantissa + round_up as u32) as u16
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+    match exponent {
+        0 => {
+            let magnitude = mantissa as f32 * (-24f32).exp2();
+            if sign == 0 {
+                magnitude
+            } else {
+                -magnitude
+            }
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | mantissa << 13),
+        _ => f32::from_bits(sign | (exponent + 127 - 15) << 23 | mantissa << 13),
+    }
+}
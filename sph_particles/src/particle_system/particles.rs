@@ -1,10 +1,24 @@
+use anyhow::{anyhow, bail};
 use cgmath::Vector3;
 use log::info;
 use wgpu::util::DeviceExt;
 
-use super::grid::Grid;
+use super::container::{ContainerAnimation, ContainerRaw, ContainerWall};
+use super::elastic::{self, ElasticNeighborRaw};
+use super::flow::{self, FlowBoundaries, FlowFaceRaw};
+use super::fluid_material::FluidMaterialRaw;
+use super::force_field::{ForceField, ForceFieldsRaw};
+use super::gpu_pass::{workgroup_grid, PARTICLE_WORKGROUP_SIZE_X};
+use super::grid::{Grid, MAX_NUM_PARTICLES_PER_CELL, NEIGHBOR_LIST_STRIDE};
+use super::obstacle::{Obstacle, ObstacleRaw};
+use super::particle_layout::{solver_pass_fields, ParticleStorage, SOLVER_PASSES};
+use super::readback::ParticleReadback;
+use super::scalar::{ScalarsRaw, MAX_SCALARS, MAX_SCALAR_SOURCES};
+use super::scene::{
+    NeighborSearch, ParticleLayout, ParticleOrder, Scene, SimParams, SolverMode,
+    AMBIENT_TEMPERATURE,
+};
 use crate::renderer::BindGroupLayoutCache;
-#[allow(unused_imports)]
 use super::utils::{get_particles_3d, get_particles_2d};
 
 #[derive(Debug, Clone, Copy)]
@@ -15,6 +29,12 @@ pub struct Particle {
     pub density: f32,
     pub ptype: u32, // 0: fluid, 1: boundary
     pub cell_id: u32,
+    /// index into the scene's material table
+    pub material: u32,
+    /// °C
+    pub temperature: f32,
+    /// passive scalar channels, see scalar.rs
+    pub scalars: [f32; MAX_SCALARS],
 }
 
 #[repr(C)]
@@ -26,7 +46,9 @@ pub struct ParticleRaw {
     pressure: f32,
     ptype: u32,
     pub cell_id: u32,
-    _pad: [f32; 2],
+    material: u32,
+    temperature: f32,
+    scalars: [f32; MAX_SCALARS],
 }
 
 impl Default for Particle {
@@ -38,12 +60,15 @@ impl Default for Particle {
             density: 1000.0,
             ptype: 0,
             cell_id: 0,
+            material: 0,
+            temperature: AMBIENT_TEMPERATURE,
+            scalars: [0.0; MAX_SCALARS],
         }
     }
 }
 
 impl Particle {
-    fn to_raw(&self) -> ParticleRaw {
+    pub fn to_raw(&self) -> ParticleRaw {
         ParticleRaw {
             position: self.position.into(),
             velocity: self.velocity.into(),
@@ -51,9 +76,29 @@ impl Particle {
             density: self.density,
             ptype: self.ptype,
             cell_id: self.cell_id,
-            _pad: [0.0, 0.0],
+            material: self.material,
+            temperature: self.temperature,
+            scalars: self.scalars,
         }
     }
+
+    pub fn from_raw(raw: &ParticleRaw) -> Self {
+        Self {
+            position: raw.position.into(),
+            velocity: raw.velocity.into(),
+            pressure: raw.pressure,
+            density: raw.density,
+            ptype: raw.ptype,
+            cell_id: raw.cell_id,
+            material: raw.material,
+            temperature: raw.temperature,
+            scalars: raw.scalars,
+        }
+    }
+
+    pub fn is_fluid(&self) -> bool {
+        self.ptype == 0
+    }
 }
 
 #[repr(C)]
@@ -63,40 +108,103 @@ pub struct WorldData {
     pub particle_radius: f32,
     pub boundary_lower: [f32; 3],
     pub support_radius: f32,
+    pub container: ContainerRaw,
+    pub flow: [FlowFaceRaw; 6],
+    /// m_V, see `ParticleState::new`
+    pub rest_volume: f32,
+    _pad: [f32; 3],
+}
+
+/// heat.h.wgsl HeatSource
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct HeatSourceRaw {
+    pub lower: [f32; 3],
+    pub temperature: f32,
+    pub upper: [f32; 3],
+    pub rate: f32,
 }
 
 pub struct ParticleState {
     pub particle_radius: f32,
     pub support_radius: f32,
     pub grid_2d: Grid,
+    pub sim_params: SimParams,
+    /// any block uses a material with its own viscosity model
+    pub has_material_viscosity: bool,
+    /// the scene has heat sources or blocks at different temperatures
+    pub has_heat_transfer: bool,
+    /// any block is an elastic solid, adds the elastic stress and force passes
+    pub has_elastic: bool,
+    /// the scene has passive scalar channels, adds the scalar transport pass
+    pub has_scalars: bool,
+    pub container: ContainerAnimation,
+    /// seconds simulated, drives the container animation
+    pub time: f32,
+    /// solver steps run so far, a frame runs `SimParams::substeps` of them
+    pub steps: u64,
+    /// inlets and outlets, see `take_flow_particles`
+    pub flow: FlowBoundaries,
+    /// particles the buffers hold, the live ones come first. more than the scene starts
+    /// with when it has inlets, which stop adding once the buffers are full
+    pub capacity: usize,
 
     // particle data shared with shaders
     pub cell_extens: Vec<Vector3<u32>>,
     pub cell_id_offsets: Vec<u32>,
 
-    // staging buffer for reading data back
+    /// the live particles as last uploaded or read back, see `dump_particle_data_from_gpu`
     pub particle_data: Vec<ParticleRaw>,
-    pub particle_buffers: [wgpu::Buffer; 2], // double buffer
+    /// workgroups covering the capacity, see `workgroup_grid`
+    pub dispatch_size: (u32, u32, u32),
+    /// the particles in `layout`, see particle_layout.rs
+    pub layout: ParticleLayout,
+    pub storage: ParticleStorage,
+    /// a packed copy of `storage` for reading data back, see `ParticleStorage::copy_to`
     pub staging_buffer: wgpu::Buffer,
+    /// snapshots for the consumers that can wait, `staging_buffer` blocks until the
+    /// GPU is done
+    pub readback: ParticleReadback,
 
-    // wgpu state
+    // wgpu state, the particles as the passes outside the solver bind them
     pub particle_render_bind_group: wgpu::BindGroup,
-    pub particle_compute_bind_group_0: wgpu::BindGroup,
-    pub particle_compute_bind_group_1: wgpu::BindGroup,
+    pub particle_compute_bind_group: wgpu::BindGroup,
 
     // world data buffers
+    pub world_data: WorldData,
     pub world_buffer: wgpu::Buffer,
+    /// world uniforms of every substep of an animated container, see `stage_container`
+    container_staging_buffer: Option<wgpu::Buffer>,
+    pub material_buffer: wgpu::Buffer,
+    pub heat_source_buffer: wgpu::Buffer,
+    /// rest shape and stress of the elastic particles, see elastic.rs
+    pub elastic_particle_buffer: wgpu::Buffer,
+    pub elastic_neighbor_buffer: wgpu::Buffer,
+    /// contents of `elastic_neighbor_buffer`, renumbered on every reorder
+    elastic_neighbors: Vec<ElasticNeighborRaw>,
+    /// uploaded with `SimParams::gravity` at the start of every step
+    pub force_fields: Vec<ForceField>,
+    pub force_field_buffer: wgpu::Buffer,
+    pub obstacles: Vec<Obstacle>,
+    pub obstacle_buffer: wgpu::Buffer,
+    pub scalar_buffer: wgpu::Buffer,
+    /// obstacles of every substep when any of them moves, see `stage_obstacles`
+    obstacle_staging_buffer: Option<wgpu::Buffer>,
     pub world_bind_group: wgpu::BindGroup,
 }
 
 impl ParticleState {
-    pub fn new(device: &wgpu::Device, bind_group_layout_cache: &BindGroupLayoutCache) -> Self {
-        let particle_radius = 0.1;
-        let support_radius = 0.4;
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layout_cache: &BindGroupLayoutCache,
+        scene: &Scene,
+    ) -> anyhow::Result<Self> {
+        let particle_radius = scene.particle_radius;
+        let support_radius = scene.support_radius;
 
         let grid = Grid::new(
-            Vector3::new(0.0, 0.0, 0.0),
-            Vector3::new(25, 25, 25),
+            scene.domain_lower,
+            scene.domain_cells,
             Vector3::new(support_radius, support_radius, support_radius),
         );
 
@@ -109,23 +217,120 @@ impl ParticleState {
         // fluids
         let mut particle_list = vec![];
 
-        particle_list.append(&mut get_particles_3d(
-            (1.0, 2.0, 1.0),
-            (8.0, 5.0, 4.0),
-            true,
-            1000.0,
-            Some(Vector3::new(2.0, -2.0, 0.0)),
-            particle_radius * 2.0,
-        ));
-
-        particle_list.append(&mut get_particles_3d(
-            (4.0, 4.0, 4.0),
-            (9.0, 9.0, 9.0),
-            true,
-            1000.0,
-            Some(Vector3::new(-4.0, -2.0, 0.0)),
+        // one material per block, the particles refer to it by index
+        let mut materials = scene
+            .blocks
+            .iter()
+            .map(|block| block.material.to_raw())
+            .collect::<Vec<_>>();
+        if materials.is_empty() {
+            materials.push(FluidMaterialRaw::default());
+        }
+
+        // the inlets' materials follow those of the blocks
+        let planar = scene.solver == SolverMode::Planar;
+        let flow = FlowBoundaries::new(
+            &scene.inlets,
+            &scene.outlets,
+            grid.boundary_lower,
+            grid.boundary_upper,
             particle_radius * 2.0,
-        ));
+            planar,
+            materials.len() as u32,
+        );
+        materials.extend(flow.materials().map(|material| material.to_raw()));
+        let has_material_viscosity = scene.blocks.iter().any(|b| b.material.is_viscous())
+            || flow.materials().any(|material| material.is_viscous());
+
+        if scene.scalars.len() > MAX_SCALARS {
+            bail!(
+                "the scene has {} scalar channels, particles carry at most {MAX_SCALARS}",
+                scene.scalars.len()
+            );
+        }
+        if scene.scalar_sources.len() > MAX_SCALAR_SOURCES {
+            bail!(
+                "the scene has {} scalar sources, at most {MAX_SCALAR_SOURCES} are supported",
+                scene.scalar_sources.len()
+            );
+        }
+        if let Some(source) = scene
+            .scalar_sources
+            .iter()
+            .find(|source| source.channel >= scene.scalars.len())
+        {
+            bail!("a scalar source feeds channel {}, which the scene lacks", source.channel);
+        }
+        if let Some(field) = scene.force_fields.iter().find(|field| !field.has_valid_axis()) {
+            bail!("the vortex at {:?} has no axis direction, {:?}", field.center, field.kind);
+        }
+        if let Some(elastic) = scene
+            .blocks
+            .iter()
+            .filter_map(|block| block.material.elastic)
+            .find(|elastic| !elastic.has_valid_poisson_ratio())
+        {
+            bail!(
+                "the Poisson ratio of an elastic block is {}, it must be in [0, 0.5)",
+                elastic.poisson_ratio
+            );
+        }
+
+        // a rate of zero keeps the placeholder entry inert
+        let mut heat_sources = scene
+            .heat_sources
+            .iter()
+            .map(|source| HeatSourceRaw {
+                lower: source.lower.into(),
+                temperature: source.temperature,
+                upper: source.upper.into(),
+                rate: source.rate,
+            })
+            .collect::<Vec<_>>();
+        if heat_sources.is_empty() {
+            heat_sources.push(HeatSourceRaw::default());
+        }
+        let temperatures = scene
+            .blocks
+            .iter()
+            .map(|block| block.temperature)
+            .chain(scene.inlets.iter().map(|inlet| inlet.temperature))
+            .collect::<Vec<_>>();
+        let has_heat_transfer = !scene.heat_sources.is_empty()
+            || temperatures.windows(2).any(|pair| pair[0] != pair[1]);
+
+        // the particles of each elastic block, in particle order
+        let mut elastic_bodies = vec![];
+        for (material, block) in scene.blocks.iter().enumerate() {
+            let mut block_particles = match scene.solver {
+                SolverMode::Planar => get_particles_2d(
+                    (block.lower.x, block.lower.y),
+                    (block.upper.x, block.upper.y),
+                    block.is_fluid,
+                    1000.0,
+                    Some(block.velocity),
+                    particle_radius * 2.0,
+                ),
+                SolverMode::Volumetric => get_particles_3d(
+                    block.lower.into(),
+                    block.upper.into(),
+                    block.is_fluid,
+                    1000.0,
+                    Some(block.velocity),
+                    particle_radius * 2.0,
+                ),
+            };
+            for p in block_particles.iter_mut() {
+                p.material = material as u32;
+                p.temperature = block.temperature;
+                p.scalars = block.scalars;
+            }
+            if block.material.elastic.is_some() {
+                let start = particle_list.len();
+                elastic_bodies.push(start..start + block_particles.len());
+            }
+            particle_list.append(&mut block_particles);
+        }
 
         // generate walls
         // let particle_diameter = particle_radius * 2.0;
@@ -170,8 +375,52 @@ impl ParticleState {
         //     particle_diameter,
         // ));
 
+        // the buffer zones of the inlets start out full
+        particle_list.append(&mut flow.initial_particles(support_radius));
+
         info!("particle list len: {}", particle_list.len());
 
+        // indices into the particle buffers, `reorder_particles` renumbers them
+        let has_elastic = !elastic_bodies.is_empty();
+        if !flow.is_empty() {
+            if has_elastic {
+                bail!("inlets and outlets can't add or delete the particles of elastic solids");
+            }
+            if !scene.container.is_static() {
+                bail!("inlets and outlets need a static container");
+            }
+            let z_face = |wall| matches!(wall, ContainerWall::MinZ | ContainerWall::MaxZ);
+            if planar
+                && (scene.inlets.iter().any(|inlet| z_face(inlet.wall))
+                    || scene.outlets.iter().copied().any(z_face))
+            {
+                bail!("the planar solver has no inlets or outlets on the z faces");
+            }
+        }
+        // the dense grid of the cached lists clamps what leaves it into its border cells
+        if scene.neighbor_search == NeighborSearch::CachedList && !scene.container.is_static() {
+            let (lower, upper) =
+                scene.container.swept_bounds(grid.boundary_lower, grid.boundary_upper);
+            let slack = 1e-3 * particle_radius;
+            if (0..3).any(|axis| {
+                lower[axis] < grid.boundary_lower[axis] - slack
+                    || upper[axis] > grid.boundary_upper[axis] + slack
+            }) {
+                bail!(
+                    "the container moves out of the domain, {lower:?} to {upper:?} leaves \
+                     {:?} to {:?}. the cached neighbour lists need it inside, \
+                     NeighborSearch::HashedList follows the particles out",
+                    grid.boundary_lower,
+                    grid.boundary_upper
+                );
+            }
+        }
+        let (elastic_particles, mut elastic_neighbors) =
+            elastic::rest_shape(&particle_list, &elastic_bodies, support_radius);
+        if elastic_neighbors.is_empty() {
+            elastic_neighbors.push(Default::default());
+        }
+
         // ---------------------------------------
 
         let particle_data = particle_list
@@ -179,62 +428,131 @@ impl ParticleState {
             .map(Particle::to_raw)
             .collect::<Vec<_>>();
 
-        let particle_buffers = [
-            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Particle Buffer"),
-                contents: bytemuck::cast_slice(&particle_data),
-                usage: wgpu::BufferUsages::STORAGE
-                    | wgpu::BufferUsages::COPY_SRC
-                    | wgpu::BufferUsages::COPY_DST,
-            }),
-            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Particle Buffer"),
-                contents: bytemuck::cast_slice(&particle_data),
-                usage: wgpu::BufferUsages::STORAGE
-                    | wgpu::BufferUsages::COPY_SRC
-                    | wgpu::BufferUsages::COPY_DST,
-            }),
-        ];
+        // room for the inlets to fill the domain, as far as the device can bind and dispatch
+        // over it. the checks below still fail for a scene starting with too many
+        let limits = device.limits();
+        let max_bytes = limits.max_storage_buffer_binding_size as u64;
+        let max_workgroups = limits.max_compute_workgroups_per_dimension;
+        let buffer_stride = scene.layout.largest_buffer_stride() as u64;
+        let index_bytes = std::mem::size_of::<u32>() as u64;
+        let list_bytes_per_particle = index_bytes * NEIGHBOR_LIST_STRIDE as u64;
+        let capacity = if flow.is_empty() {
+            particle_data.len()
+        } else {
+            let mut max_particles = max_bytes / buffer_stride;
+            if scene.neighbor_search != NeighborSearch::AllPairs {
+                max_particles = max_particles.min(max_bytes / list_bytes_per_particle);
+            }
+            let max_particles = max_particles.min(
+                max_workgroups as u64 * max_workgroups as u64 * PARTICLE_WORKGROUP_SIZE_X as u64,
+            ) as usize;
+            flow::domain_capacity(
+                grid.boundary_lower,
+                grid.boundary_upper,
+                particle_radius * 2.0,
+                planar,
+            )
+            .min(max_particles)
+            .max(particle_data.len())
+        };
+        info!("particle capacity: {capacity}");
+
+        // fail before allocating anything the device can't bind or dispatch over
+        let particle_bytes = buffer_stride * capacity as u64;
+        if particle_bytes > max_bytes {
+            bail!(
+                "the scene needs room for {capacity} particles ({particle_bytes} bytes), more \
+                 than the {max_bytes} bytes the device can bind in one storage buffer"
+            );
+        }
+        let too_many = |what: &str, count: usize| {
+            anyhow!(
+                "the scene has {count} {what}, more than the device can dispatch \
+                 ({max_workgroups}x{max_workgroups} workgroups)"
+            )
+        };
+        let dispatch_size = workgroup_grid(capacity as u32, max_workgroups)
+            .ok_or_else(|| too_many("particles", capacity))?;
+        // the particle buffers of the pass binding the most plus the scratch space,
+        // materials, heat sources, the two elastic rest shape buffers and the obstacles, and
+        // the four of the cached neighbour lists
+        let particle_storage_buffers = SOLVER_PASSES
+            .iter()
+            .map(|pass| scene.layout.storage_buffers(solver_pass_fields(pass)))
+            .max()
+            .unwrap();
+        let neighbor_storage_buffers = match scene.neighbor_search {
+            NeighborSearch::AllPairs => 0,
+            NeighborSearch::CachedList | NeighborSearch::HashedList { .. } => 4,
+        };
+        let storage_buffers = particle_storage_buffers + 6 + neighbor_storage_buffers;
+        if limits.max_storage_buffers_per_shader_stage < storage_buffers {
+            bail!(
+                "the {:?} particle layout with {:?} neighbour search binds {storage_buffers} \
+                 storage buffers per pass, the device allows {}",
+                scene.layout,
+                scene.neighbor_search,
+                limits.max_storage_buffers_per_shader_stage
+            );
+        }
+        if scene.neighbor_search != NeighborSearch::AllPairs {
+            let list_bytes = list_bytes_per_particle * capacity as u64;
+            let cell_bytes = index_bytes
+                * MAX_NUM_PARTICLES_PER_CELL as u64
+                * scene.neighbor_search.num_buckets(scene.domain_cells) as u64;
+            if list_bytes > max_bytes || cell_bytes > max_bytes {
+                bail!(
+                    "the cached neighbour search needs {list_bytes} bytes of neighbour lists \
+                     and {cell_bytes} bytes of cells, more than the {max_bytes} bytes \
+                     the device can bind in one storage buffer"
+                );
+            }
+        }
+
+        let storage = ParticleStorage::new(
+            device,
+            &bind_group_layout_cache.particle_compute_bind_group_layout,
+            scene.layout,
+            &particle_data,
+            capacity,
+        );
 
         let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Particle Staging Buffer"),
-            size: (std::mem::size_of::<ParticleRaw>() * particle_data.len()) as wgpu::BufferAddress,
+            size: scene.layout.packed_size(capacity),
             usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
-        let particle_compute_bind_group_0 = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Compute Particle Bind Group 0"),
-            layout: &bind_group_layout_cache.particle_compute_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: particle_buffers[0].as_entire_binding(),
-            }],
-        });
-
-        let particle_compute_bind_group_1 = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Compute Particle Bind Group 1"),
-            layout: &bind_group_layout_cache.particle_compute_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: particle_buffers[1].as_entire_binding(),
-            }],
-        });
+        let particle_compute_bind_group = storage.view_bind_group(
+            device,
+            bind_group_layout_cache.particle_compute_layout(scene.layout),
+        );
+        let particle_render_bind_group = storage.view_bind_group(
+            device,
+            bind_group_layout_cache.particle_render_layout(scene.layout),
+        );
 
-        let particle_render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Render Particle Bind Group"),
-            layout: &bind_group_layout_cache.particle_render_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: particle_buffers[0].as_entire_binding(),
-            }],
-        });
+        let rest_volume = scene.rest_volume();
+        info!("particle rest volume {rest_volume}");
 
+        // the particles leave through the outlets
+        let mut container = scene.container.clone();
+        container.open_walls.extend(
+            scene
+                .outlets
+                .iter()
+                .filter(|wall| !scene.container.open_walls.contains(wall)),
+        );
         let world_data = WorldData {
             boundary_upper: grid.boundary_upper.into(),
             boundary_lower: grid.boundary_lower.into(),
             particle_radius,
             support_radius,
+            container: container.to_raw(grid.boundary_lower, grid.boundary_upper, 0.0),
+            flow: flow.to_raw(grid.boundary_lower, grid.boundary_upper),
+            rest_volume,
+            _pad: [0.0; 3],
         };
 
         let world_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -243,56 +561,366 @@ impl ParticleState {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        let material_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Buffer"),
+            contents: bytemuck::cast_slice(&materials),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let heat_source_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Heat Source Buffer"),
+            contents: bytemuck::cast_slice(&heat_sources),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let elastic_particle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Elastic Particle Buffer"),
+            contents: bytemuck::cast_slice(&elastic_particles),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let elastic_neighbor_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Elastic Neighbor Buffer"),
+            contents: bytemuck::cast_slice(&elastic_neighbors),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let force_field_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Force Field Buffer"),
+            contents: bytemuck::bytes_of(&ForceFieldsRaw::new(
+                scene.params.gravity,
+                &scene.force_fields,
+            )),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // an empty slot keeps the buffer from being empty
+        let mut obstacles = scene
+            .obstacles
+            .iter()
+            .map(|obstacle| obstacle.to_raw(0.0))
+            .collect::<Vec<_>>();
+        if obstacles.is_empty() {
+            obstacles.push(ObstacleRaw::default());
+        }
+        let obstacle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Obstacle Buffer"),
+            contents: bytemuck::cast_slice(&obstacles),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let scalar_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Scalar Buffer"),
+            contents: bytemuck::bytes_of(&ScalarsRaw::new(&scene.scalars, &scene.scalar_sources)),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
         let world_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("World Bind Group"),
             layout: &bind_group_layout_cache.world_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: world_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: world_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: material_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: heat_source_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: force_field_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: elastic_particle_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: elastic_neighbor_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: obstacle_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: scalar_buffer.as_entire_binding(),
+                },
+            ],
         });
 
-        Self {
+        Ok(Self {
             particle_data,
-            particle_buffers,
+            dispatch_size,
+            layout: scene.layout,
+            storage,
             staging_buffer,
-            particle_compute_bind_group_0,
-            particle_compute_bind_group_1,
+            particle_compute_bind_group,
             particle_render_bind_group,
+            readback: ParticleReadback::new(),
             particle_radius,
             support_radius,
             grid_2d: grid,
+            sim_params: scene.params,
+            has_material_viscosity,
+            has_heat_transfer,
+            has_elastic,
+            has_scalars: !scene.scalars.is_empty(),
+            container,
+            time: 0.0,
+            steps: 0,
+            flow,
+            capacity,
             cell_extens,
             cell_id_offsets,
+            world_data,
             world_buffer,
+            container_staging_buffer: None,
+            material_buffer,
+            heat_source_buffer,
+            elastic_particle_buffer,
+            elastic_neighbor_buffer,
+            elastic_neighbors,
+            force_fields: scene.force_fields.clone(),
+            force_field_buffer,
+            obstacles: scene.obstacles.clone(),
+            obstacle_buffer,
+            scalar_buffer,
+            obstacle_staging_buffer: None,
             world_bind_group,
+        })
+    }
+
+    /// upload gravity and the force fields, they may have been edited since the last step
+    pub fn upload_force_fields(&self, queue: &wgpu::Queue) {
+        let raw = ForceFieldsRaw::new(self.sim_params.gravity, &self.force_fields);
+        queue.write_buffer(&self.force_field_buffer, 0, bytemuck::bytes_of(&raw));
+    }
+
+    /// stage the world uniforms of the next `substeps` steps, so `copy_container` can
+    /// move each into place between the passes of one command buffer. false for a static
+    /// container, its uniforms never change
+    pub fn stage_container(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        substeps: u32,
+    ) -> bool {
+        if self.container.is_static() {
+            return false;
         }
+
+        // the same sums as `time` will go through, so substeps don't change the poses
+        let world_data = (0..substeps)
+            .scan(self.time, |time, _| {
+                let container = self.container.to_raw(
+                    self.grid_2d.boundary_lower,
+                    self.grid_2d.boundary_upper,
+                    *time,
+                );
+                *time += self.sim_params.time_step;
+                Some(WorldData {
+                    container,
+                    ..self.world_data
+                })
+            })
+            .collect::<Vec<_>>();
+        self.world_data = *world_data.last().unwrap_or(&self.world_data);
+
+        let size = std::mem::size_of_val(world_data.as_slice()) as wgpu::BufferAddress;
+        if self
+            .container_staging_buffer
+            .as_ref()
+            .is_none_or(|buffer| buffer.size() < size)
+        {
+            self.container_staging_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Container Staging Buffer"),
+                size,
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        let staging_buffer = self.container_staging_buffer.as_ref().unwrap();
+        queue.write_buffer(staging_buffer, 0, bytemuck::cast_slice(&world_data));
+        true
     }
 
-    /// upload particle data to index 0
-    pub fn upload_particle_data_to_gpu(&self, queue: &wgpu::Queue) {
-        queue.write_buffer(
-            &self.particle_buffers[0],
+    /// world uniforms of `substep` from the last `stage_container`
+    pub fn copy_container(&self, encoder: &mut wgpu::CommandEncoder, substep: u32) {
+        let Some(staging_buffer) = &self.container_staging_buffer else {
+            return;
+        };
+        let size = std::mem::size_of::<WorldData>() as wgpu::BufferAddress;
+        encoder.copy_buffer_to_buffer(
+            staging_buffer,
+            substep as wgpu::BufferAddress * size,
+            &self.world_buffer,
+            0,
+            size,
+        );
+    }
+
+    /// stage the obstacles of the next `substeps` steps, so `copy_obstacles` can move each
+    /// into place between the passes of one command buffer. false when none of them move,
+    /// the buffer keeps the poses they were created with
+    pub fn stage_obstacles(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        substeps: u32,
+    ) -> bool {
+        if self.obstacles.iter().all(Obstacle::is_static) {
+            return false;
+        }
+
+        // the same times as `stage_container`
+        let obstacles = (0..substeps)
+            .scan(self.time, |time, _| {
+                let t = *time;
+                *time += self.sim_params.time_step;
+                Some(self.obstacles.iter().map(move |obstacle| obstacle.to_raw(t)))
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let size = std::mem::size_of_val(obstacles.as_slice()) as wgpu::BufferAddress;
+        if self
+            .obstacle_staging_buffer
+            .as_ref()
+            .is_none_or(|buffer| buffer.size() < size)
+        {
+            self.obstacle_staging_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Obstacle Staging Buffer"),
+                size,
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        let staging_buffer = self.obstacle_staging_buffer.as_ref().unwrap();
+        queue.write_buffer(staging_buffer, 0, bytemuck::cast_slice(&obstacles));
+        true
+    }
+
+    /// obstacles of `substep` from the last `stage_obstacles`
+    pub fn copy_obstacles(&self, encoder: &mut wgpu::CommandEncoder, substep: u32) {
+        let Some(staging_buffer) = &self.obstacle_staging_buffer else {
+            return;
+        };
+        let size = (std::mem::size_of::<ObstacleRaw>() * self.obstacles.len())
+            as wgpu::BufferAddress;
+        encoder.copy_buffer_to_buffer(
+            staging_buffer,
+            substep as wgpu::BufferAddress * size,
+            &self.obstacle_buffer,
             0,
-            bytemuck::cast_slice(&self.particle_data),
+            size,
         );
     }
 
-    /// dump particle data from index buffer
+    /// upload particle data to the first side of the storage
+    pub fn upload_particle_data_to_gpu(&self, queue: &wgpu::Queue) {
+        self.storage.write(queue, self.layout, &self.particle_data);
+    }
+
+    /// sort the particles by the id of their cell in `order`. stable, particles in the
+    /// same cell keep their order so the result only depends on the previous step. the
+    /// rest shapes of the elastic particles move along. false when the order was kept
+    pub async fn reorder_particles(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        order: ParticleOrder,
+    ) -> bool {
+        self.dump_particle_data_from_gpu(device, queue).await;
+        for particle in &mut self.particle_data {
+            particle.cell_id = self.grid_2d.cell_id(particle.position.into(), order);
+        }
+        let mut permutation = (0..self.particle_data.len() as u32).collect::<Vec<_>>();
+        // particles in the same cell keep their previous order
+        permutation.sort_unstable_by_key(|&i| (self.particle_data[i as usize].cell_id, i));
+        if permutation.iter().enumerate().all(|(new, &old)| new as u32 == old) {
+            return false;
+        }
+
+        self.particle_data = permutation
+            .iter()
+            .map(|&old| self.particle_data[old as usize])
+            .collect();
+        self.upload_particle_data_to_gpu(queue);
+
+        if self.has_elastic {
+            let elastic_particles = read_buffer(
+                device,
+                queue,
+                &self.elastic_particle_buffer,
+                self.particle_data.len(),
+            )
+            .await;
+            let elastic_particles =
+                elastic::reorder(&elastic_particles, &mut self.elastic_neighbors, &permutation);
+            queue.write_buffer(
+                &self.elastic_particle_buffer,
+                0,
+                bytemuck::cast_slice(&elastic_particles),
+            );
+            queue.write_buffer(
+                &self.elastic_neighbor_buffer,
+                0,
+                bytemuck::cast_slice(&self.elastic_neighbors),
+            );
+        }
+        true
+    }
+
+    /// the particles that entered through the inlets since the last call, at `time`.
+    /// `None` when none did and the outlets aren't due a sweep either, otherwise
+    /// `ComputeFlowPass` appends them and deletes the particles that left through the
+    /// outlets on the GPU
+    pub fn take_flow_particles(&mut self) -> Option<Vec<ParticleRaw>> {
+        if self.flow.is_empty() || !self.flow.advance(self.time) {
+            return None;
+        }
+        Some(
+            self.flow
+                .take_new_particles()
+                .iter()
+                .map(Particle::to_raw)
+                .collect(),
+        )
+    }
+
+    /// copy the particles into the readback ring if it has a snapshot requested, see
+    /// `ParticleReadback::end_frame`. call once per frame after its last pass
+    pub fn end_frame_readback(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.readback
+            .end_frame(device, queue, &self.storage, self.layout, self.capacity);
+    }
+
+    /// the live particles on the GPU, blocks until it is done
+    pub async fn read_count(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> usize {
+        let count: Vec<u32> = read_buffer(device, queue, self.storage.count_buffer(), 1).await;
+        count[0] as usize
+    }
+
+    /// dump the live particles from the first side of the storage
     pub async fn dump_particle_data_from_gpu(
         &mut self,
-        particle_buffer_idx: usize,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
     ) {
         let mut command_encoder =
             device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        command_encoder.copy_buffer_to_buffer(
-            &self.particle_buffers[particle_buffer_idx % 2],
-            0,
+        self.storage.copy_to(
+            &mut command_encoder,
             &self.staging_buffer,
-            0,
-            (std::mem::size_of::<ParticleRaw>() * self.particle_data.len()) as wgpu::BufferAddress,
+            self.capacity,
         );
         queue.submit(Some(command_encoder.finish()));
         let buffer_slice = self.staging_buffer.slice(..);
@@ -300,8 +928,37 @@ impl ParticleState {
         buffer_slice.map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap());
         device.poll(wgpu::Maintain::Wait);
         receiver.receive().await.unwrap().unwrap();
-        self.particle_data
-            .copy_from_slice(bytemuck::cast_slice(&buffer_slice.get_mapped_range()[..]));
+        self.particle_data = self
+            .layout
+            .unpack(&buffer_slice.get_mapped_range()[..], self.capacity);
         self.staging_buffer.unmap();
     }
-}
\ No newline at end of file
+}
+
+/// the first `len` elements of `buffer`, through a temporary staging buffer
+async fn read_buffer<T: bytemuck::Pod>(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    len: usize,
+) -> Vec<T> {
+    let size = (std::mem::size_of::<T>() * len) as wgpu::BufferAddress;
+    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Staging Buffer"),
+        size,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut command_encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    command_encoder.copy_buffer_to_buffer(buffer, 0, &staging_buffer, 0, size);
+    queue.submit(Some(command_encoder.finish()));
+    let buffer_slice = staging_buffer.slice(..);
+    let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+    buffer_slice.map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap());
+    device.poll(wgpu::Maintain::Wait);
+    receiver.receive().await.unwrap().unwrap();
+    let data = bytemuck::cast_slice(&buffer_slice.get_mapped_range()[..]).to_vec();
+    staging_buffer.unmap();
+    data
+}
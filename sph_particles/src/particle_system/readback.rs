@@ -0,0 +1,218 @@
+//! Snapshots of the particle buffer for the consumers that can wait a frame or two, like
+//! exporters and statistics. A request is copied into a free slot of a ring of map-read
+//! buffers at the end of the frame, and handed out to the ticket it returned once its map
+//! finishes, without ever waiting on the GPU. the requests of the same frame share a copy. `ParticleState::dump_particle_data_from_gpu` is left to the passes
+//! that need the current particles
+use super::particle_layout::ParticleStorage;
+use super::particles::Particle;
+use super::scene::ParticleLayout;
+
+/// number of map-read buffers the snapshots rotate through
+pub const READBACK_RING_SIZE: usize = 3;
+
+/// names a requested snapshot, see `ParticleReadback::take_snapshot`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SnapshotTicket(u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SlotState {
+    Free,
+    /// copied at the end of `frame`, the map hasn't finished yet
+    InFlight {
+        frame: u64,
+    },
+    /// mapped, holding the particles of the end of `frame`
+    Ready {
+        frame: u64,
+    },
+}
+
+struct ReadbackSlot {
+    /// grown to the capacity of the copy, `None` until the first one
+    buffer: Option<wgpu::Buffer>,
+    capacity: usize,
+    /// of the copy, packed as `ParticleLayout::pack` does
+    layout: ParticleLayout,
+    state: SlotState,
+    /// the requests copied into it, it is freed once each of them took the snapshot
+    tickets: Vec<SnapshotTicket>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParticleSnapshot {
+    /// frame the particles were copied at the end of, counting from 1 like
+    /// `HeadlessSim::steps`
+    pub frame: u64,
+    pub particles: Vec<Particle>,
+}
+
+pub struct ParticleReadback {
+    ring: Vec<ReadbackSlot>,
+    sender: flume::Sender<(usize, bool)>,
+    receiver: flume::Receiver<(usize, bool)>,
+    /// requests for the end of the frame, wait for a free slot when all of them are busy
+    pending: Vec<SnapshotTicket>,
+    next_ticket: u64,
+    /// frames ended so far
+    frame: u64,
+}
+
+impl Default for ParticleReadback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParticleReadback {
+    pub fn new() -> Self {
+        let (sender, receiver) = flume::unbounded();
+        Self {
+            ring: (0..READBACK_RING_SIZE)
+                .map(|_| ReadbackSlot {
+                    buffer: None,
+                    capacity: 0,
+                    layout: ParticleLayout::default(),
+                    state: SlotState::Free,
+                    tickets: vec![],
+                })
+                .collect(),
+            sender,
+            receiver,
+            pending: vec![],
+            next_ticket: 0,
+            frame: 0,
+        }
+    }
+
+    /// copy the particles at the end of the current frame into the next free slot, the
+    /// ticket takes the snapshot once it arrived
+    pub fn request(&mut self) -> SnapshotTicket {
+        let ticket = SnapshotTicket(self.next_ticket);
+        self.next_ticket += 1;
+        self.pending.push(ticket);
+        ticket
+    }
+
+    /// frames simulated since `snapshot` was copied
+    pub fn age(&self, snapshot: &ParticleSnapshot) -> u64 {
+        self.frame - snapshot.frame
+    }
+
+    /// call once per frame after its last pass: copies the particles of `storage` when a
+    /// snapshot is requested and a slot is free, and collects the maps that finished.
+    /// never blocks
+    pub fn end_frame(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        storage: &ParticleStorage,
+        layout: ParticleLayout,
+        capacity: usize,
+    ) {
+        self.frame += 1;
+        if !self.pending.is_empty() {
+            if let Some(slot_idx) = self
+                .ring
+                .iter()
+                .position(|slot| slot.state == SlotState::Free)
+            {
+                self.copy(device, queue, slot_idx, storage, layout, capacity);
+            }
+        }
+        self.poll(device);
+    }
+
+    fn copy(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        slot_idx: usize,
+        storage: &ParticleStorage,
+        layout: ParticleLayout,
+        capacity: usize,
+    ) {
+        let size = layout.packed_size(capacity);
+        let slot = &mut self.ring[slot_idx];
+        if !slot
+            .buffer
+            .as_ref()
+            .is_some_and(|buffer| buffer.size() >= size)
+        {
+            slot.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Particle Readback Buffer"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        let buffer = slot.buffer.as_ref().unwrap();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Particle Readback Encoder"),
+        });
+        storage.copy_to(&mut encoder, buffer, capacity);
+        queue.submit(Some(encoder.finish()));
+
+        slot.capacity = capacity;
+        slot.layout = layout;
+        slot.state = SlotState::InFlight { frame: self.frame };
+        slot.tickets = std::mem::take(&mut self.pending);
+        let sender = self.sender.clone();
+        buffer
+            .slice(..size)
+            .map_async(wgpu::MapMode::Read, move |r| {
+                let _ = sender.send((slot_idx, r.is_ok()));
+            });
+    }
+
+    fn poll(&mut self, device: &wgpu::Device) {
+        device.poll(wgpu::Maintain::Poll);
+        self.collect();
+    }
+
+    /// mark the slots whose map finished. the requests of a failed map are copied again
+    /// at the end of the next frame
+    fn collect(&mut self) {
+        while let Ok((slot_idx, mapped)) = self.receiver.try_recv() {
+            let slot = &mut self.ring[slot_idx];
+            slot.state = match (slot.state, mapped) {
+                (SlotState::InFlight { frame }, true) => SlotState::Ready { frame },
+                _ => {
+                    self.pending.append(&mut slot.tickets);
+                    SlotState::Free
+                }
+            };
+        }
+    }
+
+    /// the snapshot of `ticket` once its map finished, `None` before and after it was
+    /// taken, see `age`. a slot stays mapped until all the tickets copied into it took the
+    /// snapshot, tickets that are never taken end up holding the whole ring
+    pub fn take_snapshot(&mut self, ticket: SnapshotTicket) -> Option<ParticleSnapshot> {
+        self.collect();
+        let slot = self
+            .ring
+            .iter_mut()
+            .find(|slot| slot.tickets.contains(&ticket))?;
+        let SlotState::Ready { frame } = slot.state else {
+            return None;
+        };
+        let buffer = slot.buffer.as_ref().unwrap();
+        let particles = {
+            let mapped = buffer
+                .slice(..slot.layout.packed_size(slot.capacity))
+                .get_mapped_range();
+            slot.layout
+                .unpack(&mapped, slot.capacity)
+                .iter()
+                .map(Particle::from_raw)
+                .collect()
+        };
+        slot.tickets.retain(|&taken| taken != ticket);
+        if slot.tickets.is_empty() {
+            buffer.unmap();
+            slot.state = SlotState::Free;
+        }
+        Some(ParticleSnapshot { frame, particles })
+    }
+}
@@ -0,0 +1,111 @@
+//! Passive scalars carried by the particles, such as dye concentrations. They diffuse
+//! between neighbouring fluid particles and relax towards the values of their sources,
+//! without acting back on the flow. See scalar.h.wgsl
+use cgmath::Vector3;
+
+/// channels per particle, the components of `SphParticle::scalars`
+pub const MAX_SCALARS: usize = 4;
+/// length of the uniform array of sources
+pub const MAX_SCALAR_SOURCES: usize = 8;
+
+/// colours of the channels in the dye colour mode until the scene picks its own
+pub const DEFAULT_SCALAR_COLORS: [[f32; 3]; MAX_SCALARS] = [
+    [0.9, 0.1, 0.1],
+    [1.0, 0.85, 0.1],
+    [0.1, 0.8, 0.2],
+    [0.8, 0.2, 0.9],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScalarChannel {
+    /// m²/s
+    pub diffusivity: f32,
+    /// linear RGB the channel tints the water with at a concentration of 1
+    pub color: [f32; 3],
+}
+
+impl ScalarChannel {
+    pub fn new(diffusivity: f32) -> Self {
+        Self {
+            diffusivity,
+            color: DEFAULT_SCALAR_COLORS[0],
+        }
+    }
+
+    pub fn with_color(mut self, color: [f32; 3]) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+/// box pulling one channel of the fluid inside it towards a value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScalarSource {
+    pub channel: usize,
+    pub lower: Vector3<f32>,
+    pub upper: Vector3<f32>,
+    pub value: f32,
+    /// 1/s, how fast the particles inside approach `value`
+    pub rate: f32,
+}
+
+impl ScalarSource {
+    pub fn new(channel: usize, lower: Vector3<f32>, upper: Vector3<f32>, value: f32) -> Self {
+        Self {
+            channel,
+            lower,
+            upper,
+            value,
+            rate: 5.0,
+        }
+    }
+
+    pub fn with_rate(mut self, rate: f32) -> Self {
+        self.rate = rate;
+        self
+    }
+}
+
+/// scalar.h.wgsl ScalarSource
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ScalarSourceRaw {
+    lower: [f32; 3],
+    channel: u32,
+    upper: [f32; 3],
+    value: f32,
+    rate: f32,
+    _pad: [f32; 3],
+}
+
+/// scalar.h.wgsl ScalarUniforms
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ScalarsRaw {
+    diffusivity: [f32; MAX_SCALARS],
+    count: u32,
+    _pad: [u32; 3],
+    sources: [ScalarSourceRaw; MAX_SCALAR_SOURCES],
+}
+
+impl ScalarsRaw {
+    /// the channels past the scene's don't diffuse
+    pub fn new(channels: &[ScalarChannel], sources: &[ScalarSource]) -> Self {
+        let mut raw = Self::default();
+        for (diffusivity, channel) in raw.diffusivity.iter_mut().zip(channels) {
+            *diffusivity = channel.diffusivity;
+        }
+        for (slot, source) in raw.sources.iter_mut().zip(sources) {
+            *slot = ScalarSourceRaw {
+                lower: source.lower.into(),
+                channel: source.channel as u32,
+                upper: source.upper.into(),
+                value: source.value,
+                rate: source.rate,
+                _pad: [0.0; 3],
+            };
+        }
+        raw.count = sources.len().min(MAX_SCALAR_SOURCES) as u32;
+        raw
+    }
+}
@@ -0,0 +1,397 @@
+//! Scene description: simulation domain, solver parameters and the blocks
+//! of particles spawned at start up
+use cgmath::Vector3;
+
+use super::container::{ContainerAnimation, ContainerWall};
+use super::flow::Inlet;
+use super::fluid_material::FluidMaterial;
+use super::force_field::ForceField;
+use super::obstacle::Obstacle;
+use super::scalar::{ScalarChannel, ScalarSource, MAX_SCALARS};
+use super::utils::calibrated_rest_volume;
+
+/// initial temperature of spawned particles, °C
+pub const AMBIENT_TEMPERATURE: f32 = 20.0;
+
+/// box of particles placed on a lattice at start up
+#[derive(Debug, Clone, Copy)]
+pub struct SpawnBlock {
+    pub lower: Vector3<f32>,
+    pub upper: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+    pub is_fluid: bool,
+    pub material: FluidMaterial,
+    /// °C, boundary blocks hold it for the whole run
+    pub temperature: f32,
+    /// initial passive scalars, see `Scene::scalars`
+    pub scalars: [f32; MAX_SCALARS],
+}
+
+impl SpawnBlock {
+    pub fn fluid(lower: Vector3<f32>, upper: Vector3<f32>) -> Self {
+        Self {
+            lower,
+            upper,
+            velocity: Vector3::new(0.0, 0.0, 0.0),
+            is_fluid: true,
+            material: FluidMaterial::default(),
+            temperature: AMBIENT_TEMPERATURE,
+            scalars: [0.0; MAX_SCALARS],
+        }
+    }
+
+    pub fn with_velocity(mut self, velocity: Vector3<f32>) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn with_material(mut self, material: FluidMaterial) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn with_scalar(mut self, channel: usize, value: f32) -> Self {
+        self.scalars[channel] = value;
+        self
+    }
+}
+
+/// box that pulls the temperature of the fluid inside towards `temperature`
+#[derive(Debug, Clone, Copy)]
+pub struct HeatSource {
+    pub lower: Vector3<f32>,
+    pub upper: Vector3<f32>,
+    /// °C
+    pub temperature: f32,
+    /// relaxation rate, 1/s
+    pub rate: f32,
+}
+
+impl HeatSource {
+    pub fn new(lower: Vector3<f32>, upper: Vector3<f32>, temperature: f32) -> Self {
+        Self {
+            lower,
+            upper,
+            temperature,
+            rate: 5.0,
+        }
+    }
+
+    pub fn with_rate(mut self, rate: f32) -> Self {
+        self.rate = rate;
+        self
+    }
+}
+
+/// which compute shader steps the particles
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SolverMode {
+    /// compute_particle_2d.wgsl, particles are spawned on the z = 0 plane
+    Planar,
+    /// compute_particle_3d.wgsl
+    #[default]
+    Volumetric,
+}
+
+impl SolverMode {
+    pub const ALL: [SolverMode; 2] = [SolverMode::Planar, SolverMode::Volumetric];
+
+    pub fn shader(&self) -> &'static str {
+        match self {
+            SolverMode::Planar => "compute_particle_2d.wgsl",
+            SolverMode::Volumetric => "compute_particle_3d.wgsl",
+        }
+    }
+}
+
+/// how the particles are stored on the GPU, see particle_layout.rs. rendering, diagnostics
+/// and readback bind the same storage as the solver
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParticleLayout {
+    /// particle_aos.h.wgsl, one 64 byte record per particle
+    #[default]
+    Interleaved,
+    /// a buffer per field, every solver pass binds only the fields it uses so neighbour
+    /// loops read only those. with `half_precision` velocity and pressure are packed into
+    /// four f16
+    Split { half_precision: bool },
+}
+
+/// how the solver finds the neighbours of a particle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NeighborSearch {
+    /// neighbors_all.h.wgsl, every neighbour loop goes over all particles
+    #[default]
+    AllPairs,
+    /// neighbors_list.h.wgsl, every substep bins the particles into the grid cells and
+    /// builds a list per particle that the density, force and pressure stages reuse.
+    /// both have a fixed capacity, see `grid::MAX_NUM_NEIGHBORS`, and what doesn't fit
+    /// is counted in `NeighborStats`. particles outside the domain share its border cells
+    CachedList,
+    /// `CachedList` on a hash table of cells (neighbor_grid_hashed.h.wgsl), so particles
+    /// can leave the domain through open container walls. cells sharing a bucket cost
+    /// extra candidates, counted in `NeighborStats::hash_collisions`
+    HashedList { table_size: u32 },
+}
+
+impl NeighborSearch {
+    /// the header the solver shaders include instead of neighbors_all.h.wgsl
+    pub fn header(&self) -> &'static str {
+        match self {
+            NeighborSearch::AllPairs => "neighbors_all.h.wgsl",
+            NeighborSearch::CachedList | NeighborSearch::HashedList { .. } => {
+                "neighbors_list.h.wgsl"
+            }
+        }
+    }
+
+    /// the header neighbors_list.h.wgsl includes to find the cell of a position
+    pub fn grid_header(&self) -> &'static str {
+        match self {
+            NeighborSearch::HashedList { .. } => "neighbor_grid_hashed.h.wgsl",
+            _ => "neighbor_grid_dense.h.wgsl",
+        }
+    }
+
+    /// cells or hash buckets the particles are binned into, none for `AllPairs`
+    pub fn num_buckets(&self, domain_cells: Vector3<u32>) -> u32 {
+        match self {
+            NeighborSearch::AllPairs => 0,
+            NeighborSearch::CachedList => domain_cells.x * domain_cells.y * domain_cells.z,
+            NeighborSearch::HashedList { table_size } => (*table_size).max(1),
+        }
+    }
+}
+
+/// how a solver step advances the particles from the accelerations of the force passes,
+/// see integrator.h.wgsl
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Integrator {
+    /// the velocities take the whole step, then the positions move with the new ones
+    #[default]
+    SymplecticEuler,
+    /// velocity Verlet: the positions move with a velocity half a step behind, the forces
+    /// and the readback see it kicked forward by the last acceleration
+    Leapfrog,
+    /// forces at the start of the step predict a midpoint, the forces there take the
+    /// step. twice the passes, the heat conduction only runs in the second half
+    PredictorCorrector,
+}
+
+impl Integrator {
+    pub const ALL: [Integrator; 3] = [
+        Integrator::SymplecticEuler,
+        Integrator::Leapfrog,
+        Integrator::PredictorCorrector,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Integrator::SymplecticEuler => "Symplectic Euler",
+            Integrator::Leapfrog => "Leapfrog",
+            Integrator::PredictorCorrector => "Predictor-corrector",
+        }
+    }
+}
+
+/// how grid cells are numbered when the particle buffers are reordered, particles in
+/// cells with close ids end up close in memory, see `Grid::cell_id`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParticleOrder {
+    /// row by row along x, then y, then z
+    #[default]
+    Linear,
+    /// Z-order curve of the cell coordinates, the neighbouring cells of every axis stay
+    /// close, not only those along x
+    Morton,
+}
+
+impl ParticleOrder {
+    pub const ALL: [ParticleOrder; 2] = [ParticleOrder::Linear, ParticleOrder::Morton];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParticleOrder::Linear => "Linear",
+            ParticleOrder::Morton => "Morton",
+        }
+    }
+}
+
+/// secondary spray, foam and bubble particles, see compute_pass_diffuse.rs
+#[derive(Debug, Clone, Copy)]
+pub struct DiffuseParams {
+    pub enabled: bool,
+    /// particles spawned per second by a fluid particle at full trapped air potential
+    pub trapped_air: f32,
+    /// particles spawned per second by a fluid particle at full wave crest potential
+    pub wave_crest: f32,
+    /// mean lifetime in seconds
+    pub lifetime: f32,
+    /// size of the ring buffer, the oldest particles are replaced once it is full. fixed
+    /// when the diffuse pass is created, which checks it against the device limits
+    pub max_particles: u32,
+}
+
+impl Default for DiffuseParams {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            trapped_air: 40.0,
+            wave_crest: 40.0,
+            lifetime: 3.0,
+            max_particles: 1 << 16,
+        }
+    }
+}
+
+/// solver parameters, uploaded with the compute uniforms every step
+#[derive(Debug, Clone, Copy)]
+pub struct SimParams {
+    pub time_step: f32,
+    pub integrator: Integrator,
+    /// m/s², any direction
+    pub gravity: Vector3<f32>,
+    /// solver steps per frame, recorded into a single command buffer
+    pub substeps: u32,
+    /// WCSPH pressure constant
+    pub stiffness: f32,
+    pub viscosity: f32,
+    /// adds an extra curl pass and a confinement pass
+    pub vorticity_enabled: bool,
+    pub vorticity: f32,
+    /// adds an extra XSPH pass before advection
+    pub xsph_enabled: bool,
+    pub xsph: f32,
+    /// Jacobi iterations of the implicit material viscosity solve, 0 disables it
+    pub viscosity_iterations: u32,
+    pub diffuse: DiffuseParams,
+    /// the diffuse particles spawn in particle order instead of racing on the ring
+    /// cursor. the solver itself always steps by `time_step` and sums the neighbours in a
+    /// fixed order, so with this repeated runs on the same adapter are bit-reproducible,
+    /// unless a cell of the neighbour grid overflows: which particles it drops depends
+    /// on the order its counter is incremented in
+    pub deterministic: bool,
+    /// cell numbering the particle buffers are sorted by
+    pub particle_order: ParticleOrder,
+    /// frames between two full reorders of the particle buffers by `particle_order`, 0
+    /// keeps the spawn order. each one reads the particles back and uploads them again
+    pub reorder_interval: u32,
+    /// size the particles so the spawn lattice is at the rest density, otherwise each
+    /// one takes 0.8 diameter² and the fluid compresses on the first steps. off by
+    /// default, the stiffness of the existing scenes is tuned to the fixed volume
+    pub calibrate_rest_volume: bool,
+    /// steps run before the start that only move the particles, their velocities are
+    /// zeroed after each one and set back to the spawn velocities at the end. settles
+    /// the lattice against the walls and under gravity
+    pub relaxation_steps: u32,
+}
+
+impl Default for SimParams {
+    fn default() -> Self {
+        Self {
+            time_step: 0.005,
+            integrator: Integrator::default(),
+            gravity: Vector3::new(0.0, -9.8, 0.0),
+            substeps: 1,
+            stiffness: 50.0,
+            viscosity: 0.05,
+            vorticity_enabled: false,
+            vorticity: 0.05,
+            xsph_enabled: false,
+            xsph: 0.1,
+            viscosity_iterations: 4,
+            diffuse: DiffuseParams::default(),
+            deterministic: false,
+            particle_order: ParticleOrder::default(),
+            reorder_interval: 0,
+            calibrate_rest_volume: false,
+            relaxation_steps: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Scene {
+    pub particle_radius: f32,
+    pub support_radius: f32,
+    pub domain_lower: Vector3<f32>,
+    /// domain size in grid cells, each cell is `support_radius` wide
+    pub domain_cells: Vector3<u32>,
+    pub solver: SolverMode,
+    pub layout: ParticleLayout,
+    pub neighbor_search: NeighborSearch,
+    pub params: SimParams,
+    pub blocks: Vec<SpawnBlock>,
+    pub heat_sources: Vec<HeatSource>,
+    /// external forces on top of gravity, at most `MAX_FORCE_FIELDS` are applied
+    pub force_fields: Vec<ForceField>,
+    /// the domain box as a moving container, static by default. with
+    /// `NeighborSearch::CachedList` it has to stay inside the domain, so only a piston
+    /// moves it
+    pub container: ContainerAnimation,
+    /// analytic colliders inside the container
+    pub obstacles: Vec<Obstacle>,
+    /// faces of the domain box feeding particles in, needs a static container
+    pub inlets: Vec<Inlet>,
+    /// faces of the domain box deleting the particles that leave through them
+    pub outlets: Vec<ContainerWall>,
+    /// passive scalars every particle carries, at most `MAX_SCALARS`
+    pub scalars: Vec<ScalarChannel>,
+    pub scalar_sources: Vec<ScalarSource>,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            particle_radius: 0.1,
+            support_radius: 0.4,
+            domain_lower: Vector3::new(0.0, 0.0, 0.0),
+            domain_cells: Vector3::new(25, 25, 25),
+            solver: SolverMode::default(),
+            layout: ParticleLayout::default(),
+            neighbor_search: NeighborSearch::default(),
+            params: SimParams {
+                calibrate_rest_volume: true,
+                ..Default::default()
+            },
+            blocks: vec![
+                SpawnBlock::fluid(Vector3::new(1.0, 2.0, 1.0), Vector3::new(8.0, 5.0, 4.0))
+                    .with_velocity(Vector3::new(2.0, -2.0, 0.0)),
+                SpawnBlock::fluid(Vector3::new(4.0, 4.0, 4.0), Vector3::new(9.0, 9.0, 9.0))
+                    .with_velocity(Vector3::new(-4.0, -2.0, 0.0)),
+            ],
+            heat_sources: vec![],
+            force_fields: vec![],
+            container: ContainerAnimation::default(),
+            obstacles: vec![],
+            inlets: vec![],
+            outlets: vec![],
+            scalars: vec![],
+            scalar_sources: vec![],
+        }
+    }
+}
+
+impl Scene {
+    /// particle volume `m_V` of the solver. with `SimParams::calibrate_rest_volume` the
+    /// spawn lattice starts at the rest density, so the fluid doesn't pop on the first
+    /// step. with a support radius of 4 particle radii the fixed volume puts it at less
+    /// than half of it
+    pub fn rest_volume(&self) -> f32 {
+        let diameter = 2.0 * self.particle_radius;
+        if self.params.calibrate_rest_volume {
+            calibrated_rest_volume(
+                diameter,
+                self.support_radius,
+                self.solver == SolverMode::Planar,
+            )
+        } else {
+            0.8 * diameter * diameter
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use super::particles::Particle;
-use cgmath::Vector3;
+use cgmath::{InnerSpace, Vector3};
 
 pub fn get_particles_3d(
     bottom_left: (f32, f32, f32),
@@ -68,3 +68,76 @@ pub fn get_particles_2d(
 
     particles
 }
+
+/// cubicKernel2D of math.h.wgsl, the density kernel of both solvers
+fn cubic_kernel_2d(r: f32, h: f32) -> f32 {
+    let k = 40.0 / (7.0 * std::f32::consts::PI * h * h);
+    let q = r / h;
+    if q > 1.0 {
+        0.0
+    } else if q <= 0.5 {
+        k * (6.0 * q * q * q - 6.0 * q * q + 1.0)
+    } else {
+        k * 2.0 * (1.0 - q).powi(3)
+    }
+}
+
+/// radial derivative of `cubic_kernel_2d`, the length of cubicGrad2D of math.h.wgsl
+/// with its sign
+fn cubic_kernel_2d_derivative(r: f32, h: f32) -> f32 {
+    let k = 240.0 / (7.0 * std::f32::consts::PI * h * h);
+    let q = r / h;
+    if q > 1.0 {
+        0.0
+    } else if q <= 0.5 {
+        k * q * (3.0 * q - 2.0) / h
+    } else {
+        -k * (1.0 - q).powi(2) / h
+    }
+}
+
+/// offsets of the neighbours of a particle inside the lattice of `get_particles_2d`
+/// (`planar`) or `get_particles_3d`, closer than `support_radius`
+fn lattice_neighbors(
+    diameter: f32,
+    support_radius: f32,
+    planar: bool,
+) -> impl Iterator<Item = Vector3<f32>> {
+    let reach = (support_radius / diameter).ceil() as i32;
+    let z_reach = if planar { 0 } else { reach };
+    (-reach..=reach)
+        .flat_map(move |i| (-reach..=reach).map(move |j| (i, j)))
+        .flat_map(move |(i, j)| (-z_reach..=z_reach).map(move |k| (i, j, k)))
+        .filter(|&ijk| ijk != (0, 0, 0))
+        .map(move |(i, j, k)| Vector3::new(i as f32, j as f32, k as f32) * diameter)
+        .filter(move |offset| offset.magnitude() < support_radius)
+}
+
+/// the particle volume that gives a fluid particle inside the lattice of
+/// `get_particles_2d` (`planar`) or `get_particles_3d` exactly the rest density, see
+/// calc_density in compute_particle_2d.wgsl
+pub fn calibrated_rest_volume(diameter: f32, support_radius: f32, planar: bool) -> f32 {
+    let kernel_sum = lattice_neighbors(diameter, support_radius, planar)
+        .map(|offset| cubic_kernel_2d(offset.magnitude(), support_radius))
+        .sum::<f32>();
+    1.0 / kernel_sum
+}
+
+/// how much the pressure force of calc_pressure_force overestimates the gradient of a
+/// pressure field that changes linearly along one lattice axis, for a particle inside the
+/// lattice with volume `rest_volume`. 1 for a kernel whose gradient is normalised on the
+/// lattice, the cubic spline at 2 particle spacings isn't
+pub fn pressure_gradient_scale(
+    diameter: f32,
+    support_radius: f32,
+    planar: bool,
+    rest_volume: f32,
+) -> f32 {
+    rest_volume
+        * lattice_neighbors(diameter, support_radius, planar)
+            .map(|offset| {
+                let r = offset.magnitude();
+                -cubic_kernel_2d_derivative(r, support_radius) * offset.y * offset.y / r
+            })
+            .sum::<f32>()
+}
@@ -2,8 +2,13 @@ pub(crate) mod bind_group_layout_cache;
 pub(crate) mod compute_pass_copy_depth;
 pub(crate) mod compute_pass_depth_filter;
 pub(crate) mod compute_pass_depth_filter_basic;
+pub(crate) mod compute_pass_diffuse;
+pub(crate) mod compute_pass_flow;
 pub(crate) mod compute_pass_particle;
+pub(crate) mod compute_pass_reduction;
 pub(crate) mod render_pass_depth;
+pub(crate) mod render_pass_diffuse;
+pub(crate) mod render_pass_obstacles;
 pub(crate) mod render_pass_water;
 
 use std::sync::Arc;
@@ -13,8 +18,12 @@ pub(crate) use bind_group_layout_cache::BindGroupLayoutCache;
 use compute_pass_copy_depth::CopyDepthPass;
 use compute_pass_depth_filter::ComputeDepthFilterPass;
 use compute_pass_depth_filter_basic::ComputeDepthFilterBasicPass;
+use compute_pass_diffuse::ComputeDiffusePass;
 use compute_pass_particle::ComputeParticlePass;
+use compute_pass_reduction::ComputeReductionPass;
 use render_pass_depth::RenderDepthPass;
+use render_pass_diffuse::RenderDiffusePass;
+use render_pass_obstacles::RenderObstaclesPass;
 use render_pass_water::RenderQuadPass;
 
 const RENDER_TARGET: i32 = 2;
@@ -33,13 +42,17 @@ const CLEAR_COLOR: wgpu::Color = wgpu::Color {
 
 use crate::{
     camera::{self, Camera},
-    particle_system::ParticleState,
+    particle_system::{scene::Scene, ParticleState},
 };
 
 pub struct Renderer {
     pub render_depth_pass: RenderDepthPass,
     pub render_quad_pass: RenderQuadPass,
+    pub render_diffuse_pass: RenderDiffusePass,
+    pub render_obstacles_pass: RenderObstaclesPass,
     pub compute_particle_pass: ComputeParticlePass,
+    pub compute_reduction_pass: ComputeReductionPass,
+    pub compute_diffuse_pass: ComputeDiffusePass,
     pub copy_depth_pass: CopyDepthPass,
     pub compute_depth_filter_pass: ComputeDepthFilterPass,
     pub compute_depth_filter_basic_pass: ComputeDepthFilterBasicPass,
@@ -52,14 +65,34 @@ impl Renderer {
         camera: &Camera,
         surface_config: &wgpu::SurfaceConfiguration,
         bind_group_layout_cache: &BindGroupLayoutCache,
-    ) -> Self {
-        let render_depth_pass =
-            RenderDepthPass::new(device, camera, surface_config, bind_group_layout_cache).await;
+        scene: &Scene,
+    ) -> anyhow::Result<Self> {
+        let render_depth_pass = RenderDepthPass::new(
+            device,
+            camera,
+            surface_config,
+            bind_group_layout_cache,
+            scene.layout,
+        )
+        .await;
 
         let render_quad_pass =
             RenderQuadPass::new(device, camera, surface_config, bind_group_layout_cache).await;
 
-        let compute_particle_pass = ComputeParticlePass::new(device, bind_group_layout_cache).await;
+        let render_diffuse_pass =
+            RenderDiffusePass::new(device, surface_config, bind_group_layout_cache).await;
+
+        let render_obstacles_pass =
+            RenderObstaclesPass::new(device, surface_config, bind_group_layout_cache).await;
+
+        let compute_particle_pass =
+            ComputeParticlePass::new(device, bind_group_layout_cache, scene).await;
+
+        let compute_reduction_pass =
+            ComputeReductionPass::new(device, bind_group_layout_cache, scene.layout).await;
+
+        let compute_diffuse_pass =
+            ComputeDiffusePass::new(device, bind_group_layout_cache, scene).await?;
 
         let copy_depth_pass =
             CopyDepthPass::new(device, surface_config, bind_group_layout_cache).await;
@@ -71,19 +104,22 @@ impl Renderer {
         let compute_depth_filter_basic_pass =
             ComputeDepthFilterBasicPass::new(device, bind_group_layout_cache).await;
 
-        Self {
+        Ok(Self {
             render_depth_pass,
             render_quad_pass,
+            render_diffuse_pass,
+            render_obstacles_pass,
             compute_particle_pass,
+            compute_reduction_pass,
+            compute_diffuse_pass,
             copy_depth_pass,
             compute_depth_filter_pass,
             compute_depth_filter_basic_pass,
-        }
+        })
     }
 
     pub fn render(
         &mut self,
-        dt: f32,
         particle_state: &mut ParticleState,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -96,7 +132,17 @@ impl Renderer {
             particle_state,
         ));
         self.compute_particle_pass
-            .compute_sph(device, queue, particle_state, dt);
+            .compute_sph(device, queue, particle_state);
+
+        self.compute_reduction_pass
+            .reduce(device, queue, particle_state);
+
+        let diffuse_enabled = particle_state.sim_params.diffuse.enabled;
+        if diffuse_enabled {
+            self.compute_diffuse_pass
+                .compute(device, queue, particle_state);
+        }
+        particle_state.end_frame_readback(device, queue);
 
         self.render_depth_pass
             .render(particle_state, device, queue, view);
@@ -138,8 +184,36 @@ impl Renderer {
         // self.compute_state.compute_filter
 
         // self.render_state.render_final
-        self.render_quad_pass
-            .render(depth_read_bg_1, device, queue, view);
+        self.render_quad_pass.render(
+            depth_read_bg_1,
+            &self.render_depth_pass.particle_attribute_texture_bind_group,
+            device,
+            queue,
+            view,
+        );
+
+        // the particle depth is free once copied, the obstacles and the diffuse particles
+        // are depth tested against it
+        let depth_view = &self.render_depth_pass.particle_depth_texture.view;
+        self.render_obstacles_pass.render(
+            &self.render_depth_pass.camera_bind_group,
+            particle_state,
+            device,
+            queue,
+            view,
+            depth_view,
+        );
+
+        if diffuse_enabled && RENDER_TARGET == RENDER_WATER {
+            self.render_diffuse_pass.render(
+                &self.render_depth_pass.camera_bind_group,
+                &self.compute_diffuse_pass,
+                device,
+                queue,
+                view,
+                depth_view,
+            );
+        }
     }
 
     pub fn resize(
@@ -1,15 +1,31 @@
+use crate::particle_system::particle_layout::{count_layout_entry, split_bindings};
+use crate::particle_system::scene::ParticleLayout;
+
 pub struct BindGroupLayoutCache {
     pub texture_bind_group_layout: wgpu::BindGroupLayout,
     pub particle_depth_texture_bind_group_layout: wgpu::BindGroupLayout,
     pub sampled_depth_texture_read_bind_group_layout: wgpu::BindGroupLayout,
     pub sampled_depth_texture_write_bind_group_layout: wgpu::BindGroupLayout,
     pub particle_thickness_texture_bind_group_layout: wgpu::BindGroupLayout,
+    /// temperature and passive scalars of the front most particle, for the colour modes
+    pub particle_attribute_texture_bind_group_layout: wgpu::BindGroupLayout,
     pub camera_bind_group_layout: wgpu::BindGroupLayout,
     pub render_uniforms_bind_group_layout: wgpu::BindGroupLayout,
     pub compute_uniforms_bind_group_layout: wgpu::BindGroupLayout,
+    pub sph_uniforms_bind_group_layout: wgpu::BindGroupLayout,
+    /// `sph_uniforms_bind_group_layout` plus the buffers of the cached neighbour lists
+    pub sph_uniforms_neighbor_list_bind_group_layout: wgpu::BindGroupLayout,
     pub world_bind_group_layout: wgpu::BindGroupLayout,
     pub particle_render_bind_group_layout: wgpu::BindGroupLayout,
     pub particle_compute_bind_group_layout: wgpu::BindGroupLayout,
+    /// the buffers of the split layouts in full and half precision as the passes outside
+    /// the solver bind them, see `ParticleLayout::view_header`
+    particle_split_render_bind_group_layouts: [wgpu::BindGroupLayout; 2],
+    particle_split_compute_bind_group_layouts: [wgpu::BindGroupLayout; 2],
+    pub reduction_bind_group_layout: wgpu::BindGroupLayout,
+    pub diffuse_bind_group_layout: wgpu::BindGroupLayout,
+    /// one particle buffer of the inlet and outlet compaction, see compute_pass_flow.rs
+    pub flow_bind_group_layout: wgpu::BindGroupLayout,
 }
 
 impl BindGroupLayoutCache {
@@ -84,6 +100,22 @@ impl BindGroupLayoutCache {
                 label: Some("sampled_depth_texture_write_bind_group_layout"),
             });
 
+        let attribute_texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        };
+        let particle_attribute_texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[attribute_texture_entry(0), attribute_texture_entry(1)],
+                label: Some("particle_attribute_texture_bind_group_layout"),
+            });
+
         let particle_thickness_texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
@@ -154,49 +186,357 @@ impl BindGroupLayoutCache {
                 label: Some("uniforms_bind_group_layout"),
             });
 
+        let sph_uniforms_entries = [
+            // a slot per substep, see ComputeParticlePass::compute_sph
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // per particle scratch space of the viscosity solve, vorticity confinement and
+            // integrators, see scratch.h.wgsl
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ];
+        let sph_uniforms_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("SPH Uniforms Bind Group Layout"),
+                entries: &sph_uniforms_entries,
+            });
+
+        // cell counts, cell particles, neighbour lists and overflow counters of
+        // NeighborSearch::CachedList, see neighbors_list.h.wgsl
+        let neighbor_list_entries = (2..6).map(|binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+        let sph_uniforms_neighbor_list_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("SPH Uniforms Neighbor List Bind Group Layout"),
+                entries: &sph_uniforms_entries
+                    .into_iter()
+                    .chain(neighbor_list_entries)
+                    .collect::<Vec<_>>(),
+            });
+
         let world_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    // fluid materials, one per spawn block
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // heat sources
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // gravity and force fields
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // per particle rest shape and stress of the elastic solids
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // their rest shape neighbour lists
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // analytic obstacles, see obstacle.h.wgsl
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // passive scalar diffusivities and sources, see scalar.h.wgsl
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 7,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
                 label: Some("world_bind_group_layout"),
             });
 
         let particle_render_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("Particle Bind Group Layout for Render"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    count_layout_entry(wgpu::ShaderStages::VERTEX),
+                ],
             });
 
         let particle_compute_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("Particle Bind Group Layout for Compute Read"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: false },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    count_layout_entry(wgpu::ShaderStages::COMPUTE),
+                ],
+            });
+
+        let particle_split_bind_group_layouts = |visibility, read_only, label| {
+            [false, true].map(|half_precision| {
+                let entries = split_bindings(half_precision)
+                    .map(|binding| wgpu::BindGroupLayoutEntry {
+                        binding,
+                        visibility,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    })
+                    .chain([count_layout_entry(visibility)])
+                    .collect::<Vec<_>>();
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some(label),
+                    entries: &entries,
+                })
+            })
+        };
+        let particle_split_render_bind_group_layouts = particle_split_bind_group_layouts(
+            wgpu::ShaderStages::VERTEX,
+            true,
+            "Particle Split Bind Group Layout for Render",
+        );
+        let particle_split_compute_bind_group_layouts = particle_split_bind_group_layouts(
+            wgpu::ShaderStages::COMPUTE,
+            false,
+            "Particle Split Bind Group Layout for Compute Read",
+        );
+
+        let reduction_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Reduction Bind Group Layout"),
+                entries: &[
+                    // partial results, one per workgroup
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // final result
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let diffuse_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Diffuse Bind Group Layout"),
+                entries: &[
+                    // diffuse particles
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // spawn cursor into the ring buffer
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // fluid surface normals
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // per particle spawn slots, deterministic mode only
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // fluid grid cell counters
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // fluid grid cell particles
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let flow_storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let flow_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Flow Bind Group Layout"),
+                entries: &[
+                    // the uniforms of the buffer
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // first and second side of the particle buffer
+                    flow_storage_entry(1, true),
+                    flow_storage_entry(2, false),
+                    // the inlet particles
+                    flow_storage_entry(3, true),
+                    // the new index of every particle
+                    flow_storage_entry(4, false),
+                    // the particle count
+                    flow_storage_entry(5, false),
+                ],
             });
 
         Self {
@@ -205,12 +545,40 @@ impl BindGroupLayoutCache {
             sampled_depth_texture_read_bind_group_layout,
             sampled_depth_texture_write_bind_group_layout,
             particle_thickness_texture_bind_group_layout,
+            particle_attribute_texture_bind_group_layout,
             camera_bind_group_layout,
             render_uniforms_bind_group_layout,
             compute_uniforms_bind_group_layout,
+            sph_uniforms_bind_group_layout,
+            sph_uniforms_neighbor_list_bind_group_layout,
             world_bind_group_layout,
             particle_render_bind_group_layout,
             particle_compute_bind_group_layout,
+            particle_split_render_bind_group_layouts,
+            particle_split_compute_bind_group_layouts,
+            reduction_bind_group_layout,
+            diffuse_bind_group_layout,
+            flow_bind_group_layout,
+        }
+    }
+
+    /// the particles of `layout` in the render passes, see `ParticleLayout::view_header`
+    pub fn particle_render_layout(&self, layout: ParticleLayout) -> &wgpu::BindGroupLayout {
+        match layout {
+            ParticleLayout::Interleaved => &self.particle_render_bind_group_layout,
+            ParticleLayout::Split { half_precision } => {
+                &self.particle_split_render_bind_group_layouts[half_precision as usize]
+            }
+        }
+    }
+
+    /// the particles of `layout` in the compute passes outside the solver
+    pub fn particle_compute_layout(&self, layout: ParticleLayout) -> &wgpu::BindGroupLayout {
+        match layout {
+            ParticleLayout::Interleaved => &self.particle_compute_bind_group_layout,
+            ParticleLayout::Split { half_precision } => {
+                &self.particle_split_compute_bind_group_layouts[half_precision as usize]
+            }
         }
     }
 }
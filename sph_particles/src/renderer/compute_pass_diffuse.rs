@@ -0,0 +1,428 @@
+//! Secondary spray, foam and bubble particles (Ihmsen et al. 2012). Fluid particles
+//! spawn diffuse particles from trapped air, wave crests and kinetic energy, the
+//! diffuse particles are advected by the fluid and classified by neighbour count
+use anyhow::anyhow;
+use cgmath::Vector3;
+use wgpu::util::DeviceExt;
+
+use crate::particle_system::grid::MAX_NUM_PARTICLES_PER_CELL;
+use crate::particle_system::scene::{NeighborSearch, Scene};
+use crate::particle_system::{workgroup_grid, ParticleState};
+use crate::resources::load_shader_with_headers;
+
+use super::BindGroupLayoutCache;
+
+/// compute_diffuse.wgsl DiffuseParticle
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DiffuseParticleRaw {
+    position: [f32; 3],
+    lifetime: f32,
+    velocity: [f32; 3],
+    kind: u32,
+}
+
+/// classified every step from the number of fluid neighbours
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffuseKind {
+    /// few neighbours, ballistic
+    Spray,
+    /// on the surface, carried by the fluid and dissolving
+    Foam,
+    /// inside the fluid, rising
+    Bubble,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DiffuseParticle {
+    pub position: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+    /// seconds left
+    pub lifetime: f32,
+    pub kind: DiffuseKind,
+}
+
+impl DiffuseParticle {
+    /// `None` for a free slot of the ring buffer
+    fn from_raw(raw: &DiffuseParticleRaw) -> Option<Self> {
+        if raw.lifetime <= 0.0 {
+            return None;
+        }
+        Some(Self {
+            position: raw.position.into(),
+            velocity: raw.velocity.into(),
+            lifetime: raw.lifetime,
+            kind: match raw.kind {
+                0 => DiffuseKind::Spray,
+                1 => DiffuseKind::Foam,
+                _ => DiffuseKind::Bubble,
+            },
+        })
+    }
+}
+
+/// compute_diffuse.wgsl DiffuseUniforms
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct DiffuseUniforms {
+    time_step: f32,
+    trapped_air: f32,
+    wave_crest: f32,
+    lifetime: f32,
+    seed: u32,
+    deterministic: u32,
+    /// seconds simulated, animates the force fields
+    time: f32,
+    _pad: u32,
+}
+
+/// how the fluid is binned, into the cells of the scene's neighbour search. without one
+/// the cells are hashed like `HashedList`, into a bucket per domain cell, as the fluid
+/// can follow a moving container out of the domain
+fn fluid_grid(scene: &Scene) -> NeighborSearch {
+    match scene.neighbor_search {
+        NeighborSearch::AllPairs => NeighborSearch::HashedList {
+            table_size: NeighborSearch::CachedList.num_buckets(scene.domain_cells),
+        },
+        neighbor_search => neighbor_search,
+    }
+}
+
+/// `len` u32 counters or indices, cleared with `clear_buffer`
+fn create_grid_buffer(device: &wgpu::Device, label: &str, len: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: std::mem::size_of::<u32>() as u64 * len.max(1),
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+pub struct ComputeDiffusePass {
+    bin_fluid_pipeline: wgpu::ComputePipeline,
+    surface_normal_pipeline: wgpu::ComputePipeline,
+    spawn_pipeline: wgpu::ComputePipeline,
+    assign_slots_pipeline: wgpu::ComputePipeline,
+    emit_pipeline: wgpu::ComputePipeline,
+    advect_pipeline: wgpu::ComputePipeline,
+
+    uniforms_buffer: wgpu::Buffer,
+    uniforms_bind_group: wgpu::BindGroup,
+
+    /// slots of the ring buffer, `DiffuseParams::max_particles` of the scene
+    capacity: u32,
+    advect_dispatch_size: (u32, u32, u32),
+    // sized by the particle count, so they are created on first use
+    num_normals: u32,
+    diffuse_buffer: Option<wgpu::Buffer>,
+    normal_buffer: Option<wgpu::Buffer>,
+    spawn_slot_buffer: Option<wgpu::Buffer>,
+    cursor_buffer: wgpu::Buffer,
+    /// the fluid binned into the cells of the scene's neighbour search once per frame,
+    /// a counter and `MAX_NUM_PARTICLES_PER_CELL` slots per cell or hash bucket
+    cell_counts_buffer: wgpu::Buffer,
+    cell_particles_buffer: wgpu::Buffer,
+    diffuse_bind_group: Option<wgpu::BindGroup>,
+
+    step: u32,
+}
+
+impl ComputeDiffusePass {
+    pub async fn new(
+        device: &wgpu::Device,
+        bind_group_layout_cache: &BindGroupLayoutCache,
+        scene: &Scene,
+    ) -> anyhow::Result<Self> {
+        let capacity = scene.params.diffuse.max_particles;
+        let limits = device.limits();
+        let max_workgroups = limits.max_compute_workgroups_per_dimension;
+        let advect_dispatch_size = workgroup_grid(capacity, max_workgroups).ok_or_else(|| {
+            anyhow!(
+                "{capacity} diffuse particle slots are more than the device can dispatch \
+                 ({max_workgroups}x{max_workgroups} workgroups)"
+            )
+        })?;
+        let max_bytes = limits.max_storage_buffer_binding_size as u64;
+        let ring_bytes = std::mem::size_of::<DiffuseParticleRaw>() as u64 * capacity as u64;
+        if ring_bytes > max_bytes {
+            return Err(anyhow!(
+                "{capacity} diffuse particle slots take {ring_bytes} bytes, more than the \
+                 {max_bytes} bytes the device can bind in one storage buffer"
+            ));
+        }
+
+        let fluid_grid = fluid_grid(scene);
+        let num_buckets = fluid_grid.num_buckets(scene.domain_cells) as u64;
+        let cell_bytes =
+            std::mem::size_of::<u32>() as u64 * num_buckets * MAX_NUM_PARTICLES_PER_CELL as u64;
+        if cell_bytes > max_bytes {
+            return Err(anyhow!(
+                "the diffuse particles' fluid grid takes {cell_bytes} bytes, more than the \
+                 {max_bytes} bytes the device can bind in one storage buffer"
+            ));
+        }
+
+        let view_header = scene.layout.view_header(0, false);
+        let shader = device.create_shader_module(
+            load_shader_with_headers(
+                "compute_diffuse.wgsl",
+                &[("neighbor_grid_dense.h.wgsl", fluid_grid.grid_header())],
+                &[("particle_view.h.wgsl", &view_header)],
+            )
+            .await
+            .unwrap(),
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Diffuse Pipeline Layout"),
+            bind_group_layouts: &[
+                bind_group_layout_cache.particle_compute_layout(scene.layout),
+                &bind_group_layout_cache.diffuse_bind_group_layout,
+                &bind_group_layout_cache.compute_uniforms_bind_group_layout,
+                &bind_group_layout_cache.world_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Diffuse Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point,
+            })
+        };
+        let bin_fluid_pipeline = create_pipeline("bin_fluid_main");
+        let surface_normal_pipeline = create_pipeline("surface_normal_main");
+        let spawn_pipeline = create_pipeline("spawn_main");
+        let assign_slots_pipeline = create_pipeline("assign_slots_main");
+        let emit_pipeline = create_pipeline("emit_main");
+        let advect_pipeline = create_pipeline("advect_main");
+
+        let uniforms_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Diffuse Uniforms Buffer"),
+            contents: bytemuck::cast_slice(&[DiffuseUniforms::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let uniforms_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Diffuse Uniforms Bind Group"),
+            layout: &bind_group_layout_cache.compute_uniforms_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniforms_buffer.as_entire_binding(),
+            }],
+        });
+
+        let cursor_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Diffuse Cursor Buffer"),
+            contents: bytemuck::cast_slice(&[0u32]),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let cell_counts_buffer =
+            create_grid_buffer(device, "Diffuse Cell Counts Buffer", num_buckets);
+        let cell_particles_buffer = create_grid_buffer(
+            device,
+            "Diffuse Cell Particles Buffer",
+            num_buckets * MAX_NUM_PARTICLES_PER_CELL as u64,
+        );
+
+        Ok(Self {
+            bin_fluid_pipeline,
+            surface_normal_pipeline,
+            spawn_pipeline,
+            assign_slots_pipeline,
+            emit_pipeline,
+            advect_pipeline,
+            uniforms_buffer,
+            uniforms_bind_group,
+            capacity,
+            advect_dispatch_size,
+            num_normals: 0,
+            diffuse_buffer: None,
+            normal_buffer: None,
+            spawn_slot_buffer: None,
+            cursor_buffer,
+            cell_counts_buffer,
+            cell_particles_buffer,
+            diffuse_bind_group: None,
+            step: 0,
+        })
+    }
+
+    /// number of slots in the ring buffer
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// `None` before the first step
+    pub fn diffuse_buffer(&self) -> Option<&wgpu::Buffer> {
+        self.diffuse_buffer.as_ref()
+    }
+
+    /// create the ring on first use and (re)create the per particle buffers when the
+    /// particle count changes
+    fn ensure_buffers(&mut self, device: &wgpu::Device, num_particles: u32) {
+        if self.diffuse_buffer.is_some() && self.num_normals == num_particles {
+            return;
+        }
+
+        if self.diffuse_buffer.is_none() {
+            self.diffuse_buffer = Some(device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("Diffuse Particle Buffer"),
+                    contents: bytemuck::cast_slice(&vec![
+                        DiffuseParticleRaw::default();
+                        self.capacity.max(1) as usize
+                    ]),
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                },
+            ));
+        }
+
+        if self.normal_buffer.is_none() || self.num_normals != num_particles {
+            self.normal_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Diffuse Normal Buffer"),
+                size: (std::mem::size_of::<[f32; 4]>() * num_particles.max(1) as usize)
+                    as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            }));
+            self.spawn_slot_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Diffuse Spawn Slot Buffer"),
+                size: (std::mem::size_of::<[u32; 2]>() * num_particles.max(1) as usize)
+                    as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            }));
+            self.num_normals = num_particles;
+        }
+
+        self.diffuse_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Diffuse Bind Group"),
+            layout: &self.advect_pipeline.get_bind_group_layout(1),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.diffuse_buffer.as_ref().unwrap().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.cursor_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.normal_buffer.as_ref().unwrap().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.spawn_slot_buffer.as_ref().unwrap().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.cell_counts_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: self.cell_particles_buffer.as_entire_binding(),
+                },
+            ],
+        }));
+    }
+
+    /// spawn from and advect through the latest particle buffer (index 0)
+    pub fn compute(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        particle_state: &ParticleState,
+    ) {
+        let params = particle_state.sim_params;
+        self.ensure_buffers(device, particle_state.capacity as u32);
+
+        self.step = self.step.wrapping_add(1);
+        let uniforms = DiffuseUniforms {
+            // runs once per frame, after all substeps
+            time_step: params.time_step * params.substeps.max(1) as f32,
+            trapped_air: params.diffuse.trapped_air,
+            wave_crest: params.diffuse.wave_crest,
+            lifetime: params.diffuse.lifetime,
+            seed: self.step,
+            deterministic: params.deterministic as u32,
+            time: particle_state.time,
+            _pad: 0,
+        };
+        queue.write_buffer(&self.uniforms_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Diffuse Encoder"),
+        });
+        encoder.clear_buffer(&self.cell_counts_buffer, 0, None);
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Diffuse Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_bind_group(0, &particle_state.particle_compute_bind_group, &[]);
+            compute_pass.set_bind_group(1, self.diffuse_bind_group.as_ref().unwrap(), &[]);
+            compute_pass.set_bind_group(2, &self.uniforms_bind_group, &[]);
+            compute_pass.set_bind_group(3, &particle_state.world_bind_group, &[]);
+
+            // ParticleState::new checked both counts against the device limits
+            let (x, y, z) = particle_state.dispatch_size;
+            compute_pass.set_pipeline(&self.bin_fluid_pipeline);
+            compute_pass.dispatch_workgroups(x, y, z);
+            compute_pass.set_pipeline(&self.surface_normal_pipeline);
+            compute_pass.dispatch_workgroups(x, y, z);
+            compute_pass.set_pipeline(&self.spawn_pipeline);
+            compute_pass.dispatch_workgroups(x, y, z);
+            if params.deterministic {
+                compute_pass.set_pipeline(&self.assign_slots_pipeline);
+                compute_pass.dispatch_workgroups(1, 1, 1);
+                compute_pass.set_pipeline(&self.emit_pipeline);
+                compute_pass.dispatch_workgroups(x, y, z);
+            }
+
+            let (x, y, z) = self.advect_dispatch_size;
+            compute_pass.set_pipeline(&self.advect_pipeline);
+            compute_pass.dispatch_workgroups(x, y, z);
+        }
+
+        queue.submit(Some(encoder.finish()));
+    }
+
+    /// read the live diffuse particles back, blocks until the GPU is done
+    pub async fn read_diffuse_particles(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Vec<DiffuseParticle> {
+        let Some(diffuse_buffer) = &self.diffuse_buffer else {
+            return vec![];
+        };
+
+        let size = diffuse_buffer.size();
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Diffuse Staging Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(diffuse_buffer, 0, &staging_buffer, 0, size);
+        queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = staging_buffer.slice(..);
+        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap());
+        device.poll(wgpu::Maintain::Wait);
+        receiver.receive().await.unwrap().unwrap();
+
+        let mapped = buffer_slice.get_mapped_range();
+        let raw: &[DiffuseParticleRaw] = bytemuck::cast_slice(&mapped);
+        raw.iter().filter_map(DiffuseParticle::from_raw).collect()
+    }
+}
@@ -0,0 +1,238 @@
+//! Deletes the particles that left through the outlets and appends those that entered
+//! through the inlets, on the GPU and in place, see compute_flow.wgsl. The particle count
+//! only ever lives on the GPU, nothing here waits for it
+use crate::particle_system::particles::ParticleRaw;
+use crate::particle_system::{ParticleState, PARTICLE_WORKGROUP_SIZE_X};
+use crate::resources::load_shader;
+
+use super::BindGroupLayoutCache;
+
+/// compute_flow.wgsl FlowUniforms
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct FlowUniforms {
+    lower: [f32; 3],
+    outlets: u32,
+    upper: [f32; 3],
+    num_new: u32,
+    words: u32,
+    inlet_offset: u32,
+    row_stride: u32,
+    _pad: u32,
+}
+
+/// the buffers sized by the particle state, see `ComputeFlowPass::ensure_buffers`
+struct FlowBuffers {
+    /// a slot per particle buffer
+    uniforms_buffer: wgpu::Buffer,
+    /// the inlet particles packed as `ParticleLayout::pack` does, grows with them
+    inlet_buffer: wgpu::Buffer,
+    /// per particle slot
+    ranks_buffer: wgpu::Buffer,
+    /// per particle buffer, the first also ranks the particles
+    bind_groups: Vec<wgpu::BindGroup>,
+}
+
+pub struct ComputeFlowPass {
+    rank_pipeline: wgpu::ComputePipeline,
+    scatter_pipeline: wgpu::ComputePipeline,
+    uniforms_stride: wgpu::BufferAddress,
+    /// created on first use
+    buffers: Option<FlowBuffers>,
+}
+
+impl ComputeFlowPass {
+    pub async fn new(
+        device: &wgpu::Device,
+        bind_group_layout_cache: &BindGroupLayoutCache,
+    ) -> Self {
+        let shader = device.create_shader_module(load_shader("compute_flow.wgsl").await.unwrap());
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Flow Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout_cache.flow_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Flow Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point,
+            })
+        };
+
+        Self {
+            rank_pipeline: pipeline("flow_rank_main"),
+            scatter_pipeline: pipeline("flow_scatter_main"),
+            uniforms_stride: (std::mem::size_of::<FlowUniforms>() as wgpu::BufferAddress)
+                .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64),
+            buffers: None,
+        }
+    }
+
+    /// (re)create the buffers on first use and when the inlet particles outgrow theirs
+    fn ensure_buffers(
+        &mut self,
+        device: &wgpu::Device,
+        particle_state: &ParticleState,
+        inlet_bytes: wgpu::BufferAddress,
+    ) {
+        if self
+            .buffers
+            .as_ref()
+            .is_some_and(|buffers| buffers.inlet_buffer.size() >= inlet_bytes)
+        {
+            return;
+        }
+
+        let particle_buffers = particle_state.storage.buffers();
+        let bind_group_layout = self.rank_pipeline.get_bind_group_layout(0);
+        let (uniforms_buffer, ranks_buffer) = match self.buffers.take() {
+            Some(buffers) => (buffers.uniforms_buffer, buffers.ranks_buffer),
+            None => (
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Flow Uniforms Buffer"),
+                    size: self.uniforms_stride * particle_buffers.len() as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Flow Ranks Buffer"),
+                    size: (std::mem::size_of::<u32>() * particle_state.capacity)
+                        as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                }),
+            ),
+        };
+        // an empty buffer can't be bound
+        let inlet_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Flow Inlet Buffer"),
+            size: inlet_bytes.max(4).next_power_of_two(),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_groups = particle_buffers
+            .iter()
+            .enumerate()
+            .map(|(index, (sides, _))| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Flow Bind Group"),
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                                buffer: &uniforms_buffer,
+                                offset: self.uniforms_stride * index as wgpu::BufferAddress,
+                                size: wgpu::BufferSize::new(
+                                    std::mem::size_of::<FlowUniforms>() as wgpu::BufferAddress
+                                ),
+                            }),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: sides[0].as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: sides[1].as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: inlet_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 4,
+                            resource: ranks_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 5,
+                            resource: particle_state.storage.count_buffer().as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+
+        self.buffers = Some(FlowBuffers {
+            uniforms_buffer,
+            inlet_buffer,
+            ranks_buffer,
+            bind_groups,
+        });
+    }
+
+    /// delete the particles past the outlets and append `new_particles`, as many as fit.
+    /// the live particles keep their order and end up on the first side of the storage
+    pub fn exchange(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        particle_state: &ParticleState,
+        new_particles: &[ParticleRaw],
+    ) {
+        let layout = particle_state.layout;
+        let inlet_bytes = layout.pack(new_particles);
+        self.ensure_buffers(
+            device,
+            particle_state,
+            inlet_bytes.len() as wgpu::BufferAddress,
+        );
+        let buffers = self.buffers.as_ref().unwrap();
+        if !inlet_bytes.is_empty() {
+            queue.write_buffer(&buffers.inlet_buffer, 0, &inlet_bytes);
+        }
+
+        // the runs of `inlet_bytes` follow the order of the storage's buffers
+        let particle_buffers = particle_state.storage.buffers();
+        let (x, y, z) = particle_state.dispatch_size;
+        let word = std::mem::size_of::<u32>();
+        let mut uniforms_bytes = vec![0u8; self.uniforms_stride as usize * particle_buffers.len()];
+        let mut inlet_offset = 0;
+        for ((_, stride), slot) in particle_buffers
+            .iter()
+            .zip(uniforms_bytes.chunks_exact_mut(self.uniforms_stride as usize))
+        {
+            let uniforms = FlowUniforms {
+                lower: particle_state.grid_2d.boundary_lower.into(),
+                outlets: particle_state.flow.outlet_mask(),
+                upper: particle_state.grid_2d.boundary_upper.into(),
+                num_new: new_particles.len() as u32,
+                words: (stride / word) as u32,
+                inlet_offset: inlet_offset as u32,
+                row_stride: x * PARTICLE_WORKGROUP_SIZE_X,
+                _pad: 0,
+            };
+            slot[..std::mem::size_of::<FlowUniforms>()]
+                .copy_from_slice(bytemuck::bytes_of(&uniforms));
+            inlet_offset += stride / word * new_particles.len();
+        }
+        queue.write_buffer(&buffers.uniforms_buffer, 0, &uniforms_bytes);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Flow Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Flow Pass"),
+                timestamp_writes: None,
+            });
+            // the positions come first in every layout
+            compute_pass.set_pipeline(&self.rank_pipeline);
+            compute_pass.set_bind_group(0, &buffers.bind_groups[0], &[]);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+
+            compute_pass.set_pipeline(&self.scatter_pipeline);
+            for bind_group in &buffers.bind_groups {
+                compute_pass.set_bind_group(0, bind_group, &[]);
+                compute_pass.dispatch_workgroups(x, y, z);
+            }
+        }
+        for (sides, _) in &particle_buffers {
+            encoder.copy_buffer_to_buffer(&sides[1], 0, &sides[0], 0, sides[0].size());
+        }
+        queue.submit(Some(encoder.finish()));
+    }
+}
@@ -1,219 +1,907 @@
-use wgpu::util::DeviceExt;
+use crate::interaction::MouseInteractionRaw;
+use crate::particle_system::grid::{
+    NeighborStats, MAX_NUM_PARTICLES_PER_CELL, NEIGHBOR_LIST_STRIDE,
+};
+use crate::particle_system::particle_layout::{solver_pass_fields, SplitPass, SOLVER_PASSES};
+use crate::particle_system::particles::Particle;
+use crate::particle_system::scene::{Integrator, NeighborSearch, ParticleLayout, Scene, SimParams};
+use crate::particle_system::{ParticleState, PARTICLE_WORKGROUP_SIZE_X};
 
-use crate::particle_system::ParticleState;
+use crate::resources::load_shader_with_headers;
 
-use crate::resources::load_shader;
-
-const WORKGROUP_SIZE: (u32, u32, u32) = (512, 256, 1); // total 4194304
+use super::compute_pass_flow::ComputeFlowPass;
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ComputeUniforms {
-    pub dt: f32,
+    pub time_step: f32,
+    pub stiffness: f32,
+    pub viscosity: f32,
+    pub vorticity: f32,
+    pub mouse: MouseInteractionRaw,
+    pub xsph: f32,
+    /// particles per row of the dispatch grid
+    pub row_stride: u32,
+    /// seconds simulated at the start of the substep, animates the force fields
+    pub time: f32,
+    /// integrator.h.wgsl INTEGRATOR_*
+    pub integrator: u32,
+    /// 1 in the relaxation steps, see `ComputeParticlePass::relax`
+    pub relaxing: u32,
+    _pad: [u32; 3],
 }
 
 impl ComputeUniforms {
     pub fn new() -> Self {
-        Self { dt: 0.0 }
+        let params = SimParams::default();
+        Self {
+            time_step: params.time_step,
+            stiffness: params.stiffness,
+            viscosity: params.viscosity,
+            vorticity: params.vorticity,
+            mouse: MouseInteractionRaw::default(),
+            xsph: params.xsph,
+            row_stride: 0,
+            time: 0.0,
+            integrator: params.integrator as u32,
+            relaxing: 0,
+            _pad: [0; 3],
+        }
+    }
+
+    fn set_params(&mut self, params: &SimParams) {
+        self.time_step = params.time_step;
+        self.stiffness = params.stiffness;
+        self.viscosity = params.viscosity;
+        self.vorticity = params.vorticity;
+        self.xsph = params.xsph;
+        self.integrator = params.integrator as u32;
     }
 }
 
 pub struct ComputeParticlePass {
-    pub shader: wgpu::ShaderModule,
-
-    #[allow(dead_code)]
-    pub pipeline_layout: wgpu::PipelineLayout,
-    pub compute_density_pipeline: wgpu::ComputePipeline,
-    pub compute_non_pressure_pipeline: wgpu::ComputePipeline,
-    pub compute_pressure_pipeline: wgpu::ComputePipeline,
-    pub advect_pipeline: wgpu::ComputePipeline,
-    #[allow(dead_code)]
-    pub empty_copy_pipeline: wgpu::ComputePipeline,
-
-    // uniforms data and buffer
+    pub compute_density_pipeline: SolverPipeline,
+    pub compute_non_pressure_pipeline: SolverPipeline,
+    pub compute_pressure_pipeline: SolverPipeline,
+    pub advect_pipeline: SolverPipeline,
+    /// pads the stages to an even number of passes, only with `ParticleLayout::Interleaved`
+    pub empty_copy_pipeline: Option<SolverPipeline>,
+    pub compute_curl_pipeline: SolverPipeline,
+    pub vorticity_confinement_pipeline: SolverPipeline,
+    pub xsph_pipeline: SolverPipeline,
+    pub viscosity_prepare_pipeline: SolverPipeline,
+    pub viscosity_jacobi_pipeline: SolverPipeline,
+    pub heat_conduction_pipeline: SolverPipeline,
+    pub scalar_transport_pipeline: SolverPipeline,
+    pub elastic_stress_pipeline: SolverPipeline,
+    pub elastic_force_pipeline: SolverPipeline,
+    pub integrator_begin_pipeline: SolverPipeline,
+    pub predict_pipeline: SolverPipeline,
+
+    /// none with `NeighborSearch::AllPairs`
+    neighbor_list: Option<NeighborList>,
+
+    // uniforms data and buffer, one slot per substep bound with a dynamic offset
     pub uniforms_data: ComputeUniforms,
     pub uniforms_buffer: wgpu::Buffer,
-    pub uniforms_staging_belt: wgpu::util::StagingBelt,
     pub uniforms_bind_group: wgpu::BindGroup,
+    uniforms_stride: wgpu::BufferAddress,
+    uniforms_slots: u32,
+
+    /// per particle scratch space, see scratch.h.wgsl. sized by the capacity so it is
+    /// resized on first use
+    scratch_buffer: wgpu::Buffer,
+    scratch_buffer_len: usize,
+    /// `scratch_buffer` holds the leapfrog's half step velocities of the particles in
+    /// their current order
+    half_velocities_valid: bool,
+
+    /// frames until the next reorder of the particle buffers
+    frames_until_reorder: u32,
+
+    /// none in a scene without inlets or outlets
+    flow: Option<ComputeFlowPass>,
+}
+
+/// integrator.h.wgsl INTEGRATOR_STATE_VEC4S
+const INTEGRATOR_STATE_VEC4S: usize = 3;
+/// scratch.h.wgsl SCRATCH_INTEGRATOR, the vec4s per particle ahead of the integrator state
+const SCRATCH_INTEGRATOR: usize = 2;
+/// vec4s per particle of the scratch buffer
+const SCRATCH_VEC4S: usize = SCRATCH_INTEGRATOR + INTEGRATOR_STATE_VEC4S;
+
+/// number of map-read buffers the neighbour search statistics rotate through
+const NEIGHBOR_STATS_RING_SIZE: usize = 3;
+
+struct StatsReadbackSlot {
+    buffer: wgpu::Buffer,
+    in_flight: bool,
+}
+
+/// the grid and list passes of `NeighborSearch::CachedList` and `HashedList` and their
+/// buffers, see neighbors_list.h.wgsl
+struct NeighborList {
+    grid_pipeline: SolverPipeline,
+    list_pipeline: SolverPipeline,
+
+    /// a counter and `MAX_NUM_PARTICLES_PER_CELL` slots per cell or hash bucket
+    cell_counts_buffer: wgpu::Buffer,
+    cell_particles_buffer: wgpu::Buffer,
+    // sized by the capacity, so it is resized on first use
+    lists_buffer: wgpu::Buffer,
+    num_particles: usize,
+
+    /// counts up until it is copied into a free slot of the readback ring
+    stats_buffer: wgpu::Buffer,
+    readback_ring: Vec<StatsReadbackSlot>,
+    readback_sender: flume::Sender<(usize, bool)>,
+    readback_receiver: flume::Receiver<(usize, bool)>,
+}
+
+/// `len` u32 indices or counters
+fn create_index_buffer(device: &wgpu::Device, label: &str, len: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: std::mem::size_of::<u32>() as u64 * len.max(1),
+        usage: wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_SRC
+            | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+impl NeighborList {
+    fn new(
+        device: &wgpu::Device,
+        grid_pipeline: SolverPipeline,
+        list_pipeline: SolverPipeline,
+        num_buckets: u32,
+    ) -> Self {
+        let stats_size = std::mem::size_of::<NeighborStats>() as wgpu::BufferAddress;
+        let readback_ring = (0..NEIGHBOR_STATS_RING_SIZE)
+            .map(|_| StatsReadbackSlot {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Neighbor Stats Staging Buffer"),
+                    size: stats_size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                in_flight: false,
+            })
+            .collect();
+        let (readback_sender, readback_receiver) = flume::unbounded();
+
+        Self {
+            grid_pipeline,
+            list_pipeline,
+            cell_counts_buffer: create_index_buffer(
+                device,
+                "Cell Counts Buffer",
+                num_buckets as u64,
+            ),
+            cell_particles_buffer: create_index_buffer(
+                device,
+                "Cell Particles Buffer",
+                num_buckets as u64 * MAX_NUM_PARTICLES_PER_CELL as u64,
+            ),
+            lists_buffer: create_index_buffer(device, "Neighbor Lists Buffer", 0),
+            num_particles: 0,
+            stats_buffer: create_index_buffer(device, "Neighbor Stats Buffer", 4),
+            readback_ring,
+            readback_sender,
+            readback_receiver,
+        }
+    }
+
+    /// (re)create the lists when the particle count changes, true if it did
+    fn ensure_lists_buffer(&mut self, device: &wgpu::Device, num_particles: usize) -> bool {
+        if self.num_particles == num_particles {
+            return false;
+        }
+
+        self.lists_buffer = create_index_buffer(
+            device,
+            "Neighbor Lists Buffer",
+            num_particles as u64 * NEIGHBOR_LIST_STRIDE as u64,
+        );
+        self.num_particles = num_particles;
+        true
+    }
+
+    fn bind_group_entries(&self) -> Vec<wgpu::BindGroupEntry<'_>> {
+        [
+            &self.cell_counts_buffer,
+            &self.cell_particles_buffer,
+            &self.lists_buffer,
+            &self.stats_buffer,
+        ]
+        .into_iter()
+        .zip(2..)
+        .map(|(buffer, binding)| wgpu::BindGroupEntry {
+            binding,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect()
+    }
+
+    /// copy the counters into a free slot of the ring and restart them, nothing when every
+    /// slot is still in flight so the counts carry over to the next readback
+    fn copy_stats(&self, encoder: &mut wgpu::CommandEncoder) -> Option<usize> {
+        let slot_idx = self.readback_ring.iter().position(|slot| !slot.in_flight)?;
+        encoder.copy_buffer_to_buffer(
+            &self.stats_buffer,
+            0,
+            &self.readback_ring[slot_idx].buffer,
+            0,
+            std::mem::size_of::<NeighborStats>() as wgpu::BufferAddress,
+        );
+        encoder.clear_buffer(&self.stats_buffer, 0, None);
+        Some(slot_idx)
+    }
+
+    fn map_stats(&mut self, slot_idx: usize) {
+        let slot = &mut self.readback_ring[slot_idx];
+        slot.in_flight = true;
+        let sender = self.readback_sender.clone();
+        slot.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |r| {
+                let _ = sender.send((slot_idx, r.is_ok()));
+            });
+    }
+}
+
+fn create_uniforms_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniforms_buffer: &wgpu::Buffer,
+    scratch_buffer: &wgpu::Buffer,
+    neighbor_list: Option<&NeighborList>,
+) -> wgpu::BindGroup {
+    let mut entries = vec![
+        wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: uniforms_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(
+                    std::mem::size_of::<ComputeUniforms>() as wgpu::BufferAddress
+                ),
+            }),
+        },
+        wgpu::BindGroupEntry {
+            binding: 1,
+            resource: scratch_buffer.as_entire_binding(),
+        },
+    ];
+    if let Some(neighbor_list) = neighbor_list {
+        entries.extend(neighbor_list.bind_group_entries());
+    }
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Uniforms Bind Group"),
+        layout,
+        entries: &entries,
+    })
+}
+
+/// `slots` uniforms `stride` bytes apart
+fn create_uniforms_buffer(
+    device: &wgpu::Device,
+    stride: wgpu::BufferAddress,
+    slots: u32,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Uniforms Buffer"),
+        size: stride * slots as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// `SCRATCH_VEC4S` vec4 per particle
+fn create_scratch_buffer(device: &wgpu::Device, num_particles: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Scratch Buffer"),
+        size: (std::mem::size_of::<[f32; 4]>() * SCRATCH_VEC4S * num_particles.max(1))
+            as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// a pass of the solver
+pub struct SolverPipeline {
+    pub pipeline: wgpu::ComputePipeline,
+    /// the shader's entry point, see `SOLVER_PASSES`
+    pub entry_point: &'static str,
+    /// the particle buffers it binds, `None` with `ParticleLayout::Interleaved`
+    split: Option<SplitPass>,
+}
+
+/// builds the solver's pipelines. the interleaved layout shares one shader module and
+/// pipeline layout, the split layouts get a module with the storage header of the pass's
+/// fields and their own bind group layouts for every pass
+struct SolverShader<'a> {
+    device: &'a wgpu::Device,
+    scene: &'a Scene,
+    uniforms_bind_group_layout: &'a wgpu::BindGroupLayout,
+    world_bind_group_layout: &'a wgpu::BindGroupLayout,
+    /// with `ParticleLayout::Interleaved`
+    shared: Option<(wgpu::ShaderModule, wgpu::PipelineLayout)>,
+}
+
+impl<'a> SolverShader<'a> {
+    async fn new(
+        device: &'a wgpu::Device,
+        bind_group_layout_cache: &'a super::bind_group_layout_cache::BindGroupLayoutCache,
+        scene: &'a Scene,
+    ) -> SolverShader<'a> {
+        let uniforms_bind_group_layout = match scene.neighbor_search {
+            NeighborSearch::AllPairs => &bind_group_layout_cache.sph_uniforms_bind_group_layout,
+            NeighborSearch::CachedList | NeighborSearch::HashedList { .. } => {
+                &bind_group_layout_cache.sph_uniforms_neighbor_list_bind_group_layout
+            }
+        };
+        let mut solver_shader = Self {
+            device,
+            scene,
+            uniforms_bind_group_layout,
+            world_bind_group_layout: &bind_group_layout_cache.world_bind_group_layout,
+            shared: None,
+        };
+        if scene.layout == ParticleLayout::Interleaved {
+            let shader = solver_shader.load(&[]).await;
+            let particle_bind_group_layout =
+                &bind_group_layout_cache.particle_compute_bind_group_layout;
+            let pipeline_layout = solver_shader
+                .pipeline_layout(particle_bind_group_layout, particle_bind_group_layout);
+            solver_shader.shared = Some((shader, pipeline_layout));
+        }
+        solver_shader
+    }
+
+    async fn load(&self, headers: &[(&str, &str)]) -> wgpu::ShaderModule {
+        let neighbor_search = self.scene.neighbor_search;
+        self.device.create_shader_module(
+            load_shader_with_headers(
+                self.scene.solver.shader(),
+                &[
+                    ("neighbors_all.h.wgsl", neighbor_search.header()),
+                    ("neighbor_grid_dense.h.wgsl", neighbor_search.grid_header()),
+                ],
+                headers,
+            )
+            .await
+            .unwrap(),
+        )
+    }
+
+    fn pipeline_layout(
+        &self,
+        particles_in: &wgpu::BindGroupLayout,
+        particles_out: &wgpu::BindGroupLayout,
+    ) -> wgpu::PipelineLayout {
+        self.device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Compute Pipeline Layout"),
+                bind_group_layouts: &[
+                    particles_in,
+                    particles_out,
+                    self.uniforms_bind_group_layout,
+                    self.world_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            })
+    }
+
+    async fn pipeline(&self, entry_point: &'static str) -> SolverPipeline {
+        let create = |module, layout| {
+            self.device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some("Compute Pipeline"),
+                    layout: Some(layout),
+                    module,
+                    entry_point,
+                })
+        };
+        if let Some((shader, pipeline_layout)) = &self.shared {
+            return SolverPipeline {
+                pipeline: create(shader, pipeline_layout),
+                entry_point,
+                split: None,
+            };
+        }
+
+        let ParticleLayout::Split { half_precision } = self.scene.layout else {
+            unreachable!("the interleaved layout shares its shader")
+        };
+        let fields = solver_pass_fields(entry_point);
+        let header = self.scene.layout.pass_header(entry_point, fields).unwrap();
+        let shader = self.load(&[("particle_aos.h.wgsl", &header)]).await;
+        let key = SOLVER_PASSES
+            .iter()
+            .position(|pass| *pass == entry_point)
+            .unwrap();
+        let split = SplitPass::new(self.device, key, half_precision, fields);
+        let [particles_in, particles_out] = &split.bind_group_layouts;
+        let pipeline_layout = self.pipeline_layout(particles_in, particles_out);
+        SolverPipeline {
+            pipeline: create(&shader, &pipeline_layout),
+            entry_point,
+            split: Some(split),
+        }
+    }
 }
 
 impl ComputeParticlePass {
     pub async fn new(
         device: &wgpu::Device,
         bind_group_layout_cache: &super::bind_group_layout_cache::BindGroupLayoutCache,
+        scene: &Scene,
     ) -> Self {
-        let shader =
-            // device.create_shader_module(load_shader("compute_particle_2d.wgsl").await.unwrap());
-            device.create_shader_module(load_shader("compute_particle_3d.wgsl").await.unwrap());
+        let solver_shader = SolverShader::new(device, bind_group_layout_cache, scene).await;
 
         let uniforms_data = ComputeUniforms::new();
 
-        let uniforms_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Uniforms Buffer"),
-            contents: bytemuck::cast_slice(&[uniforms_data]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let uniforms_staging_belt = wgpu::util::StagingBelt::new(0x1000);
+        let uniforms_stride = (std::mem::size_of::<ComputeUniforms>() as wgpu::BufferAddress)
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+        let uniforms_buffer = create_uniforms_buffer(device, uniforms_stride, 1);
 
-        let uniforms_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Uniforms Bind Group"),
-            layout: &bind_group_layout_cache.compute_uniforms_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: uniforms_buffer.as_entire_binding(),
-            }],
-        });
+        let scratch_buffer = create_scratch_buffer(device, 0);
 
         //---------------------------------------------------------------------
         // Pipeline Setup
 
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Compute Pipeline Layout"),
-            bind_group_layouts: &[
-                &bind_group_layout_cache.particle_compute_bind_group_layout,
-                &bind_group_layout_cache.particle_compute_bind_group_layout,
-                &bind_group_layout_cache.compute_uniforms_bind_group_layout,
-                &bind_group_layout_cache.world_bind_group_layout,
-            ],
-            push_constant_ranges: &[],
-        });
-
         // shader entries:
-        let compute_density_pipeline =
-            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("Compute Pipeline"),
-                layout: Some(&pipeline_layout),
-                module: &shader,
-                entry_point: "compute_density_main",
-            });
-
+        let compute_density_pipeline = solver_shader.pipeline("compute_density_main").await;
         let compute_non_pressure_pipeline =
-            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("Compute Pipeline"),
-                layout: Some(&pipeline_layout),
-                module: &shader,
-                entry_point: "compute_non_pressure_main",
-            });
+            solver_shader.pipeline("compute_non_pressure_main").await;
+        let compute_pressure_pipeline = solver_shader.pipeline("compute_pressure_main").await;
+        let advect_pipeline = solver_shader.pipeline("advect_main").await;
+        let empty_copy_pipeline = match scene.layout {
+            ParticleLayout::Interleaved => Some(solver_shader.pipeline("empty_copy_main").await),
+            ParticleLayout::Split { .. } => None,
+        };
+        let compute_curl_pipeline = solver_shader.pipeline("compute_curl_main").await;
+        let vorticity_confinement_pipeline =
+            solver_shader.pipeline("vorticity_confinement_main").await;
+        let xsph_pipeline = solver_shader.pipeline("xsph_main").await;
+        let viscosity_prepare_pipeline = solver_shader.pipeline("viscosity_prepare_main").await;
+        let viscosity_jacobi_pipeline = solver_shader.pipeline("viscosity_jacobi_main").await;
+        let elastic_stress_pipeline = solver_shader.pipeline("elastic_stress_main").await;
+        let elastic_force_pipeline = solver_shader.pipeline("elastic_force_main").await;
+        let heat_conduction_pipeline = solver_shader.pipeline("heat_conduction_main").await;
+        let scalar_transport_pipeline = solver_shader.pipeline("scalar_transport_main").await;
+        let integrator_begin_pipeline = solver_shader.pipeline("integrator_begin_main").await;
+        let predict_pipeline = solver_shader.pipeline("predict_main").await;
 
-        let compute_pressure_pipeline =
-            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("Compute Pipeline"),
-                layout: Some(&pipeline_layout),
-                module: &shader,
-                entry_point: "compute_pressure_main",
-            });
-
-        let advect_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Compute Pipeline"),
-            layout: Some(&pipeline_layout),
-            module: &shader,
-            entry_point: "advect_main",
-        });
-
-        let empty_copy_pipeline =
-            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("Compute Pipeline"),
-                layout: Some(&pipeline_layout),
-                module: &shader,
-                entry_point: "empty_copy_main",
-            });
+        let neighbor_list = match scene.neighbor_search {
+            NeighborSearch::AllPairs => None,
+            NeighborSearch::CachedList | NeighborSearch::HashedList { .. } => {
+                Some(NeighborList::new(
+                    device,
+                    solver_shader.pipeline("neighbor_grid_main").await,
+                    solver_shader.pipeline("neighbor_list_main").await,
+                    scene.neighbor_search.num_buckets(scene.domain_cells),
+                ))
+            }
+        };
+        let flow = if scene.inlets.is_empty() && scene.outlets.is_empty() {
+            None
+        } else {
+            Some(ComputeFlowPass::new(device, bind_group_layout_cache).await)
+        };
+        let uniforms_bind_group = create_uniforms_bind_group(
+            device,
+            solver_shader.uniforms_bind_group_layout,
+            &uniforms_buffer,
+            &scratch_buffer,
+            neighbor_list.as_ref(),
+        );
 
         Self {
-            shader,
-            pipeline_layout,
             compute_density_pipeline,
             compute_non_pressure_pipeline,
             compute_pressure_pipeline,
             advect_pipeline,
             empty_copy_pipeline,
+            compute_curl_pipeline,
+            vorticity_confinement_pipeline,
+            xsph_pipeline,
+            viscosity_prepare_pipeline,
+            viscosity_jacobi_pipeline,
+            heat_conduction_pipeline,
+            scalar_transport_pipeline,
+            elastic_stress_pipeline,
+            elastic_force_pipeline,
+            integrator_begin_pipeline,
+            predict_pipeline,
+            neighbor_list,
             uniforms_data,
             uniforms_buffer,
-            uniforms_staging_belt,
             uniforms_bind_group,
+            uniforms_stride,
+            uniforms_slots: 1,
+            scratch_buffer,
+            scratch_buffer_len: 0,
+            half_velocities_valid: false,
+            frames_until_reorder: 0,
+            flow,
+        }
+    }
+
+    /// (re)create the scratch buffers when the particle count changes
+    fn ensure_scratch_buffers(&mut self, device: &wgpu::Device, num_particles: usize) {
+        if self.scratch_buffer_len == num_particles {
+            return;
+        }
+
+        self.scratch_buffer = create_scratch_buffer(device, num_particles);
+        self.scratch_buffer_len = num_particles;
+        self.half_velocities_valid = false;
+        self.recreate_uniforms_bind_group(device);
+    }
+
+    /// grow the uniforms buffer to a slot per substep
+    fn ensure_uniforms_slots(&mut self, device: &wgpu::Device, substeps: u32) {
+        if self.uniforms_slots >= substeps {
+            return;
         }
+
+        self.uniforms_buffer = create_uniforms_buffer(device, self.uniforms_stride, substeps);
+        self.uniforms_slots = substeps;
+        self.recreate_uniforms_bind_group(device);
+    }
+
+    fn recreate_uniforms_bind_group(&mut self, device: &wgpu::Device) {
+        self.uniforms_bind_group = create_uniforms_bind_group(
+            device,
+            &self
+                .compute_density_pipeline
+                .pipeline
+                .get_bind_group_layout(2),
+            &self.uniforms_buffer,
+            &self.scratch_buffer,
+            self.neighbor_list.as_ref(),
+        );
+    }
+
+    /// mouse force applied from the next step on
+    pub fn set_mouse_interaction(&mut self, mouse: MouseInteractionRaw) {
+        self.uniforms_data.mouse = mouse;
     }
 
+    /// reorder the particle buffers every `SimParams::reorder_interval` frames, on the
+    /// first one too. call once per frame before `compute_sph`
     pub async fn sort_particle_data(
-        &self,
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        particle_state: &mut ParticleState,
+    ) {
+        let params = particle_state.sim_params;
+        if params.reorder_interval == 0 {
+            return;
+        }
+        if self.frames_until_reorder == 0 {
+            let reordered = particle_state
+                .reorder_particles(device, queue, params.particle_order)
+                .await;
+            self.half_velocities_valid &= !reordered;
+            self.frames_until_reorder = params.reorder_interval;
+        }
+        self.frames_until_reorder = self
+            .frames_until_reorder
+            .min(params.reorder_interval)
+            .saturating_sub(1);
+    }
+
+    /// delete the particles that left through the outlets and add those that entered
+    /// through the inlets, see `ParticleState::take_flow_particles`. call once per frame
+    /// before `compute_sph`
+    pub fn exchange_flow_particles(
+        &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         particle_state: &mut ParticleState,
     ) {
+        let Some(flow) = self.flow.as_mut() else {
+            return;
+        };
+        if let Some(new_particles) = particle_state.take_flow_particles() {
+            flow.exchange(device, queue, particle_state, &new_particles);
+            self.half_velocities_valid = false;
+        }
+    }
+
+    /// `SimParams::relaxation_steps` steps that zero the velocities after moving the
+    /// particles, with the symplectic Euler and no heat or scalar transport. then the
+    /// particles get their spawn velocities back. call once before the first frame, the
+    /// clock stays at zero
+    pub async fn relax(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        particle_state: &mut ParticleState,
+    ) {
+        let params = particle_state.sim_params;
+        if params.relaxation_steps == 0 {
+            return;
+        }
+
+        // the steps keep the particle order, the CPU copy still holds the spawn state
+        let velocities = particle_state
+            .particle_data
+            .iter()
+            .map(|raw| Particle::from_raw(raw).velocity)
+            .collect::<Vec<_>>();
+        particle_state.sim_params = SimParams {
+            integrator: Integrator::SymplecticEuler,
+            substeps: 1,
+            deterministic: true,
+            ..params
+        };
+        self.uniforms_data.relaxing = 1;
+        for _ in 0..params.relaxation_steps {
+            self.compute_sph(device, queue, particle_state);
+        }
+        self.uniforms_data.relaxing = 0;
+        particle_state.sim_params = params;
+        particle_state.time = 0.0;
+        particle_state.steps = 0;
+        self.half_velocities_valid = false;
+
         particle_state
-            .dump_particle_data_from_gpu(0, device, queue)
+            .dump_particle_data_from_gpu(device, queue)
             .await;
-        // sort particle data
-        particle_state.particle_data.sort_by(|a, b| {
-            let a = a.cell_id;
-            let b = b.cell_id;
-            a.cmp(&b)
-        });
-
+        for (raw, velocity) in particle_state.particle_data.iter_mut().zip(velocities) {
+            *raw = Particle {
+                velocity,
+                ..Particle::from_raw(raw)
+            }
+            .to_raw();
+        }
         particle_state.upload_particle_data_to_gpu(queue);
     }
 
+    /// `SimParams::substeps` solver steps, recorded into one command buffer with a single
+    /// submit. the result is on the first side of the particle storage
     pub fn compute_sph(
         &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         particle_state: &mut ParticleState,
-        dt: f32,
     ) {
-        let (mut src_bind_group, mut dst_bind_group) = (
-            &particle_state.particle_compute_bind_group_0,
-            &particle_state.particle_compute_bind_group_1,
-        );
+        let params = particle_state.sim_params;
+        let substeps = params.substeps.max(1);
+        let material_viscosity =
+            particle_state.has_material_viscosity && params.viscosity_iterations > 0;
+        if params.vorticity_enabled
+            || material_viscosity
+            || params.integrator != Integrator::SymplecticEuler
+        {
+            self.ensure_scratch_buffers(device, particle_state.capacity);
+        }
+        self.ensure_uniforms_slots(device, substeps);
+        let lists_resized = self.neighbor_list.as_mut().is_some_and(|neighbor_list| {
+            neighbor_list.ensure_lists_buffer(device, particle_state.capacity)
+        });
+        if lists_resized {
+            self.recreate_uniforms_bind_group(device);
+        }
+        let animated_container = particle_state.stage_container(device, queue, substeps);
+        let moving_obstacles = particle_state.stage_obstacles(device, queue, substeps);
+        particle_state.upload_force_fields(queue);
 
-        // add empty copy pipeline if total size is odd
-        let pipeline_list = [
-            &self.compute_density_pipeline,
-            &self.compute_non_pressure_pipeline,
-            &self.compute_pressure_pipeline,
-            &self.advect_pipeline,
-        ];
-
-        for &pipeline in pipeline_list.iter() {
-            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Compute Encoder"),
-            });
+        self.uniforms_data.set_params(&params);
+        self.uniforms_data.row_stride = particle_state.dispatch_size.0 * PARTICLE_WORKGROUP_SIZE_X;
+        let mut uniforms_bytes = vec![0u8; (self.uniforms_stride * substeps as u64) as usize];
+        self.uniforms_data.time = particle_state.time;
+        for slot in uniforms_bytes.chunks_exact_mut(self.uniforms_stride as usize) {
+            slot[..std::mem::size_of::<ComputeUniforms>()]
+                .copy_from_slice(bytemuck::bytes_of(&self.uniforms_data));
+            self.uniforms_data.time += params.time_step;
+        }
+        queue.write_buffer(&self.uniforms_buffer, 0, &uniforms_bytes);
 
-            // update uniform buffer before dispatching compute
-            self.uniforms_data.dt = dt;
-            self.uniforms_staging_belt
-                .write_buffer(
-                    &mut encoder,
-                    &self.uniforms_buffer,
-                    0,
-                    wgpu::BufferSize::new(
-                        std::mem::size_of::<ComputeUniforms>() as wgpu::BufferAddress
-                    )
-                    .unwrap(),
-                    device,
-                )
-                .copy_from_slice(bytemuck::cast_slice(&[self.uniforms_data]));
-            self.uniforms_staging_belt.finish();
+        let stages = self.substep_stages(particle_state);
 
-            {
-                let mut compute_pass =
-                    encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-                compute_pass.set_pipeline(pipeline);
-
-                use crate::particle_system::ComputeParticle;
-                compute_pass.compute_particle(
-                    WORKGROUP_SIZE,
-                    src_bind_group,
-                    dst_bind_group,
-                    &self.uniforms_bind_group,
-                    &particle_state.world_bind_group,
-                );
+        // the split layouts bind the sides each pass finds its buffers on
+        let split_passes = (0..substeps)
+            .flat_map(|_| stages.iter().flatten())
+            .filter_map(|pipeline| pipeline.split.as_ref())
+            .collect::<Vec<_>>();
+        let dispatches = particle_state.storage.prepare_solver(device, &split_passes);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Compute Encoder"),
+        });
+        // the leapfrog restarts from the velocities when it has no half step ones
+        let leapfrog = params.integrator == Integrator::Leapfrog;
+        if leapfrog && !self.half_velocities_valid {
+            let integrator_state_offset =
+                (std::mem::size_of::<[f32; 4]>() * SCRATCH_INTEGRATOR * self.scratch_buffer_len)
+                    as wgpu::BufferAddress;
+            encoder.clear_buffer(&self.scratch_buffer, integrator_state_offset, None);
+        }
+
+        let mut dispatch = 0;
+        for substep in 0..substeps {
+            // the container and obstacle poses can't change inside a compute pass
+            if animated_container {
+                particle_state.copy_container(&mut encoder, substep);
+            }
+            if moving_obstacles {
+                particle_state.copy_obstacles(&mut encoder, substep);
+            }
+            let uniforms_offset = (substep as wgpu::BufferAddress * self.uniforms_stride) as u32;
+            for pipeline_list in &stages {
+                if let Some(neighbor_list) = &self.neighbor_list {
+                    encoder.clear_buffer(&neighbor_list.cell_counts_buffer, 0, None);
+                }
+
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("SPH Substep"),
+                    timestamp_writes: None,
+                });
+                for &pipeline in pipeline_list.iter() {
+                    compute_pass.set_pipeline(&pipeline.pipeline);
+
+                    let [src_bind_group, dst_bind_group] = particle_state
+                        .storage
+                        .solver_bind_groups(&dispatches, dispatch);
+                    use crate::particle_system::ComputeParticle;
+                    compute_pass.compute_particle(
+                        particle_state.dispatch_size,
+                        src_bind_group,
+                        dst_bind_group,
+                        &self.uniforms_bind_group,
+                        uniforms_offset,
+                        &particle_state.world_bind_group,
+                    );
+                    dispatch += 1;
+                }
             }
-            queue.submit(Some(encoder.finish()));
+        }
+        self.half_velocities_valid = leapfrog;
+
+        particle_state
+            .storage
+            .finish_solver(&mut encoder, &dispatches);
+        let stats_slot = self
+            .neighbor_list
+            .as_ref()
+            .and_then(|neighbor_list| neighbor_list.copy_stats(&mut encoder));
+        queue.submit(Some(encoder.finish()));
+        if let (Some(neighbor_list), Some(slot_idx)) = (&mut self.neighbor_list, stats_slot) {
+            neighbor_list.map_stats(slot_idx);
+        }
 
-            self.uniforms_staging_belt.recall();
+        for _ in 0..substeps {
+            particle_state.time += params.time_step;
+        }
+        particle_state.steps += substeps as u64;
+    }
 
-            // swap buffers
-            (src_bind_group, dst_bind_group) = (dst_bind_group, src_bind_group);
+    /// the entry points `compute_sph` dispatches with the current parameters, in order
+    pub fn solver_passes(&self, particle_state: &ParticleState) -> Vec<&'static str> {
+        let substep = self
+            .substep_stages(particle_state)
+            .into_iter()
+            .flatten()
+            .map(|pipeline| pipeline.entry_point)
+            .collect::<Vec<_>>();
+        substep.repeat(particle_state.sim_params.substeps.max(1) as usize)
+    }
+
+    /// the stages of a substep, a compute pass each. the predictor-corrector moves the
+    /// particles half way in the first one
+    fn substep_stages(&self, particle_state: &ParticleState) -> Vec<Vec<&SolverPipeline>> {
+        match particle_state.sim_params.integrator {
+            Integrator::SymplecticEuler => {
+                vec![self.stage_pipelines(particle_state, None, true, &self.advect_pipeline)]
+            }
+            Integrator::Leapfrog => vec![self.stage_pipelines(
+                particle_state,
+                Some(&self.integrator_begin_pipeline),
+                true,
+                &self.advect_pipeline,
+            )],
+            Integrator::PredictorCorrector => vec![
+                self.stage_pipelines(
+                    particle_state,
+                    Some(&self.integrator_begin_pipeline),
+                    false,
+                    &self.predict_pipeline,
+                ),
+                self.stage_pipelines(particle_state, None, true, &self.advect_pipeline),
+            ],
+        }
+    }
+
+    /// the passes of one stage of a substep: the neighbour list, `begin`, the forces and
+    /// `end`. the forces add to the velocities, positions only change in `end` so one
+    /// list serves the whole stage. `heat` adds the heat conduction and the scalar
+    /// transport outside the relaxation, they must only run once per substep
+    fn stage_pipelines<'a>(
+        &'a self,
+        particle_state: &ParticleState,
+        begin: Option<&'a SolverPipeline>,
+        heat: bool,
+        end: &'a SolverPipeline,
+    ) -> Vec<&'a SolverPipeline> {
+        let params = particle_state.sim_params;
+        let material_viscosity =
+            particle_state.has_material_viscosity && params.viscosity_iterations > 0;
+
+        let heat = heat && self.uniforms_data.relaxing == 0;
+
+        let mut pipeline_list = vec![];
+        if let Some(neighbor_list) = &self.neighbor_list {
+            pipeline_list.push(&neighbor_list.grid_pipeline);
+            pipeline_list.push(&neighbor_list.list_pipeline);
+        }
+        pipeline_list.extend(begin);
+        pipeline_list.push(&self.compute_density_pipeline);
+        pipeline_list.push(&self.compute_non_pressure_pipeline);
+        // before the viscosity solve so it sees the new temperatures
+        if heat && particle_state.has_heat_transfer {
+            pipeline_list.push(&self.heat_conduction_pipeline);
+        }
+        if heat && particle_state.has_scalars {
+            pipeline_list.push(&self.scalar_transport_pipeline);
+        }
+        if material_viscosity {
+            pipeline_list.push(&self.viscosity_prepare_pipeline);
+            for _ in 0..params.viscosity_iterations {
+                pipeline_list.push(&self.viscosity_jacobi_pipeline);
+            }
+        }
+        if params.vorticity_enabled {
+            pipeline_list.push(&self.compute_curl_pipeline);
+            pipeline_list.push(&self.vorticity_confinement_pipeline);
+        }
+        pipeline_list.push(&self.compute_pressure_pipeline);
+        if particle_state.has_elastic {
+            pipeline_list.push(&self.elastic_stress_pipeline);
+            pipeline_list.push(&self.elastic_force_pipeline);
+        }
+        if params.xsph_enabled {
+            pipeline_list.push(&self.xsph_pipeline);
+        }
+        pipeline_list.push(end);
+
+        // add empty copy pipeline if total size is odd, every stage of the interleaved
+        // layout starts and ends on the first buffer
+        if let Some(empty_copy_pipeline) = &self.empty_copy_pipeline {
+            if pipeline_list.len() % 2 == 1 {
+                pipeline_list.push(empty_copy_pipeline);
+            }
+        }
+        pipeline_list
+    }
+
+    /// statistics of the cached neighbour search in the steps read back since the last
+    /// call, `None` when no readback finished or with `NeighborSearch::AllPairs`. never
+    /// blocks
+    pub fn poll_neighbor_stats(&mut self, device: &wgpu::Device) -> Option<NeighborStats> {
+        let neighbor_list = self.neighbor_list.as_mut()?;
+        device.poll(wgpu::Maintain::Poll);
+
+        let mut stats = None;
+        while let Ok((slot_idx, mapped)) = neighbor_list.readback_receiver.try_recv() {
+            let slot = &mut neighbor_list.readback_ring[slot_idx];
+            slot.in_flight = false;
+            if !mapped {
+                continue;
+            }
+            {
+                let mapped = slot.buffer.slice(..).get_mapped_range();
+                *stats.get_or_insert_with(NeighborStats::default) +=
+                    *bytemuck::from_bytes::<NeighborStats>(&mapped);
+            }
+            slot.buffer.unmap();
         }
+        stats
     }
 }
@@ -0,0 +1,275 @@
+//! This module reduces the particle buffer into a handful of simulation
+//! diagnostics on the GPU, so they can be read back without copying every particle
+use wgpu::util::DeviceExt;
+
+use crate::particle_system::scene::ParticleLayout;
+use crate::particle_system::ParticleState;
+use crate::resources::load_shader_with_headers;
+
+use super::BindGroupLayoutCache;
+
+
+/// number of map-read buffers the results rotate through
+pub const DIAGNOSTICS_RING_SIZE: usize = 3;
+
+/// compute_reduction.wgsl Diagnostics
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SimDiagnosticsRaw {
+    momentum: [f32; 3],
+    kinetic_energy: f32,
+    potential_energy: f32,
+    max_velocity: f32,
+    density_error_sum: f32,
+    max_density_error: f32,
+    fluid_count: f32,
+    _pad: [f32; 3],
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SimDiagnostics {
+    /// solver steps run when the data was reduced, counting every substep, see
+    /// `ParticleState::steps`
+    pub step: u64,
+    pub kinetic_energy: f32,
+    pub potential_energy: f32,
+    pub momentum: [f32; 3],
+    pub max_velocity: f32,
+    /// relative to rest density
+    pub mean_density_error: f32,
+    pub max_density_error: f32,
+}
+
+impl SimDiagnostics {
+    fn from_raw(step: u64, raw: &SimDiagnosticsRaw) -> Self {
+        Self {
+            step,
+            kinetic_energy: raw.kinetic_energy,
+            potential_energy: raw.potential_energy,
+            momentum: raw.momentum,
+            max_velocity: raw.max_velocity,
+            mean_density_error: raw.density_error_sum / raw.fluid_count.max(1.0),
+            max_density_error: raw.max_density_error,
+        }
+    }
+
+    pub fn total_energy(&self) -> f32 {
+        self.kinetic_energy + self.potential_energy
+    }
+}
+
+struct ReadbackSlot {
+    buffer: wgpu::Buffer,
+    /// step of the data copied in, `None` while the slot is free
+    step: Option<u64>,
+}
+
+pub struct ComputeReductionPass {
+    reduce_particles_pipeline: wgpu::ComputePipeline,
+    reduce_partials_pipeline: wgpu::ComputePipeline,
+
+    // partials are sized by particle count, so they are created on first use
+    num_partials: u32,
+    partials_buffer: Option<wgpu::Buffer>,
+    result_buffer: wgpu::Buffer,
+    reduction_bind_group: Option<wgpu::BindGroup>,
+
+    readback_ring: Vec<ReadbackSlot>,
+    readback_sender: flume::Sender<(usize, bool)>,
+    readback_receiver: flume::Receiver<(usize, bool)>,
+}
+
+impl ComputeReductionPass {
+    pub async fn new(
+        device: &wgpu::Device,
+        bind_group_layout_cache: &BindGroupLayoutCache,
+        layout: ParticleLayout,
+    ) -> Self {
+        let view_header = layout.view_header(0, false);
+        let shader = device.create_shader_module(
+            load_shader_with_headers(
+                "compute_reduction.wgsl",
+                &[],
+                &[("particle_view.h.wgsl", &view_header)],
+            )
+            .await
+            .unwrap(),
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Reduction Pipeline Layout"),
+            bind_group_layouts: &[
+                bind_group_layout_cache.particle_compute_layout(layout),
+                &bind_group_layout_cache.world_bind_group_layout,
+                &bind_group_layout_cache.reduction_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let reduce_particles_pipeline =
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Reduce Particles Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "reduce_particles_main",
+            });
+
+        let reduce_partials_pipeline =
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Reduce Partials Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "reduce_partials_main",
+            });
+
+        let result_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Reduction Result Buffer"),
+            contents: bytemuck::cast_slice(&[SimDiagnosticsRaw::default()]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+
+        let readback_ring = (0..DIAGNOSTICS_RING_SIZE)
+            .map(|_| ReadbackSlot {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Reduction Staging Buffer"),
+                    size: std::mem::size_of::<SimDiagnosticsRaw>() as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                step: None,
+            })
+            .collect();
+
+        let (readback_sender, readback_receiver) = flume::unbounded();
+
+        Self {
+            reduce_particles_pipeline,
+            reduce_partials_pipeline,
+            num_partials: 0,
+            partials_buffer: None,
+            result_buffer,
+            reduction_bind_group: None,
+            readback_ring,
+            readback_sender,
+            readback_receiver,
+        }
+    }
+
+    /// (re)create the partials buffer when the dispatch grid changes, one entry per workgroup
+    fn ensure_partials(&mut self, device: &wgpu::Device, num_partials: u32) {
+        if self.partials_buffer.is_some() && self.num_partials == num_partials {
+            return;
+        }
+
+        let partials_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reduction Partials Buffer"),
+            size: (std::mem::size_of::<SimDiagnosticsRaw>() * num_partials as usize)
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        self.reduction_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Reduction Bind Group"),
+            layout: &self.reduce_particles_pipeline.get_bind_group_layout(2),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: partials_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.result_buffer.as_entire_binding(),
+                },
+            ],
+        }));
+        self.partials_buffer = Some(partials_buffer);
+        self.num_partials = num_partials;
+    }
+
+    /// reduce the latest particle buffer (index 0) and queue the result for readback,
+    /// the readback is skipped when every slot of the ring is still in flight
+    pub fn reduce(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        particle_state: &ParticleState,
+    ) {
+        let (x, y, z) = particle_state.dispatch_size;
+        self.ensure_partials(device, x * y * z);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Reduction Encoder"),
+        });
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Reduction Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_bind_group(0, &particle_state.particle_compute_bind_group, &[]);
+            compute_pass.set_bind_group(1, &particle_state.world_bind_group, &[]);
+            compute_pass.set_bind_group(2, self.reduction_bind_group.as_ref().unwrap(), &[]);
+
+            compute_pass.set_pipeline(&self.reduce_particles_pipeline);
+            compute_pass.dispatch_workgroups(x, y, z);
+
+            compute_pass.set_pipeline(&self.reduce_partials_pipeline);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+
+        let free_slot = self
+            .readback_ring
+            .iter()
+            .position(|slot| slot.step.is_none());
+        if let Some(slot_idx) = free_slot {
+            encoder.copy_buffer_to_buffer(
+                &self.result_buffer,
+                0,
+                &self.readback_ring[slot_idx].buffer,
+                0,
+                std::mem::size_of::<SimDiagnosticsRaw>() as wgpu::BufferAddress,
+            );
+        }
+
+        queue.submit(Some(encoder.finish()));
+
+        if let Some(slot_idx) = free_slot {
+            let slot = &mut self.readback_ring[slot_idx];
+            slot.step = Some(particle_state.steps);
+            let sender = self.readback_sender.clone();
+            slot.buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |r| {
+                    let _ = sender.send((slot_idx, r.is_ok()));
+                });
+        }
+    }
+
+    /// collect every result that finished mapping since the last call, never blocks
+    pub fn poll_diagnostics(&mut self, device: &wgpu::Device) -> Vec<SimDiagnostics> {
+        device.poll(wgpu::Maintain::Poll);
+
+        let mut diagnostics = vec![];
+        while let Ok((slot_idx, mapped)) = self.readback_receiver.try_recv() {
+            let slot = &mut self.readback_ring[slot_idx];
+            if !mapped {
+                slot.step = None;
+                continue;
+            }
+            {
+                let mapped = slot.buffer.slice(..).get_mapped_range();
+                let raw: &[SimDiagnosticsRaw] = bytemuck::cast_slice(&mapped);
+                diagnostics.push(SimDiagnostics::from_raw(
+                    slot.step.unwrap_or_default(),
+                    &raw[0],
+                ));
+            }
+            slot.buffer.unmap();
+            slot.step = None;
+        }
+
+        diagnostics.sort_by_key(|d| d.step);
+        diagnostics
+    }
+}
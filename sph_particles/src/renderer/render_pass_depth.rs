@@ -1,11 +1,11 @@
 use crate::{
     camera::{Camera, CameraUniform},
-    particle_system::ParticleState,
+    particle_system::{scene::ParticleLayout, ParticleState},
     texture,
 };
 use wgpu::{util::DeviceExt, SurfaceConfiguration};
 
-use crate::resources::load_shader;
+use crate::resources::load_shader_with_headers;
 
 use super::bind_group_layout_cache::BindGroupLayoutCache;
 
@@ -23,6 +23,11 @@ pub struct RenderDepthPass {
     pub particle_depth_texture: texture::Texture,
     pub particle_depth_texture_bind_group: wgpu::BindGroup,
 
+    /// temperature and passive scalars of the front most particle, for the colour modes
+    pub particle_temperature_texture: texture::Texture,
+    pub particle_scalar_texture: texture::Texture,
+    pub particle_attribute_texture_bind_group: wgpu::BindGroup,
+
     // thickness render pipeline setup
     pub particle_thickness_texture: texture::Texture,
     pub particle_thickness_texture_bind_group: wgpu::BindGroup,
@@ -38,6 +43,7 @@ impl RenderDepthPass {
         camera: &Camera,
         config: &SurfaceConfiguration,
         bind_group_layout_cache: &BindGroupLayoutCache,
+        layout: ParticleLayout,
     ) -> Self {
         let camera_uniform = CameraUniform::new();
 
@@ -70,6 +76,17 @@ impl RenderDepthPass {
                 label: Some("particle_depth_bind_group"),
             });
 
+        let particle_temperature_texture =
+            texture::Texture::create_r32_render_texture(device, config, "temperature_texture");
+        let particle_scalar_texture =
+            texture::Texture::create_rgba32_render_texture(device, config, "scalar_texture");
+        let particle_attribute_texture_bind_group = create_attribute_texture_bind_group(
+            device,
+            bind_group_layout_cache,
+            &particle_temperature_texture,
+            &particle_scalar_texture,
+        );
+
         // thickness setup
         let particle_thickness_texture =
             texture::Texture::create_rgba_texture(device, config, "thickness_texture");
@@ -95,11 +112,16 @@ impl RenderDepthPass {
             });
 
         // init shader
-        let particle_depth_shader = device
-            .create_shader_module(load_shader("render_particle_depth_3d.wgsl").await.unwrap());
+        let view_header = layout.view_header(1, true);
+        let headers = [("particle_view.h.wgsl", view_header.as_str())];
+        let particle_depth_shader = device.create_shader_module(
+            load_shader_with_headers("render_particle_depth_3d.wgsl", &[], &headers)
+                .await
+                .unwrap(),
+        );
 
         let particle_thickness_shader = device.create_shader_module(
-            load_shader("render_particle_thickness_3d.wgsl")
+            load_shader_with_headers("render_particle_thickness_3d.wgsl", &[], &headers)
                 .await
                 .unwrap(),
         );
@@ -110,7 +132,7 @@ impl RenderDepthPass {
                 label: Some("Render Pipeline Layout Depth"),
                 bind_group_layouts: &[
                     &bind_group_layout_cache.camera_bind_group_layout,
-                    &bind_group_layout_cache.particle_render_bind_group_layout,
+                    bind_group_layout_cache.particle_render_layout(layout),
                 ],
                 push_constant_ranges: &[],
             });
@@ -127,15 +149,27 @@ impl RenderDepthPass {
                 fragment: Some(wgpu::FragmentState {
                     module: &particle_depth_shader,
                     entry_point: "fs_main",
-                    targets: &[if super::RENDER_TARGET == super::RENDER_PARTICLE_DEPTH {
+                    targets: &[
+                        if super::RENDER_TARGET == super::RENDER_PARTICLE_DEPTH {
+                            Some(wgpu::ColorTargetState {
+                                format: config.format,
+                                blend: Some(wgpu::BlendState::REPLACE),
+                                write_mask: wgpu::ColorWrites::ALL,
+                            })
+                        } else {
+                            None
+                        },
                         Some(wgpu::ColorTargetState {
-                            format: config.format,
-                            blend: Some(wgpu::BlendState::REPLACE),
+                            format: texture::Texture::R32_RENDER_FORMAT,
+                            blend: None,
                             write_mask: wgpu::ColorWrites::ALL,
-                        })
-                    } else {
-                        None
-                    }],
+                        }),
+                        Some(wgpu::ColorTargetState {
+                            format: texture::Texture::RGBA32_RENDER_FORMAT,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                    ],
                 }),
                 primitive: wgpu::PrimitiveState {
                     topology: wgpu::PrimitiveTopology::TriangleList,
@@ -179,7 +213,7 @@ impl RenderDepthPass {
                 label: Some("Render Pipeline Layout Thickness"),
                 bind_group_layouts: &[
                     &bind_group_layout_cache.camera_bind_group_layout,
-                    &bind_group_layout_cache.particle_render_bind_group_layout,
+                    bind_group_layout_cache.particle_render_layout(layout),
                 ],
                 push_constant_ranges: &[],
             });
@@ -231,6 +265,9 @@ impl RenderDepthPass {
             particle_thickness_render_pipeline,
             particle_depth_texture,
             particle_depth_texture_bind_group,
+            particle_temperature_texture,
+            particle_scalar_texture,
+            particle_attribute_texture_bind_group,
             particle_thickness_texture,
             particle_thickness_texture_bind_group,
             camera_uniform,
@@ -265,18 +302,37 @@ impl RenderDepthPass {
         });
 
         {
-            let color_attachments = if super::RENDER_TARGET == super::RENDER_PARTICLE_DEPTH {
-                [Some(wgpu::RenderPassColorAttachment {
+            let color_attachment = if super::RENDER_TARGET == super::RENDER_PARTICLE_DEPTH {
+                Some(wgpu::RenderPassColorAttachment {
                     view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(super::CLEAR_COLOR),
                         store: wgpu::StoreOp::Store,
                     },
-                })]
+                })
             } else {
-                [None]
+                None
             };
+            let color_attachments = [
+                color_attachment,
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &self.particle_temperature_texture.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &self.particle_scalar_texture.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+            ];
 
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
@@ -299,7 +355,7 @@ impl RenderDepthPass {
             render_pass.draw_particle_instanced(
                 0..1,
                 &self.camera_bind_group,
-                particle_state.particle_data.len() as u32,
+                particle_state.capacity as u32,
                 &particle_state.particle_render_bind_group,
             );
         }
@@ -328,5 +384,43 @@ impl RenderDepthPass {
                 }],
                 label: Some("particle_depth_bind_group"),
             });
+        self.particle_temperature_texture = texture::Texture::create_r32_render_texture(
+            device,
+            surface_config,
+            "temperature_texture",
+        );
+        self.particle_scalar_texture = texture::Texture::create_rgba32_render_texture(
+            device,
+            surface_config,
+            "scalar_texture",
+        );
+        self.particle_attribute_texture_bind_group = create_attribute_texture_bind_group(
+            device,
+            bind_group_layout_cache,
+            &self.particle_temperature_texture,
+            &self.particle_scalar_texture,
+        );
     }
 }
+
+fn create_attribute_texture_bind_group(
+    device: &wgpu::Device,
+    bind_group_layout_cache: &BindGroupLayoutCache,
+    temperature_texture: &texture::Texture,
+    scalar_texture: &texture::Texture,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &bind_group_layout_cache.particle_attribute_texture_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&temperature_texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&scalar_texture.view),
+            },
+        ],
+        label: Some("particle_attribute_bind_group"),
+    })
+}
@@ -0,0 +1,150 @@
+use wgpu::SurfaceConfiguration;
+
+use crate::resources::load_shader;
+use crate::texture;
+
+use super::compute_pass_diffuse::ComputeDiffusePass;
+use super::BindGroupLayoutCache;
+
+/// composites the spray, foam and bubble particles over the water from `RenderQuadPass`,
+/// hidden behind the fluid surface and the obstacles by the depth buffer
+pub struct RenderDiffusePass {
+    pub _shader: wgpu::ShaderModule,
+    pub render_pipeline: wgpu::RenderPipeline,
+
+    // rebuilt when the diffuse ring buffer is recreated
+    diffuse_bind_group: Option<wgpu::BindGroup>,
+    bound_capacity: u32,
+}
+
+impl RenderDiffusePass {
+    pub async fn new(
+        device: &wgpu::Device,
+        config: &SurfaceConfiguration,
+        bind_group_layout_cache: &BindGroupLayoutCache,
+    ) -> Self {
+        let shader = device.create_shader_module(load_shader("render_diffuse.wgsl").await.unwrap());
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Diffuse Render Pipeline Layout"),
+                bind_group_layouts: &[
+                    &bind_group_layout_cache.camera_bind_group_layout,
+                    &bind_group_layout_cache.particle_render_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Diffuse Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            _shader: shader,
+            render_pipeline,
+            diffuse_bind_group: None,
+            bound_capacity: 0,
+        }
+    }
+
+    /// draws on top of `view`, depth tested against `depth_view` without writing it, the
+    /// particles are transparent. nothing to do before the diffuse pass ran
+    pub fn render(
+        &mut self,
+        camera_bind_group: &wgpu::BindGroup,
+        compute_diffuse_pass: &ComputeDiffusePass,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+    ) {
+        let Some(diffuse_buffer) = compute_diffuse_pass.diffuse_buffer() else {
+            return;
+        };
+        if self.diffuse_bind_group.is_none()
+            || self.bound_capacity != compute_diffuse_pass.capacity()
+        {
+            self.diffuse_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Diffuse Render Bind Group"),
+                layout: &self.render_pipeline.get_bind_group_layout(1),
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: diffuse_buffer.as_entire_binding(),
+                }],
+            }));
+            self.bound_capacity = compute_diffuse_pass.capacity();
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Diffuse Encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Diffuse Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&self.render_pipeline);
+
+            use crate::particle_system::DrawParticle;
+            render_pass.draw_particle_instanced(
+                0..1,
+                camera_bind_group,
+                compute_diffuse_pass.capacity(),
+                self.diffuse_bind_group.as_ref().unwrap(),
+            );
+        }
+
+        queue.submit(Some(encoder.finish()));
+    }
+}
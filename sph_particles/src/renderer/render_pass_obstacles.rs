@@ -0,0 +1,155 @@
+use wgpu::SurfaceConfiguration;
+
+use crate::particle_system::ParticleState;
+use crate::resources::load_shader;
+use crate::texture;
+
+use super::BindGroupLayoutCache;
+
+/// draws the outlines of the obstacles over the water from `RenderQuadPass`, hidden
+/// behind the fluid by its depth from `RenderDepthPass`
+pub struct RenderObstaclesPass {
+    pub _shader: wgpu::ShaderModule,
+    pub render_pipeline: wgpu::RenderPipeline,
+
+    // the obstacles move, so the lines are rebuilt every frame. grown as needed
+    vertex_buffer: Option<wgpu::Buffer>,
+}
+
+impl RenderObstaclesPass {
+    pub async fn new(
+        device: &wgpu::Device,
+        config: &SurfaceConfiguration,
+        bind_group_layout_cache: &BindGroupLayoutCache,
+    ) -> Self {
+        let shader =
+            device.create_shader_module(load_shader("render_obstacles.wgsl").await.unwrap());
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Obstacles Render Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout_cache.camera_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Obstacles Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            _shader: shader,
+            render_pipeline,
+            vertex_buffer: None,
+        }
+    }
+
+    /// draws on top of `view`, depth tested against and written into `depth_view` so the
+    /// diffuse particles drawn next are hidden behind the obstacles. nothing to do for a
+    /// scene without obstacles
+    pub fn render(
+        &mut self,
+        camera_bind_group: &wgpu::BindGroup,
+        particle_state: &ParticleState,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+    ) {
+        let vertices = particle_state
+            .obstacles
+            .iter()
+            .flat_map(|obstacle| obstacle.wireframe(particle_state.time))
+            .map(|p| -> [f32; 3] { p.into() })
+            .collect::<Vec<_>>();
+        if vertices.is_empty() {
+            return;
+        }
+
+        let size = std::mem::size_of_val(vertices.as_slice()) as wgpu::BufferAddress;
+        if self
+            .vertex_buffer
+            .as_ref()
+            .is_none_or(|buffer| buffer.size() < size)
+        {
+            self.vertex_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Obstacles Vertex Buffer"),
+                size,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        let vertex_buffer = self.vertex_buffer.as_ref().unwrap();
+        queue.write_buffer(vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Obstacles Encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Obstacles Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..size));
+            render_pass.draw(0..vertices.len() as u32, 0..1);
+        }
+
+        queue.submit(Some(encoder.finish()));
+    }
+}
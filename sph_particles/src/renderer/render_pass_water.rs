@@ -2,6 +2,7 @@ use wgpu::{util::DeviceExt, SurfaceConfiguration};
 
 use crate::{
     camera::{Camera, CameraUniform},
+    particle_system::scalar::{ScalarChannel, DEFAULT_SCALAR_COLORS, MAX_SCALARS},
     texture,
 };
 
@@ -57,6 +58,66 @@ const VERTICES: &[QuadVertex] = &[
 
 const INDICES: &[u16] = &[0, 2, 1, 0, 3, 2];
 
+/// what the surface colour shows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    #[default]
+    Water,
+    Temperature,
+    /// the water tinted by the passive scalars, see scalar.rs
+    Dye,
+}
+
+impl ColorMode {
+    pub const ALL: [ColorMode; 3] = [ColorMode::Water, ColorMode::Temperature, ColorMode::Dye];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorMode::Water => "Water",
+            ColorMode::Temperature => "Temperature",
+            ColorMode::Dye => "Dye",
+        }
+    }
+
+    /// render_water.wgsl COLOR_MODE_*
+    fn shader_id(&self) -> u32 {
+        match self {
+            ColorMode::Water => 0,
+            ColorMode::Temperature => 1,
+            ColorMode::Dye => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ColorSettings {
+    pub mode: ColorMode,
+    /// °C mapped to the ends of the colour ramp
+    pub temperature_range: [f32; 2],
+    /// linear RGB of each scalar channel at a concentration of 1
+    pub scalar_colors: [[f32; 3]; MAX_SCALARS],
+}
+
+impl ColorSettings {
+    /// the colours of the scene's scalar channels
+    pub fn with_scalar_channels(mut self, channels: &[ScalarChannel]) -> Self {
+        for (color, channel) in self.scalar_colors.iter_mut().zip(channels) {
+            *color = channel.color;
+        }
+        self
+    }
+}
+
+impl Default for ColorSettings {
+    fn default() -> Self {
+        Self {
+            mode: ColorMode::default(),
+            temperature_range: [0.0, 100.0],
+            scalar_colors: DEFAULT_SCALAR_COLORS,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Zeroable, bytemuck::Pod)]
 pub struct RenderUniforms {
@@ -64,6 +125,11 @@ pub struct RenderUniforms {
     pub screen_coordinate: [f32; 2],
     pub znear: f32,
     pub zfar: f32,
+    pub color_mode: u32,
+    pub _pad: u32,
+    pub temperature_range: [f32; 2],
+    /// rgb of `ColorSettings::scalar_colors`, w unused
+    pub scalar_colors: [[f32; 4]; MAX_SCALARS],
 }
 
 pub struct RenderQuadPass {
@@ -116,6 +182,10 @@ impl RenderQuadPass {
             screen_coordinate: [config.width as f32, config.height as f32],
             znear: camera.znear,
             zfar: camera.zfar,
+            color_mode: ColorMode::default().shader_id(),
+            _pad: 0,
+            temperature_range: ColorSettings::default().temperature_range,
+            scalar_colors: [[0.0; 4]; MAX_SCALARS],
         };
 
         let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -159,6 +229,7 @@ impl RenderQuadPass {
                     &bind_group_layout_cache.camera_bind_group_layout,
                     &bind_group_layout_cache.render_uniforms_bind_group_layout,
                     &bind_group_layout_cache.sampled_depth_texture_read_bind_group_layout,
+                    &bind_group_layout_cache.particle_attribute_texture_bind_group_layout,
                 ],
                 push_constant_ranges: &[],
             });
@@ -239,9 +310,17 @@ impl RenderQuadPass {
         );
     }
 
+    /// written to the GPU with the next `update_uniforms`
+    pub fn set_color_settings(&mut self, settings: ColorSettings) {
+        self.uniform_data.color_mode = settings.mode.shader_id();
+        self.uniform_data.temperature_range = settings.temperature_range;
+        self.uniform_data.scalar_colors = settings.scalar_colors.map(|[r, g, b]| [r, g, b, 1.0]);
+    }
+
     pub fn render(
         &self,
         depth_texture_bind_group: &wgpu::BindGroup,
+        attribute_texture_bind_group: &wgpu::BindGroup,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         view: &wgpu::TextureView,
@@ -278,6 +357,7 @@ impl RenderQuadPass {
             render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
             render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
             render_pass.set_bind_group(2, &depth_texture_bind_group, &[]);
+            render_pass.set_bind_group(3, attribute_texture_bind_group, &[]);
 
             render_pass.draw_indexed(0..INDICES.len() as u32, 0, 0..1);
         }
@@ -157,7 +157,26 @@ pub async fn load_model(
 }
 
 pub async fn load_shader(name: &str) -> anyhow::Result<wgpu::ShaderModuleDescriptor> {
-    let shader_code = load_shader_module(name).await?;
+    load_shader_with_includes(name, &[]).await
+}
+
+/// like `load_shader`, but every `//!include` of a name in `replacements` pulls in
+/// its replacement instead, this is how a shader picks one of several variants of a header
+pub async fn load_shader_with_includes<'a>(
+    name: &'a str,
+    replacements: &[(&str, &str)],
+) -> anyhow::Result<wgpu::ShaderModuleDescriptor<'a>> {
+    load_shader_with_headers(name, replacements, &[]).await
+}
+
+/// like `load_shader_with_includes`, and every `//!include` of a name in `headers` pulls
+/// in the given source, for headers that depend on the scene, see `ParticleLayout`
+pub async fn load_shader_with_headers<'a>(
+    name: &'a str,
+    replacements: &[(&str, &str)],
+    headers: &[(&str, &str)],
+) -> anyhow::Result<wgpu::ShaderModuleDescriptor<'a>> {
+    let shader_code = load_shader_module(name, replacements, headers).await?;
 
     Ok(wgpu::ShaderModuleDescriptor {
         label: Some(name),
@@ -165,7 +184,26 @@ pub async fn load_shader(name: &str) -> anyhow::Result<wgpu::ShaderModuleDescrip
     })
 }
 
-async fn load_shader_module(name: &str) -> anyhow::Result<String> {
+/// headers generated from the Rust side instead of loaded from `shader/`, so the
+/// shaders can't fall out of step with the constants they share
+fn generated_header(name: &str) -> Option<String> {
+    match name {
+        "grid.h.wgsl" => Some(crate::particle_system::grid::shader_header()),
+        _ => None,
+    }
+}
+
+async fn load_shader_module(
+    name: &str,
+    replacements: &[(&str, &str)],
+    headers: &[(&str, &str)],
+) -> anyhow::Result<String> {
+    if let Some((_, header)) = headers.iter().find(|(include, _)| *include == name) {
+        return Ok(header.to_string());
+    }
+    if let Some(header) = generated_header(name) {
+        return Ok(header);
+    }
     let base_path = path::PathBuf::from("shader");
     let module_path = base_path.join(name).with_extension("wgsl");
 
@@ -182,7 +220,12 @@ async fn load_shader_module(name: &str) -> anyhow::Result<String> {
     let first_line = module_source.lines().next().unwrap();
     if first_line.starts_with("//!include") {
         for include in first_line.split_whitespace().skip(1) {
-            module_string.push_str(&Box::pin(load_shader_module(include)).await?);
+            let include = replacements
+                .iter()
+                .find(|(from, _)| *from == include)
+                .map_or(include, |(_, to)| to);
+            let header = Box::pin(load_shader_module(include, replacements, headers)).await?;
+            module_string.push_str(&header);
         }
     }
 
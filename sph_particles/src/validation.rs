@@ -0,0 +1,26 @@
+//! Validation scenarios that compare the solver against reference data,
+//! they run through the headless path
+pub mod dam_break;
+pub mod hydrostatic;
+
+/// least-squares line through `(x, y)`, returns `(slope, r_squared)`
+fn linear_fit(points: &[(f32, f32)]) -> (f32, f32) {
+    let n = points.len() as f32;
+    let mean_x = points.iter().map(|p| p.0).sum::<f32>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f32>() / n;
+
+    let (mut s_xx, mut s_xy, mut s_yy) = (0.0, 0.0, 0.0);
+    for &(x, y) in points {
+        s_xx += (x - mean_x) * (x - mean_x);
+        s_xy += (x - mean_x) * (y - mean_y);
+        s_yy += (y - mean_y) * (y - mean_y);
+    }
+
+    let slope = s_xy / s_xx;
+    let r_squared = if s_yy > 0.0 {
+        s_xy * s_xy / (s_xx * s_yy)
+    } else {
+        0.0
+    };
+    (slope, r_squared)
+}
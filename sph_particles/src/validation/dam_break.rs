@@ -0,0 +1,283 @@
+//! Dam-break column collapse, compared with the experiment of Martin & Moyce (1952).
+//!
+//! A water column of width `a` and height `2a` (n² = 2) rests against the left wall
+//! and collapses when the simulation starts. The surge front and the column height
+//! at the wall are tracked over time and compared in the experiment's dimensionless units.
+//!
+//! The experiment's column is under hydrostatic pressure when the gate opens. The weakly
+//! compressible solver starts it at the rest density, where it clamps the pressure to
+//! zero, and the column has to compress under gravity before its base pushes out. That
+//! takes about as long as sound needs to cross the column, `2a / c` with the solver's
+//! speed of sound `c = sqrt(7 * stiffness / rho_0)`, about 0.35 dimensionless time units
+//! here. The top of the column falls freely meanwhile, and the front leaves the wall
+//! that much later and trails the experiment from then on. Its speed once it runs matches
+//! the experiment's, so `surge_front_speed` is compared apart from the positions. The
+//! rest volume is calibrated, with the fixed one the column starts at half the rest
+//! density and waits even longer. The digitised reference points carry no error bars.
+use std::io;
+
+use cgmath::Vector3;
+
+use super::linear_fit;
+use crate::headless::{HeadlessSim, Particle, Scene, SimParams, SolverMode, SpawnBlock};
+
+/// surge front `(t * sqrt(2g / a), x / a)`, digitised from Martin & Moyce (1952), n² = 2
+pub const MARTIN_MOYCE_SURGE_FRONT: &[(f32, f32)] = &[
+    (0.00, 1.00),
+    (0.41, 1.11),
+    (0.84, 1.22),
+    (1.19, 1.44),
+    (1.43, 1.67),
+    (1.63, 1.89),
+    (1.83, 2.11),
+    (1.98, 2.33),
+    (2.20, 2.56),
+    (2.32, 2.78),
+    (2.51, 3.00),
+    (2.65, 3.22),
+    (2.83, 3.44),
+    (2.98, 3.67),
+    (3.11, 3.89),
+    (3.33, 4.11),
+];
+
+/// dimensionless time after which the surge front runs at a steady speed, in the units of
+/// `MARTIN_MOYCE_SURGE_FRONT`
+pub const STEADY_SURGE_TIME: f32 = 1.4;
+
+/// column height `(t * sqrt(g / a), h / 2a)`, digitised from Martin & Moyce (1952), n² = 2
+pub const MARTIN_MOYCE_COLUMN_HEIGHT: &[(f32, f32)] = &[
+    (0.00, 1.00),
+    (0.56, 0.94),
+    (0.77, 0.89),
+    (0.97, 0.83),
+    (1.17, 0.78),
+    (1.37, 0.72),
+    (1.56, 0.67),
+    (1.79, 0.61),
+    (2.03, 0.56),
+    (2.29, 0.50),
+    (2.53, 0.44),
+    (2.80, 0.39),
+    (3.08, 0.33),
+];
+
+const GRAVITY: f32 = 9.8;
+
+#[derive(Debug, Clone)]
+pub struct DamBreakConfig {
+    /// column width `a`, the column is `2a` high
+    pub column_width: f32,
+    pub particle_radius: f32,
+    /// thickness of the slab along z, in particles
+    pub depth_layers: u32,
+    pub params: SimParams,
+    /// dimensionless end time `t * sqrt(2g / a)`
+    pub end_time: f32,
+    /// solver steps between two samples
+    pub sample_interval: u32,
+}
+
+impl Default for DamBreakConfig {
+    fn default() -> Self {
+        Self {
+            column_width: 0.5,
+            particle_radius: 0.025,
+            depth_layers: 8,
+            params: SimParams {
+                time_step: 0.001,
+                stiffness: 5.0e4,
+                viscosity: 0.002,
+                deterministic: true,
+                calibrate_rest_volume: true,
+                ..Default::default()
+            },
+            end_time: 3.33,
+            sample_interval: 10,
+        }
+    }
+}
+
+impl DamBreakConfig {
+    pub fn scene(&self) -> Scene {
+        let a = self.column_width;
+        let r = self.particle_radius;
+        let support_radius = 4.0 * r;
+        let depth = self.depth_layers as f32 * 2.0 * r;
+        let cells = |length: f32| (length / support_radius).ceil() as u32;
+
+        Scene {
+            particle_radius: r,
+            support_radius,
+            domain_lower: Vector3::new(0.0, 0.0, 0.0),
+            domain_cells: Vector3::new(cells(5.0 * a), cells(3.0 * a), cells(depth)),
+            solver: SolverMode::Volumetric,
+            params: self.params,
+            blocks: vec![SpawnBlock::fluid(
+                Vector3::new(r, r, r),
+                Vector3::new(a, 2.0 * a, depth),
+            )],
+            heat_sources: vec![],
+            force_fields: vec![],
+            container: Default::default(),
+            obstacles: vec![],
+            inlets: vec![],
+            outlets: vec![],
+            scalars: vec![],
+            scalar_sources: vec![],
+            layout: Default::default(),
+            neighbor_search: Default::default(),
+        }
+    }
+
+    fn measure(&self, particles: &[Particle], time: f32) -> DamBreakSample {
+        let r = self.particle_radius;
+        // particles closer to the wall than one support radius make up the column
+        let wall_band = 4.0 * r;
+
+        let fluid = || particles.iter().filter(|p| p.is_fluid());
+        let surge_front = fluid().map(|p| p.position.x + r).fold(0.0, f32::max);
+        let column_height = fluid()
+            .filter(|p| p.position.x < wall_band)
+            .map(|p| p.position.y + r)
+            .fold(0.0, f32::max);
+
+        DamBreakSample {
+            time,
+            surge_front,
+            column_height,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DamBreakSample {
+    /// seconds since the collapse started
+    pub time: f32,
+    /// distance of the leading particle from the left wall
+    pub surge_front: f32,
+    /// height of the fluid at the left wall
+    pub column_height: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct DamBreakReport {
+    pub column_width: f32,
+    pub samples: Vec<DamBreakSample>,
+}
+
+impl DamBreakReport {
+    /// simulated surge front in the units of `MARTIN_MOYCE_SURGE_FRONT`
+    pub fn scaled_surge_front(&self) -> Vec<(f32, f32)> {
+        let a = self.column_width;
+        self.samples
+            .iter()
+            .map(|s| (s.time * (2.0 * GRAVITY / a).sqrt(), s.surge_front / a))
+            .collect()
+    }
+
+    /// simulated column height in the units of `MARTIN_MOYCE_COLUMN_HEIGHT`
+    pub fn scaled_column_height(&self) -> Vec<(f32, f32)> {
+        let a = self.column_width;
+        self.samples
+            .iter()
+            .map(|s| (s.time * (GRAVITY / a).sqrt(), s.column_height / (2.0 * a)))
+            .collect()
+    }
+
+    /// largest deviation from the reference surge front, in units of `a`
+    pub fn surge_front_error(&self) -> f32 {
+        max_deviation(&self.scaled_surge_front(), MARTIN_MOYCE_SURGE_FRONT)
+    }
+
+    /// simulated and reference surge front speed in `a` per dimensionless time unit, fitted
+    /// after `STEADY_SURGE_TIME`
+    pub fn surge_front_speed(&self) -> (f32, f32) {
+        let steady = |curve: &[(f32, f32)]| {
+            let points = curve
+                .iter()
+                .copied()
+                .filter(|&(t, _)| t >= STEADY_SURGE_TIME)
+                .collect::<Vec<_>>();
+            linear_fit(&points).0
+        };
+        (
+            steady(&self.scaled_surge_front()),
+            steady(MARTIN_MOYCE_SURGE_FRONT),
+        )
+    }
+
+    /// largest deviation from the reference column height, in units of `2a`
+    pub fn column_height_error(&self) -> f32 {
+        max_deviation(&self.scaled_column_height(), MARTIN_MOYCE_COLUMN_HEIGHT)
+    }
+
+    /// write both curves with the interpolated reference values next to them
+    pub fn write_csv<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "time,surge_front,column_height,\
+             front_t,front_z,front_z_reference,height_t,height_h,height_h_reference"
+        )?;
+        let front = self.scaled_surge_front();
+        let height = self.scaled_column_height();
+        for ((sample, (front_t, front_z)), (height_t, height_h)) in
+            self.samples.iter().zip(front).zip(height)
+        {
+            let front_ref = interpolate(MARTIN_MOYCE_SURGE_FRONT, front_t);
+            let height_ref = interpolate(MARTIN_MOYCE_COLUMN_HEIGHT, height_t);
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{}",
+                sample.time,
+                sample.surge_front,
+                sample.column_height,
+                front_t,
+                front_z,
+                front_ref.map(|z| z.to_string()).unwrap_or_default(),
+                height_t,
+                height_h,
+                height_ref.map(|h| h.to_string()).unwrap_or_default(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// linear interpolation of a curve sorted by x, `None` outside of its range
+fn interpolate(curve: &[(f32, f32)], x: f32) -> Option<f32> {
+    curve.windows(2).find_map(|w| {
+        let ((x0, y0), (x1, y1)) = (w[0], w[1]);
+        (x0..=x1)
+            .contains(&x)
+            .then(|| y0 + (y1 - y0) * (x - x0) / (x1 - x0))
+    })
+}
+
+/// largest difference between the simulated curve and the reference points it covers
+fn max_deviation(simulated: &[(f32, f32)], reference: &[(f32, f32)]) -> f32 {
+    reference
+        .iter()
+        .filter_map(|&(x, y)| interpolate(simulated, x).map(|sim_y| (sim_y - y).abs()))
+        .fold(0.0, f32::max)
+}
+
+/// run the collapse until `config.end_time` and sample it every `config.sample_interval` steps
+pub async fn run(config: &DamBreakConfig) -> anyhow::Result<DamBreakReport> {
+    let mut sim = HeadlessSim::new(&config.scene()).await?;
+
+    let end_time = config.end_time / (2.0 * GRAVITY / config.column_width).sqrt();
+
+    let mut samples = vec![config.measure(&sim.read_particles().await, sim.time())];
+    while sim.time() < end_time {
+        for _ in 0..config.sample_interval {
+            sim.step().await;
+        }
+        samples.push(config.measure(&sim.read_particles().await, sim.time()));
+    }
+
+    Ok(DamBreakReport {
+        column_width: config.column_width,
+        samples,
+    })
+}
@@ -0,0 +1,191 @@
+//! Resting water column: once settled, the pressure grows linearly with depth, the density
+//! stays close to `rho_0` and the particles stay put.
+//!
+//! The pressure gradient is not compared with the continuum `rho_0 * g`: the cubic spline's
+//! gradient summed over a lattice with two particle spacings per support radius isn't
+//! normalised, so the pressure force overestimates the gradient and the column comes to
+//! rest at a gradient `rho_0 * g / C` below it, with `C` from
+//! `utils::pressure_gradient_scale` (about 1.86 planar and 1.50 volumetric). The column
+//! uses the calibrated rest volume, so the lattice that `C` is summed over is the one the
+//! column settles to.
+use cgmath::{InnerSpace, Vector3};
+
+use super::linear_fit;
+use crate::headless::{HeadlessSim, Particle, Scene, SimParams, SolverMode, SpawnBlock};
+use crate::particle_system::utils::pressure_gradient_scale;
+
+const GRAVITY: f32 = 9.8;
+const REST_DENSITY: f32 = 1000.0;
+
+#[derive(Debug, Clone)]
+pub struct HydrostaticConfig {
+    pub solver: SolverMode,
+    pub column_width: f32,
+    pub column_height: f32,
+    pub particle_radius: f32,
+    /// thickness of the column along z in particles, the planar solver always uses one
+    pub depth_layers: u32,
+    pub params: SimParams,
+    /// seconds simulated before anything is measured
+    pub settle_time: f32,
+    /// seconds after settling over which the drift is measured
+    pub observe_time: f32,
+}
+
+impl HydrostaticConfig {
+    pub fn new(solver: SolverMode) -> Self {
+        Self {
+            solver,
+            column_width: 0.4,
+            column_height: 0.6,
+            particle_radius: 0.015,
+            depth_layers: 4,
+            params: SimParams {
+                time_step: 0.001,
+                stiffness: 5.0e4,
+                viscosity: 0.05,
+                deterministic: true,
+                calibrate_rest_volume: true,
+                ..Default::default()
+            },
+            settle_time: 1.5,
+            observe_time: 0.5,
+        }
+    }
+
+    pub fn scene(&self) -> Scene {
+        let r = self.particle_radius;
+        let support_radius = 4.0 * r;
+        let depth = match self.solver {
+            SolverMode::Planar => 2.0 * r,
+            SolverMode::Volumetric => self.depth_layers as f32 * 2.0 * r,
+        };
+        let cells = |length: f32| (length / support_radius).ceil() as u32;
+
+        Scene {
+            particle_radius: r,
+            support_radius,
+            domain_lower: Vector3::new(0.0, 0.0, 0.0),
+            domain_cells: Vector3::new(
+                cells(self.column_width),
+                cells(2.0 * self.column_height),
+                cells(depth),
+            ),
+            solver: self.solver,
+            params: self.params,
+            blocks: vec![SpawnBlock::fluid(
+                Vector3::new(r, r, r),
+                Vector3::new(self.column_width, self.column_height, depth),
+            )],
+            heat_sources: vec![],
+            force_fields: vec![],
+            container: Default::default(),
+            obstacles: vec![],
+            inlets: vec![],
+            outlets: vec![],
+            scalars: vec![],
+            scalar_sources: vec![],
+            layout: Default::default(),
+            neighbor_search: Default::default(),
+        }
+    }
+
+    /// fluid particles further than one support radius from the floor and the side walls,
+    /// the walls have no boundary particles so the density is underestimated next to them
+    fn is_interior(&self, particle: &Particle) -> bool {
+        let margin = 4.0 * self.particle_radius;
+        particle.is_fluid()
+            && particle.position.y > margin
+            && particle.position.x > margin
+            && particle.position.x < self.column_width - margin
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HydrostaticReport {
+    /// least-squares slope of the pressure over the depth below the free surface, Pa/m
+    pub pressure_gradient: f32,
+    /// gradient that holds the lattice up against gravity, `rho_0 * g / C`, see the
+    /// module docs
+    pub expected_pressure_gradient: f32,
+    /// coefficient of determination of that fit, 1 for a perfectly linear profile
+    pub pressure_r_squared: f32,
+    /// relative to `rho_0`
+    pub mean_density_error: f32,
+    pub max_density_error: f32,
+    /// largest displacement while observing, in particle diameters
+    pub max_drift: f32,
+    /// largest particle speed at the end of the run
+    pub max_velocity: f32,
+}
+
+impl HydrostaticReport {
+    /// relative deviation of the fitted pressure gradient from `expected_pressure_gradient`
+    pub fn pressure_gradient_error(&self) -> f32 {
+        (self.pressure_gradient / self.expected_pressure_gradient - 1.0).abs()
+    }
+}
+
+async fn advance(sim: &mut HeadlessSim, seconds: f32) -> anyhow::Result<()> {
+    let end_time = sim.time() + seconds;
+    while sim.time() < end_time {
+        sim.step().await;
+    }
+    Ok(())
+}
+
+/// settle the column, then measure the pressure profile and the drift
+pub async fn run(config: &HydrostaticConfig) -> anyhow::Result<HydrostaticReport> {
+    let scene = config.scene();
+    let mut sim = HeadlessSim::new(&scene).await?;
+
+    advance(&mut sim, config.settle_time).await?;
+    let settled = sim.read_particles().await;
+
+    advance(&mut sim, config.observe_time).await?;
+    let particles = sim.read_particles().await;
+
+    let fluid = || particles.iter().filter(|p| p.is_fluid());
+
+    let surface = fluid().map(|p| p.position.y).fold(f32::MIN, f32::max);
+    let pressure_samples = fluid()
+        .filter(|p| config.is_interior(p))
+        .map(|p| (surface - p.position.y, p.pressure))
+        .collect::<Vec<_>>();
+    let (pressure_gradient, pressure_r_squared) = linear_fit(&pressure_samples);
+
+    let density_errors = fluid()
+        .filter(|p| config.is_interior(p))
+        .map(|p| (p.density - REST_DENSITY).abs() / REST_DENSITY)
+        .collect::<Vec<_>>();
+    let mean_density_error = density_errors.iter().sum::<f32>() / density_errors.len() as f32;
+    let max_density_error = density_errors.iter().copied().fold(0.0, f32::max);
+
+    // the particle order is kept between steps, so the buffers line up
+    let max_drift = settled
+        .iter()
+        .zip(particles.iter())
+        .filter(|(p, _)| p.is_fluid())
+        .map(|(a, b)| (b.position - a.position).magnitude())
+        .fold(0.0, f32::max)
+        / (2.0 * config.particle_radius);
+    let max_velocity = fluid().map(|p| p.velocity.magnitude()).fold(0.0, f32::max);
+
+    let expected_pressure_gradient = REST_DENSITY * GRAVITY
+        / pressure_gradient_scale(
+            2.0 * scene.particle_radius,
+            scene.support_radius,
+            scene.solver == SolverMode::Planar,
+            scene.rest_volume(),
+        );
+
+    Ok(HydrostaticReport {
+        pressure_gradient,
+        expected_pressure_gradient,
+        pressure_r_squared,
+        mean_density_error,
+        max_density_error,
+        max_drift,
+        max_velocity,
+    })
+}
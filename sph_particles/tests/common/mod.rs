@@ -0,0 +1,65 @@
+//! Scene setup and adapter handling shared by the GPU tests. Every test binary uses a
+//! different part of it
+#![allow(dead_code)]
+
+use cgmath::Vector3;
+use sph_particles::headless::{NoAdapter, Scene, SimParams, SolverMode, SpawnBlock};
+
+/// particle radius of the planar scenes
+pub const RADIUS: f32 = 0.015;
+
+/// the result of a test's setup, `None` when there is no software adapter to run it on.
+/// any other error is a broken scene or device and fails the test
+pub fn skip_without_adapter<T>(result: anyhow::Result<T>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(err) if err.is::<NoAdapter>() => {
+            eprintln!("skipping: {err}");
+            None
+        }
+        Err(err) => panic!("test setup failed: {err:?}"),
+    }
+}
+
+/// an empty deterministic planar tank `width` wide and `height_cells` support radii high,
+/// with particles of `RADIUS` and a 1 ms time step
+pub fn planar_scene(width: f32, height_cells: u32) -> Scene {
+    let support_radius = 4.0 * RADIUS;
+    Scene {
+        particle_radius: RADIUS,
+        support_radius,
+        domain_cells: Vector3::new((width / support_radius) as u32, height_cells, 1),
+        solver: SolverMode::Planar,
+        params: SimParams {
+            time_step: 0.001,
+            stiffness: 5.0e4,
+            deterministic: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// a block of fluid from `lower` to `upper` in the plane of `planar_scene`, one particle
+/// layer thick
+pub fn planar_block(lower: [f32; 2], upper: [f32; 2]) -> SpawnBlock {
+    SpawnBlock::fluid(
+        Vector3::new(lower[0], lower[1], RADIUS),
+        Vector3::new(upper[0], upper[1], 3.0 * RADIUS),
+    )
+}
+
+/// an empty deterministic volumetric scene of a few hundred coarse particles per block,
+/// cheap enough to run several times per test
+pub fn coarse_scene(domain_cells: Vector3<u32>) -> Scene {
+    Scene {
+        particle_radius: 0.05,
+        support_radius: 0.2,
+        domain_cells,
+        params: SimParams {
+            deterministic: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
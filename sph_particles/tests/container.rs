@@ -0,0 +1,204 @@
+mod common;
+
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Quaternion, Rad, Rotation3, Vector3};
+use common::RADIUS;
+use sph_particles::headless::{
+    ContainerAnimation, ContainerKeyframe, ContainerWall, HeadlessSim, NeighborSearch, NoAdapter,
+    Particle, Piston, Scene, Shake,
+};
+
+const EPSILON: f32 = 1e-4;
+
+#[test]
+fn keyframes_interpolate_and_hold() {
+    let container = ContainerAnimation::default().with_keyframes(vec![
+        ContainerKeyframe::new(1.0).with_translation(Vector3::new(1.0, 0.0, 0.0)),
+        ContainerKeyframe::new(3.0)
+            .with_translation(Vector3::new(3.0, 2.0, 0.0))
+            .with_rotation(Vector3::unit_z(), Rad(1.0)),
+    ]);
+
+    // the first pose is held before the first keyframe, the last one after the last
+    assert!((container.pose(0.0).translation - Vector3::new(1.0, 0.0, 0.0)).magnitude() < EPSILON);
+    assert!((container.pose(5.0).translation - Vector3::new(3.0, 2.0, 0.0)).magnitude() < EPSILON);
+
+    let halfway = container.pose(2.0);
+    assert!((halfway.translation - Vector3::new(2.0, 1.0, 0.0)).magnitude() < EPSILON);
+    let expected = Quaternion::from_axis_angle(Vector3::unit_z(), Rad(0.5));
+    assert!((halfway.rotation - expected).magnitude() < EPSILON);
+}
+
+#[test]
+fn shake_and_piston_start_at_rest() {
+    let container = ContainerAnimation::default()
+        .with_shake(Shake {
+            amplitude: Vector3::new(0.2, 0.0, 0.0),
+            frequency: 2.0,
+        })
+        .with_piston(Piston {
+            wall: ContainerWall::MaxX,
+            stroke: 0.5,
+            frequency: 1.0,
+        });
+
+    let start = container.pose(0.0);
+    assert!(start.translation.magnitude() < EPSILON);
+    assert!(start.upper_offset.magnitude() < EPSILON);
+
+    // a quarter shake period is the largest displacement
+    assert!((container.pose(0.125).translation.x - 0.2).abs() < EPSILON);
+    // half a piston period is the full stroke, inwards
+    let stroke = container.pose(0.5);
+    assert!((stroke.upper_offset.x + 0.5).abs() < EPSILON);
+    assert!(stroke.lower_offset.magnitude() < EPSILON);
+}
+
+const WIDTH: f32 = 0.6;
+const PISTON: Piston = Piston {
+    wall: ContainerWall::MaxX,
+    stroke: 0.1,
+    frequency: 2.0,
+};
+/// a quarter piston period, the wall is halfway in and at its fastest
+const PISTON_STEPS: usize = 125;
+
+/// a shallow tank of water, with `container` moving it
+fn tank(container: ContainerAnimation) -> Scene {
+    Scene {
+        blocks: vec![common::planar_block(
+            [RADIUS, RADIUS],
+            [WIDTH - RADIUS, 0.15],
+        )],
+        container,
+        ..common::planar_scene(WIDTH, 8)
+    }
+}
+
+/// the particles after `PISTON_STEPS` steps
+async fn pushed(container: ContainerAnimation) -> anyhow::Result<Vec<Particle>> {
+    let mut sim = HeadlessSim::new(&tank(container)).await?;
+    for _ in 0..PISTON_STEPS {
+        sim.step().await;
+    }
+    Ok(sim.read_particles().await)
+}
+
+/// mean x velocity of the particles within a support radius of `wall_x`
+fn velocity_at_wall(particles: &[Particle], wall_x: f32) -> f32 {
+    let at_wall = particles
+        .iter()
+        .filter(|p| wall_x - p.position.x < 4.0 * RADIUS)
+        .map(|p| p.velocity.x)
+        .collect::<Vec<_>>();
+    at_wall.iter().sum::<f32>() / at_wall.len() as f32
+}
+
+#[test]
+fn piston_pushes_the_fluid() {
+    let result = pollster::block_on(async {
+        anyhow::Ok((
+            pushed(ContainerAnimation::default()).await?,
+            pushed(ContainerAnimation::default().with_piston(PISTON)).await?,
+        ))
+    });
+    let Some((still, moved)) = common::skip_without_adapter(result) else {
+        return;
+    };
+
+    let time = PISTON_STEPS as f32 * tank(ContainerAnimation::default()).params.time_step;
+    let phase = 2.0 * PI * PISTON.frequency * time;
+    let wall_x = WIDTH - 0.5 * PISTON.stroke * (1.0 - phase.cos());
+    let wall_velocity = -PI * PISTON.frequency * PISTON.stroke * phase.sin();
+
+    // the walls clamp the particles and reflect their velocity relative to the wall
+    let overshoot = moved
+        .iter()
+        .map(|p| p.position.x - wall_x)
+        .fold(f32::MIN, f32::max);
+    assert!(
+        overshoot < RADIUS,
+        "a particle is {overshoot} m past the piston"
+    );
+    let pushed = velocity_at_wall(&moved, wall_x);
+    assert!(
+        (pushed - wall_velocity).abs() < 0.2 * wall_velocity.abs(),
+        "the fluid at the piston moves at {pushed} m/s, the piston at {wall_velocity} m/s"
+    );
+    let resting = velocity_at_wall(&still, WIDTH);
+    assert!(
+        resting.abs() < 0.2 * wall_velocity.abs(),
+        "the fluid at the still wall moves at {resting} m/s"
+    );
+}
+
+#[test]
+fn swept_bounds_cover_the_animation() {
+    let lower = Vector3::new(0.0, 0.0, 0.0);
+    let upper = Vector3::new(1.0, 1.0, 1.0);
+
+    // a piston only moves inwards
+    let (piston_lower, piston_upper) = ContainerAnimation::default()
+        .with_piston(PISTON)
+        .swept_bounds(lower, upper);
+    assert!((piston_lower - lower).magnitude() < EPSILON);
+    assert!((piston_upper - upper).magnitude() < EPSILON);
+
+    let (shake_lower, shake_upper) = ContainerAnimation::default()
+        .with_shake(Shake {
+            amplitude: Vector3::new(0.2, 0.0, 0.0),
+            frequency: 2.0,
+        })
+        .swept_bounds(lower, upper);
+    assert!((shake_lower - Vector3::new(-0.2, 0.0, 0.0)).magnitude() < EPSILON);
+    assert!((shake_upper - Vector3::new(1.2, 1.0, 1.0)).magnitude() < EPSILON);
+
+    // a quarter turn about z reaches the corners at sqrt(2) / 2 from the centre
+    let (turn_lower, turn_upper) = ContainerAnimation::default()
+        .with_keyframes(vec![
+            ContainerKeyframe::new(0.0),
+            ContainerKeyframe::new(1.0).with_rotation(Vector3::unit_z(), Rad(PI / 2.0)),
+        ])
+        .swept_bounds(lower, upper);
+    let reach = 0.5 * 2f32.sqrt();
+    assert!((turn_lower.x - (0.5 - reach)).abs() < 1e-3);
+    assert!((turn_upper.y - (0.5 + reach)).abs() < 1e-3);
+}
+
+#[test]
+fn cached_lists_reject_a_container_leaving_the_domain() {
+    let shaking = ContainerAnimation::default().with_shake(Shake {
+        amplitude: Vector3::new(0.05, 0.0, 0.0),
+        frequency: 2.0,
+    });
+    let scene = |neighbor_search, container| Scene {
+        neighbor_search,
+        ..tank(container)
+    };
+
+    let result = pollster::block_on(HeadlessSim::new(&scene(
+        NeighborSearch::CachedList,
+        shaking.clone(),
+    )));
+    match result {
+        Err(err) if err.is::<NoAdapter>() => return,
+        Err(err) => assert!(
+            err.to_string().contains("out of the domain"),
+            "unexpected error: {err}"
+        ),
+        Ok(_) => panic!("a shaking container was accepted with the cached lists"),
+    }
+
+    // the piston stays inside, the hashed cells follow the particles out
+    for (neighbor_search, container) in [
+        (
+            NeighborSearch::CachedList,
+            ContainerAnimation::default().with_piston(PISTON),
+        ),
+        (NeighborSearch::HashedList { table_size: 1024 }, shaking),
+    ] {
+        let result = pollster::block_on(HeadlessSim::new(&scene(neighbor_search, container)));
+        common::skip_without_adapter(result);
+    }
+}
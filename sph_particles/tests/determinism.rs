@@ -0,0 +1,129 @@
+mod common;
+
+use cgmath::Vector3;
+use sph_particles::headless::{
+    ContainerAnimation, DiffuseParams, HeadlessSim, NeighborSearch, Scene, Shake, SimParams,
+    SpawnBlock,
+};
+
+const STEPS: usize = 40;
+
+/// two colliding blocks with plenty of spray, so the diffuse spawn is covered too
+fn scene() -> Scene {
+    let scene = common::coarse_scene(Vector3::new(10, 8, 4));
+    Scene {
+        params: SimParams {
+            vorticity_enabled: true,
+            diffuse: DiffuseParams {
+                enabled: true,
+                trapped_air: 400.0,
+                wave_crest: 400.0,
+                ..Default::default()
+            },
+            ..scene.params
+        },
+        blocks: vec![
+            SpawnBlock::fluid(Vector3::new(0.1, 0.1, 0.1), Vector3::new(0.6, 0.5, 0.7))
+                .with_velocity(Vector3::new(4.0, 0.0, 0.0)),
+            SpawnBlock::fluid(Vector3::new(1.4, 0.1, 0.1), Vector3::new(1.9, 0.5, 0.7))
+                .with_velocity(Vector3::new(-4.0, 0.0, 0.0)),
+        ],
+        ..scene
+    }
+}
+
+/// particle hash and the diffuse particles' bits after `STEPS` steps
+async fn run(scene: &Scene) -> anyhow::Result<(u64, Vec<[u32; 4]>)> {
+    let mut sim = HeadlessSim::new(scene).await?;
+    for _ in 0..STEPS {
+        sim.step().await;
+    }
+    let diffuse = sim
+        .read_diffuse_particles()
+        .await
+        .iter()
+        .map(|d| {
+            [
+                d.position.x.to_bits(),
+                d.position.y.to_bits(),
+                d.position.z.to_bits(),
+                d.lifetime.to_bits(),
+            ]
+        })
+        .collect();
+    Ok((sim.particle_hash().await, diffuse))
+}
+
+#[test]
+fn repeated_runs_are_bit_identical() {
+    let Some(first) = common::skip_without_adapter(pollster::block_on(run(&scene()))) else {
+        return;
+    };
+    let second = pollster::block_on(run(&scene())).unwrap();
+
+    assert_eq!(
+        first.0, second.0,
+        "particle buffers differ after {STEPS} steps"
+    );
+    assert_eq!(
+        first.1, second.1,
+        "diffuse particles differ after {STEPS} steps"
+    );
+}
+
+#[test]
+fn diffuse_particles_match_across_fluid_grids() {
+    // the diffuse pass bins the fluid into the domain's cells for the cached lists, and
+    // into the hash buckets for the hashed lists
+    let dense = Scene {
+        neighbor_search: NeighborSearch::CachedList,
+        ..scene()
+    };
+    let hashed = Scene {
+        neighbor_search: NeighborSearch::HashedList { table_size: 61 },
+        ..scene()
+    };
+    let Some(dense) = common::skip_without_adapter(pollster::block_on(run(&dense))) else {
+        return;
+    };
+    let hashed = pollster::block_on(run(&hashed)).unwrap();
+
+    assert!(!dense.1.is_empty(), "no diffuse particles spawned");
+    assert_eq!(dense.0, hashed.0, "the hash table changed the fluid");
+    // the neighbours are gathered sorted by index, so the sums run in the same order
+    assert_eq!(
+        dense.1, hashed.1,
+        "diffuse particles differ between the dense and the hashed grid"
+    );
+}
+
+/// particle hash after `STEPS` solver steps taken `substeps` per frame, in a shaking
+/// container so the per substep world uniforms matter
+async fn run_substeps(substeps: u32) -> anyhow::Result<u64> {
+    let mut scene = scene();
+    scene.params.substeps = substeps;
+    scene.params.diffuse.enabled = false;
+    scene.container = ContainerAnimation::default().with_shake(Shake {
+        amplitude: Vector3::new(0.05, 0.0, 0.0),
+        frequency: 4.0,
+    });
+
+    let mut sim = HeadlessSim::new(&scene).await?;
+    for _ in 0..STEPS / substeps as usize {
+        sim.step().await;
+    }
+    Ok(sim.particle_hash().await)
+}
+
+#[test]
+fn substeps_match_single_steps() {
+    let Some(single) = common::skip_without_adapter(pollster::block_on(run_substeps(1))) else {
+        return;
+    };
+    let batched = pollster::block_on(run_substeps(4)).unwrap();
+
+    assert_eq!(
+        single, batched,
+        "{STEPS} steps in frames of 4 substeps differ from single steps"
+    );
+}
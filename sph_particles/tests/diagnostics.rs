@@ -0,0 +1,127 @@
+mod common;
+
+use cgmath::{InnerSpace, Vector3};
+use sph_particles::headless::{
+    HeadlessSim, Particle, Scene, SimDiagnostics, SimParams, SpawnBlock,
+};
+
+/// sph.h.wgsl rho_0
+const REST_DENSITY: f32 = 1000.0;
+const STEPS: u64 = 20;
+const SUBSTEPS: u32 = 2;
+/// relative to the sum of the magnitudes, f32 sums over a few hundred particles
+const TOLERANCE: f32 = 1.0e-4;
+
+/// a falling block of a few workgroups of particles, so the partials of several
+/// workgroups are folded
+fn scene() -> Scene {
+    let scene = common::coarse_scene(Vector3::new(10, 8, 4));
+    Scene {
+        params: SimParams {
+            substeps: SUBSTEPS,
+            ..scene.params
+        },
+        blocks: vec![SpawnBlock::fluid(
+            Vector3::new(0.1, 0.1, 0.1),
+            Vector3::new(1.5, 1.0, 0.7),
+        )],
+        ..scene
+    }
+}
+
+/// the diagnostics of the last step and the particles they were reduced from
+async fn reduced() -> anyhow::Result<(SimDiagnostics, Vec<Particle>, f32)> {
+    let mut sim = HeadlessSim::new(&scene()).await?;
+    let mut latest = None;
+    for _ in 0..STEPS {
+        sim.step().await;
+        latest = sim.diagnostics().pop().or(latest);
+    }
+    let particles = sim.read_particles().await;
+    Ok((latest.unwrap(), particles, sim.rest_volume() * REST_DENSITY))
+}
+
+fn assert_close(name: &str, gpu: f32, cpu: f32, scale: f32) {
+    assert!(
+        (gpu - cpu).abs() <= TOLERANCE * scale,
+        "{name}: reduced {gpu}, summed on the CPU {cpu}"
+    );
+}
+
+#[test]
+fn reduction_matches_a_cpu_sum() {
+    let Some((diagnostics, particles, mass)) =
+        common::skip_without_adapter(pollster::block_on(reduced()))
+    else {
+        return;
+    };
+    let fluid = particles
+        .iter()
+        .filter(|p| p.is_fluid())
+        .collect::<Vec<_>>();
+    assert!(fluid.len() > 512, "only {} fluid particles", fluid.len());
+    assert_eq!(
+        diagnostics.step,
+        STEPS * SUBSTEPS as u64,
+        "the diagnostics should count every substep"
+    );
+
+    let kinetic_energy = fluid
+        .iter()
+        .map(|p| 0.5 * mass * p.velocity.magnitude2())
+        .sum::<f32>();
+    assert_close(
+        "kinetic energy",
+        diagnostics.kinetic_energy,
+        kinetic_energy,
+        kinetic_energy,
+    );
+
+    let momentum = fluid
+        .iter()
+        .map(|p| mass * p.velocity)
+        .sum::<Vector3<f32>>();
+    let momentum_scale = fluid
+        .iter()
+        .map(|p| mass * p.velocity.magnitude())
+        .sum::<f32>();
+    for axis in 0..3 {
+        assert_close(
+            &format!("momentum {axis}"),
+            diagnostics.momentum[axis],
+            momentum[axis],
+            momentum_scale,
+        );
+    }
+
+    let max_velocity = fluid
+        .iter()
+        .map(|p| p.velocity.magnitude())
+        .fold(0.0, f32::max);
+    assert!(max_velocity > 0.0, "the block never started falling");
+    assert_close(
+        "max velocity",
+        diagnostics.max_velocity,
+        max_velocity,
+        max_velocity,
+    );
+
+    let density_errors = fluid
+        .iter()
+        .map(|p| (p.density - REST_DENSITY).abs() / REST_DENSITY)
+        .collect::<Vec<_>>();
+    let mean_density_error = density_errors.iter().sum::<f32>() / fluid.len() as f32;
+    let max_density_error = density_errors.iter().copied().fold(0.0, f32::max);
+    assert_close(
+        "mean density error",
+        diagnostics.mean_density_error,
+        mean_density_error,
+        mean_density_error,
+    );
+    assert_close(
+        "max density error",
+        diagnostics.max_density_error,
+        max_density_error,
+        max_density_error,
+    );
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use cgmath::Vector3;
+use sph_particles::headless::{
+    DiffuseParams, HeadlessSim, NoAdapter, Scene, SimParams, SpawnBlock,
+};
+
+#[test]
+fn oversized_ring_is_an_error() {
+    let scene = common::coarse_scene(Vector3::new(4, 4, 4));
+    let scene = Scene {
+        params: SimParams {
+            diffuse: DiffuseParams {
+                enabled: true,
+                max_particles: u32::MAX,
+                ..Default::default()
+            },
+            ..scene.params
+        },
+        ..scene
+    };
+
+    match pollster::block_on(HeadlessSim::new(&scene)) {
+        Err(err) if err.is::<NoAdapter>() => {}
+        Err(err) => assert!(
+            err.to_string().contains("diffuse particle slots"),
+            "unexpected error: {err}"
+        ),
+        Ok(_) => panic!("a ring of u32::MAX diffuse particles was accepted"),
+    }
+}
+
+#[test]
+fn oversized_particle_scene_is_an_error() {
+    // about 2.5 million particles of 64 bytes, past the 128 MiB the software adapter binds
+    let scene = Scene {
+        particle_radius: 0.005,
+        support_radius: 0.02,
+        domain_cells: Vector3::new(70, 70, 70),
+        blocks: vec![SpawnBlock::fluid(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.35, 1.35, 1.35),
+        )],
+        ..Default::default()
+    };
+
+    match pollster::block_on(HeadlessSim::new(&scene)) {
+        Err(err) if err.is::<NoAdapter>() => {}
+        Err(err) => assert!(
+            err.to_string().contains("can bind in one storage buffer"),
+            "unexpected error: {err}"
+        ),
+        Ok(_) => panic!("a scene of more particles than the device can bind was accepted"),
+    }
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use cgmath::{InnerSpace, Vector3};
+use common::RADIUS;
+use sph_particles::headless::{
+    ContainerWall, FluidMaterial, HeadlessSim, Inlet, Particle, ParticleLayout, Scene,
+};
+
+const WIDTH: f32 = 1.0;
+const DEPTH: f32 = 0.15;
+const SPEED: f32 = 1.0;
+const STEPS: usize = 800;
+const LAYOUT_STEPS: usize = 200;
+
+/// a channel fed through its left face and drained through its right one, starting
+/// half full of still water
+fn scene() -> Scene {
+    let scene = common::planar_scene(WIDTH, 6);
+    Scene {
+        blocks: vec![common::planar_block(
+            [scene.support_radius, RADIUS],
+            [0.5 * WIDTH, DEPTH],
+        )],
+        inlets: vec![Inlet::new(ContainerWall::MinX, SPEED)
+            .with_region(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, DEPTH, 0.0))
+            .with_material(FluidMaterial::default())],
+        outlets: vec![ContainerWall::MaxX],
+        ..scene
+    }
+}
+
+#[test]
+fn inlets_add_and_outlets_delete_particles() {
+    let scene = scene();
+    let result = pollster::block_on(async {
+        let mut sim = HeadlessSim::new(&scene).await?;
+        let initial = sim.read_particles().await;
+        for _ in 0..STEPS {
+            sim.step().await;
+        }
+        anyhow::Ok((initial, sim.read_particles().await))
+    });
+    let Some((initial, particles)) = common::skip_without_adapter(result) else {
+        return;
+    };
+
+    // the block is material 0, the inlet's particles material 1
+    let count = |particles: &[Particle], material| {
+        particles.iter().filter(|p| p.material == material).count()
+    };
+    let counts = format!(
+        "block particles {} -> {}, inlet particles {} -> {}",
+        count(&initial, 0),
+        count(&particles, 0),
+        count(&initial, 1),
+        count(&particles, 1)
+    );
+    assert!(
+        count(&particles, 1) > count(&initial, 1) * 10,
+        "the inlet barely added any: {counts}"
+    );
+    assert!(
+        count(&particles, 0) < count(&initial, 0),
+        "the outlet deleted none: {counts}"
+    );
+
+    // swept every few frames, so none is far past the outlet
+    let furthest = particles
+        .iter()
+        .map(|p| p.position.x)
+        .fold(f32::MIN, f32::max);
+    assert!(
+        furthest < WIDTH + scene.support_radius,
+        "a particle reached x {furthest}, past the outlet at {WIDTH}"
+    );
+
+    // the buffer zone moves with the inlet
+    let zone = particles
+        .iter()
+        .filter(|p| p.position.x < scene.support_radius)
+        .collect::<Vec<_>>();
+    let mean_speed = zone.iter().map(|p| p.velocity.x).sum::<f32>() / zone.len() as f32;
+    assert!(
+        (mean_speed - SPEED).abs() < 0.05 * SPEED,
+        "{} particles in the inlet zone at {mean_speed} m/s instead of {SPEED}",
+        zone.len()
+    );
+}
+
+/// the channel in `layout` after `LAYOUT_STEPS`, and the particle count the GPU reports
+async fn channel(layout: ParticleLayout) -> anyhow::Result<(Vec<Particle>, usize)> {
+    let mut sim = HeadlessSim::new(&Scene { layout, ..scene() }).await?;
+    for _ in 0..LAYOUT_STEPS {
+        sim.step().await;
+    }
+    Ok((sim.read_particles().await, sim.num_particles().await))
+}
+
+#[test]
+fn split_layout_exchanges_the_same_particles() {
+    let Some((interleaved, count)) =
+        common::skip_without_adapter(pollster::block_on(channel(ParticleLayout::Interleaved)))
+    else {
+        return;
+    };
+    let (split, split_count) = pollster::block_on(channel(ParticleLayout::Split {
+        half_precision: false,
+    }))
+    .unwrap();
+
+    assert_eq!(
+        count,
+        interleaved.len(),
+        "the count disagrees with the readback"
+    );
+    assert_eq!(
+        split_count,
+        split.len(),
+        "the split count disagrees with the readback"
+    );
+    assert_eq!(
+        split_count, count,
+        "the split layout kept a different number of particles"
+    );
+    let max_distance = interleaved
+        .iter()
+        .zip(&split)
+        .map(|(a, b)| (a.position - b.position).magnitude())
+        .fold(0.0, f32::max);
+    // the same particles in the same order, only where they are stored differs
+    assert_eq!(
+        max_distance, 0.0,
+        "split layout diverged from the interleaved one"
+    );
+}
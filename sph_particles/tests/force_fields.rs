@@ -0,0 +1,136 @@
+mod common;
+
+use cgmath::{InnerSpace, Vector3, Zero};
+use sph_particles::headless::{
+    ForceField, HeadlessSim, NoAdapter, Particle, Scene, SimParams, SpawnBlock,
+};
+
+const STEPS: usize = 30;
+
+/// a small block floating in the middle of the domain, without gravity unless `gravity`
+fn scene(gravity: Vector3<f32>, force_fields: Vec<ForceField>) -> Scene {
+    let scene = common::coarse_scene(Vector3::new(10, 10, 10));
+    Scene {
+        params: SimParams {
+            gravity,
+            ..scene.params
+        },
+        blocks: vec![SpawnBlock::fluid(
+            Vector3::new(0.7, 0.7, 0.7),
+            Vector3::new(1.3, 1.3, 1.3),
+        )],
+        force_fields,
+        ..scene
+    }
+}
+
+async fn run(scene: Scene) -> anyhow::Result<Vec<Particle>> {
+    let mut sim = HeadlessSim::new(&scene).await?;
+    for _ in 0..STEPS {
+        sim.step().await;
+    }
+    Ok(sim.read_particles().await)
+}
+
+fn centre() -> Vector3<f32> {
+    Vector3::new(1.0, 1.0, 1.0)
+}
+
+fn mean_velocity(particles: &[Particle]) -> Vector3<f32> {
+    particles
+        .iter()
+        .fold(Vector3::zero(), |sum, p| sum + p.velocity)
+        / particles.len() as f32
+}
+
+#[test]
+fn fields_push_the_fluid() {
+    let sideways = pollster::block_on(run(scene(Vector3::new(5.0, 0.0, 0.0), vec![])));
+    let Some(sideways) = common::skip_without_adapter(sideways) else {
+        return;
+    };
+    let velocity = mean_velocity(&sideways);
+    assert!(
+        velocity.x > 0.5,
+        "gravity along x moved the fluid by {velocity:?}"
+    );
+    assert!(
+        velocity.y.abs() < 0.1 * velocity.x,
+        "gravity along x moved the fluid by {velocity:?}"
+    );
+
+    // off to one side of the block, the whole block should head for it
+    let attractor = Vector3::new(1.8, 1.0, 1.0);
+    let attracted = pollster::block_on(run(scene(
+        Vector3::zero(),
+        vec![ForceField::attractor(attractor, 20.0)],
+    )))
+    .unwrap();
+    let towards = mean_velocity(&attracted).dot((attractor - centre()).normalize());
+    assert!(
+        towards > 1.0,
+        "the attractor pulled the fluid in at {towards} m/s"
+    );
+
+    // counter clockwise around +y
+    let swirled = pollster::block_on(run(scene(
+        Vector3::zero(),
+        vec![ForceField::vortex(centre(), Vector3::unit_y(), 10.0)],
+    )))
+    .unwrap();
+    let angular_momentum = swirled
+        .iter()
+        .map(|p| (p.position - centre()).cross(p.velocity).y)
+        .sum::<f32>();
+    assert!(
+        angular_momentum > 0.0,
+        "the vortex spun the fluid up to an angular momentum of {angular_momentum} about y"
+    );
+
+    // pulsing, drifting noise stirs the block without blowing it up
+    let stirred = pollster::block_on(run(scene(
+        Vector3::zero(),
+        vec![ForceField::turbulence(0.3, 20.0).with_pulse(0.5, 2.0)],
+    )))
+    .unwrap();
+    let max_speed = stirred
+        .iter()
+        .map(|p| p.velocity.magnitude())
+        .fold(0.0, f32::max);
+    assert!(
+        max_speed.is_finite() && max_speed > 0.1,
+        "the turbulence stirred the fluid up to {max_speed} m/s"
+    );
+}
+
+#[test]
+fn fields_stay_inside_their_region() {
+    let far_away = Vector3::new(0.2, 1.8, 0.2);
+    let fields = vec![
+        ForceField::attractor(centre(), 50.0).with_region(far_away, 0.2, 0.1),
+        ForceField::wind(Vector3::new(5.0, 0.0, 0.0), 10.0).with_region(far_away, 0.2, 0.1),
+    ];
+    let with_fields = pollster::block_on(run(scene(Vector3::zero(), fields)));
+    let Some(with_fields) = common::skip_without_adapter(with_fields) else {
+        return;
+    };
+    let without = pollster::block_on(run(scene(Vector3::zero(), vec![]))).unwrap();
+
+    for (a, b) in with_fields.iter().zip(&without) {
+        assert_eq!(a.position, b.position, "a field acted outside its region");
+        assert_eq!(a.velocity, b.velocity, "a field acted outside its region");
+    }
+}
+
+#[test]
+fn a_vortex_needs_an_axis() {
+    let scene = scene(
+        Vector3::zero(),
+        vec![ForceField::vortex(centre(), Vector3::zero(), 10.0)],
+    );
+    match pollster::block_on(HeadlessSim::new(&scene)) {
+        Err(err) if err.is::<NoAdapter>() => {}
+        Err(err) => assert!(err.to_string().contains("axis"), "unexpected error: {err}"),
+        Ok(_) => panic!("a vortex without an axis was accepted"),
+    }
+}
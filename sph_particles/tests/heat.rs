@@ -0,0 +1,130 @@
+mod common;
+
+use cgmath::{Vector3, Zero};
+use common::RADIUS;
+use sph_particles::headless::{FluidMaterial, HeadlessSim, Particle, Scene, SimParams};
+
+const WIDTH: f32 = 1.0;
+const HOT: f32 = 80.0;
+const COLD: f32 = 20.0;
+/// m²/s, fast enough for the blocks to even out within a second
+const DIFFUSIVITY: f32 = 1.0e-2;
+const CONDUCTION_TIME: f32 = 1.0;
+const SLUMP_TIME: f32 = 1.0;
+
+/// a hot and a cold block side by side in the middle of a planar tank without gravity,
+/// clear of the walls, which would hold their own temperature
+fn contact_scene() -> Scene {
+    let scene = common::planar_scene(WIDTH, 10);
+    let material = FluidMaterial {
+        thermal_diffusivity: DIFFUSIVITY,
+        ..FluidMaterial::water()
+    };
+    let block = |x: f32, temperature: f32| {
+        common::planar_block([x, 0.15], [x + 0.09 - RADIUS, 0.45])
+            .with_material(material)
+            .with_temperature(temperature)
+    };
+    Scene {
+        params: SimParams {
+            gravity: Vector3::zero(),
+            ..scene.params
+        },
+        blocks: vec![block(0.41, HOT), block(0.5, COLD)],
+        ..scene
+    }
+}
+
+/// mean temperature of the fluid particles of `material`
+fn mean_temperature(particles: &[Particle], material: u32) -> f32 {
+    let block = particles
+        .iter()
+        .filter(|p| p.is_fluid() && p.material == material);
+    block.clone().map(|p| p.temperature).sum::<f32>() / block.count() as f32
+}
+
+async fn conduct() -> anyhow::Result<Vec<Particle>> {
+    let mut sim = HeadlessSim::new(&contact_scene()).await?;
+    while sim.time() < CONDUCTION_TIME {
+        sim.step().await;
+    }
+    Ok(sim.read_particles().await)
+}
+
+#[test]
+fn hot_and_cold_blocks_even_out() {
+    let Some(particles) = common::skip_without_adapter(pollster::block_on(conduct())) else {
+        return;
+    };
+    let (hot, cold) = (
+        mean_temperature(&particles, 0),
+        mean_temperature(&particles, 1),
+    );
+    // current solver: about 5 K apart after a second
+    assert!(
+        hot - cold < 0.25 * (HOT - COLD),
+        "the blocks are still {hot} °C and {cold} °C"
+    );
+
+    // the blocks are equally large, so their heat is shared evenly
+    let fluid = particles.iter().filter(|p| p.is_fluid());
+    let mean = fluid.clone().map(|p| p.temperature).sum::<f32>() / fluid.clone().count() as f32;
+    assert!(
+        (mean - 0.5 * (HOT + COLD)).abs() < 0.02 * (HOT - COLD),
+        "the mean temperature drifted to {mean} °C"
+    );
+    for p in fluid {
+        assert!(
+            (COLD..=HOT).contains(&p.temperature),
+            "a particle at {:?} overshot to {} °C",
+            p.position,
+            p.temperature
+        );
+    }
+}
+
+/// how far the front of a column of wax at `temperature` advanced in `SLUMP_TIME`
+async fn slump(temperature: f32) -> anyhow::Result<f32> {
+    let front = |particles: &[Particle]| {
+        particles
+            .iter()
+            .filter(|p| p.is_fluid())
+            .map(|p| p.position.x)
+            .fold(f32::MIN, f32::max)
+    };
+    let scene = common::planar_scene(WIDTH, 10);
+    let scene = Scene {
+        params: SimParams {
+            viscosity_iterations: 32,
+            xsph_enabled: true,
+            xsph: 0.5,
+            ..scene.params
+        },
+        blocks: vec![common::planar_block([RADIUS, RADIUS], [0.3, 0.4])
+            .with_material(FluidMaterial::wax())
+            .with_temperature(temperature)],
+        ..scene
+    };
+    let mut sim = HeadlessSim::new(&scene).await?;
+    let start = front(&sim.read_particles().await);
+    while sim.time() < SLUMP_TIME {
+        sim.step().await;
+    }
+    Ok(front(&sim.read_particles().await) - start)
+}
+
+#[test]
+fn wax_below_its_solidus_stays_rigid() {
+    let solidus = FluidMaterial::wax().thermal.unwrap().solidus_temperature;
+    let Some(solid) = common::skip_without_adapter(pollster::block_on(slump(solidus - 20.0)))
+    else {
+        return;
+    };
+    let molten = pollster::block_on(slump(solidus + 40.0)).unwrap();
+    // current solver: the solid column's foot moves about a centimetre as it settles
+    assert!(molten > 0.2, "the molten wax only spread {molten} m");
+    assert!(
+        solid < 0.1 * molten,
+        "the solid wax spread {solid} m, molten {molten} m"
+    );
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use sph_particles::headless::SolverMode;
+use sph_particles::validation::hydrostatic::{self, HydrostaticConfig};
+
+const GRAVITY: f32 = 9.8;
+const REST_DENSITY: f32 = 1000.0;
+
+/// relative to `expected_pressure_gradient`, which already accounts for the lattice
+const PRESSURE_GRADIENT_TOLERANCE: f32 = 0.05;
+const MIN_PRESSURE_R_SQUARED: f32 = 0.9;
+/// in particle diameters, a particle that swapped places with a neighbour moved one
+const DRIFT_TOLERANCE: f32 = 0.5;
+
+/// the Tait equation's density at the floor of the column, relative to `rho_0`, no
+/// particle should be compressed further
+fn max_density_error(config: &HydrostaticConfig) -> f32 {
+    let floor_pressure = REST_DENSITY * GRAVITY * config.column_height;
+    (1.0 + floor_pressure / config.params.stiffness).powf(1.0 / 7.0) - 1.0
+}
+
+/// a tenth of the speed of a shallow water wave in the column, m/s
+fn velocity_tolerance(config: &HydrostaticConfig) -> f32 {
+    0.1 * (GRAVITY * config.column_height).sqrt()
+}
+
+fn check_resting_column(solver: SolverMode) {
+    let config = HydrostaticConfig::new(solver);
+    let Some(report) = common::skip_without_adapter(pollster::block_on(hydrostatic::run(&config)))
+    else {
+        return;
+    };
+    assert!(
+        report.pressure_gradient_error() < PRESSURE_GRADIENT_TOLERANCE,
+        "{solver:?}: pressure gradient {} Pa/m instead of {}",
+        report.pressure_gradient,
+        report.expected_pressure_gradient
+    );
+    assert!(
+        report.pressure_r_squared > MIN_PRESSURE_R_SQUARED,
+        "{solver:?}: pressure profile is not linear, R² = {}",
+        report.pressure_r_squared
+    );
+    // the density grows about linearly with depth, so the mean is half the floor's
+    let max_density_error = max_density_error(&config);
+    assert!(
+        report.mean_density_error < 0.5 * max_density_error,
+        "{solver:?}: mean density error {}",
+        report.mean_density_error
+    );
+    assert!(
+        report.max_density_error < max_density_error,
+        "{solver:?}: max density error {}",
+        report.max_density_error
+    );
+    assert!(
+        report.max_drift < DRIFT_TOLERANCE,
+        "{solver:?}: particles drifted by {} diameters",
+        report.max_drift
+    );
+    assert!(
+        report.max_velocity < velocity_tolerance(&config),
+        "{solver:?}: column did not settle, max velocity {} m/s",
+        report.max_velocity
+    );
+}
+
+#[test]
+fn hydrostatic_planar() {
+    check_resting_column(SolverMode::Planar);
+}
+
+#[test]
+fn hydrostatic_volumetric() {
+    check_resting_column(SolverMode::Volumetric);
+}
@@ -0,0 +1,162 @@
+mod common;
+
+use cgmath::Vector3;
+use sph_particles::headless::{
+    ContainerAnimation, ContainerWall, HeadlessSim, NeighborSearch, NeighborStats, Scene,
+    SimParams, SpawnBlock,
+};
+
+const STEPS: usize = 30;
+
+/// two colliding blocks with the velocity filters on, so every neighbour loop runs
+fn scene(neighbor_search: NeighborSearch) -> Scene {
+    let scene = common::coarse_scene(Vector3::new(10, 8, 4));
+    Scene {
+        neighbor_search,
+        params: SimParams {
+            vorticity_enabled: true,
+            xsph_enabled: true,
+            ..scene.params
+        },
+        blocks: vec![
+            SpawnBlock::fluid(Vector3::new(0.1, 0.1, 0.1), Vector3::new(0.6, 0.5, 0.7))
+                .with_velocity(Vector3::new(4.0, 0.0, 0.0)),
+            SpawnBlock::fluid(Vector3::new(1.4, 0.1, 0.1), Vector3::new(1.9, 0.5, 0.7))
+                .with_velocity(Vector3::new(-4.0, 0.0, 0.0)),
+        ],
+        ..scene
+    }
+}
+
+#[test]
+fn cached_list_matches_all_pairs() {
+    let mut hashes = vec![];
+    for neighbor_search in [NeighborSearch::AllPairs, NeighborSearch::CachedList] {
+        let sim = pollster::block_on(HeadlessSim::new(&scene(neighbor_search)));
+        let Some(mut sim) = common::skip_without_adapter(sim) else {
+            return;
+        };
+        for _ in 0..STEPS {
+            pollster::block_on(sim.step());
+        }
+        let stats = sim.neighbor_stats();
+        assert!(
+            !stats.overflowed(),
+            "{neighbor_search:?} overflowed: {stats:?}"
+        );
+        hashes.push(pollster::block_on(sim.particle_hash()));
+    }
+    // the lists are sorted by index, so the sums run in the same order
+    assert_eq!(
+        hashes[0], hashes[1],
+        "the cached neighbour lists changed the result"
+    );
+}
+
+/// a cube of 8x8x8 particles in a domain of support radius wide cells
+fn crowded(neighbor_search: NeighborSearch, support_radius: f32) -> Scene {
+    let cells = (1.0 / support_radius).ceil() as u32;
+    Scene {
+        support_radius,
+        neighbor_search,
+        blocks: vec![SpawnBlock::fluid(
+            Vector3::new(0.1, 0.1, 0.1),
+            Vector3::new(0.9, 0.9, 0.9),
+        )],
+        ..common::coarse_scene(Vector3::new(cells, cells, cells))
+    }
+}
+
+async fn stats_after_a_step(scene: &Scene) -> anyhow::Result<NeighborStats> {
+    let mut sim = HeadlessSim::new(scene).await?;
+    sim.step().await;
+    Ok(sim.neighbor_stats())
+}
+
+#[test]
+fn crowded_scenes_report_overflow() {
+    // 20 particle radii put the whole cube in one cell, 7 radii leave about 40 particles
+    // per cell but more than 150 in the support sphere
+    let cases = [
+        (NeighborSearch::AllPairs, 1.0),
+        (NeighborSearch::CachedList, 1.0),
+        (NeighborSearch::CachedList, 0.35),
+    ];
+    for (neighbor_search, support_radius) in cases {
+        let scene = crowded(neighbor_search, support_radius);
+        let Some(stats) =
+            common::skip_without_adapter(pollster::block_on(stats_after_a_step(&scene)))
+        else {
+            return;
+        };
+        match (neighbor_search, support_radius) {
+            (NeighborSearch::AllPairs, _) => {
+                assert!(!stats.overflowed(), "all pairs overflowed: {stats:?}")
+            }
+            (NeighborSearch::CachedList, 1.0) => assert!(
+                stats.cell_overflow > 0,
+                "the particles fit one cell: {stats:?}"
+            ),
+            _ => assert!(
+                stats.cell_overflow == 0 && stats.neighbor_overflow > 0,
+                "the neighbours fit the lists: {stats:?}"
+            ),
+        }
+    }
+}
+
+/// a block thrown down through the open floor of the domain
+fn falling(neighbor_search: NeighborSearch) -> Scene {
+    Scene {
+        neighbor_search,
+        blocks: vec![
+            SpawnBlock::fluid(Vector3::new(0.3, 0.1, 0.3), Vector3::new(0.9, 0.6, 0.9))
+                .with_velocity(Vector3::new(0.0, -4.0, 0.0)),
+        ],
+        container: ContainerAnimation::default().with_open_wall(ContainerWall::MinY),
+        ..common::coarse_scene(Vector3::new(6, 4, 6))
+    }
+}
+
+#[test]
+fn hashed_list_follows_particles_out_of_the_domain() {
+    let cases = [
+        NeighborSearch::AllPairs,
+        NeighborSearch::HashedList { table_size: 4096 },
+        // a few cells per bucket, the collisions cost candidates but not accuracy
+        NeighborSearch::HashedList { table_size: 31 },
+    ];
+    let mut hashes = vec![];
+    for neighbor_search in cases {
+        let sim = pollster::block_on(HeadlessSim::new(&falling(neighbor_search)));
+        let Some(mut sim) = common::skip_without_adapter(sim) else {
+            return;
+        };
+        for _ in 0..STEPS {
+            pollster::block_on(sim.step());
+        }
+        let particles = pollster::block_on(sim.read_particles());
+        let stats = sim.neighbor_stats();
+        let below = particles.iter().filter(|p| p.position.y < 0.0).count();
+        assert!(
+            below == particles.len(),
+            "{below} of {} particles left the domain",
+            particles.len()
+        );
+        assert!(
+            !stats.overflowed(),
+            "{neighbor_search:?} overflowed: {stats:?}"
+        );
+        if neighbor_search == (NeighborSearch::HashedList { table_size: 31 }) {
+            assert!(
+                stats.hash_collisions > 0,
+                "31 buckets without collisions: {stats:?}"
+            );
+        }
+        hashes.push(pollster::block_on(sim.particle_hash()));
+    }
+    assert!(
+        hashes.iter().all(|&hash| hash == hashes[0]),
+        "the hashed neighbour lists changed the result"
+    );
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use cgmath::{InnerSpace, Vector3};
+use common::RADIUS;
+use sph_particles::headless::{
+    ContainerKeyframe, HeadlessSim, Obstacle, ObstacleMotion, Particle, Scene,
+};
+
+const WIDTH: f32 = 1.0;
+const STEPS: usize = 800;
+const EPSILON: f32 = 1e-4;
+
+// current solver: the particles are pushed onto the surfaces, within 1e-8 m
+/// furthest a particle may be inside an obstacle
+const MAX_PENETRATION: f32 = 0.1 * RADIUS;
+
+const BALL_CENTER: Vector3<f32> = Vector3::new(0.45, 0.06, 1.5 * RADIUS);
+const BALL_RADIUS: f32 = 0.06;
+const PADDLE_HALF_EXTENTS: Vector3<f32> = Vector3::new(0.02, 0.15, 0.5);
+const PADDLE_START: Vector3<f32> = Vector3::new(0.04, 0.15, 1.5 * RADIUS);
+const PADDLE_TRAVEL: f32 = 0.2;
+
+/// a dam break into a ball on the floor, `paddle` adds a plate sweeping the column along
+fn scene(paddle: bool) -> Scene {
+    let mut obstacles = vec![Obstacle::sphere(BALL_CENTER, BALL_RADIUS).with_restitution(0.2)];
+    if paddle {
+        obstacles.push(
+            Obstacle::cuboid(PADDLE_START, PADDLE_HALF_EXTENTS).with_motion(
+                ObstacleMotion::default().with_keyframes(vec![
+                    ContainerKeyframe::new(0.1),
+                    ContainerKeyframe::new(0.5).with_translation(Vector3::unit_x() * PADDLE_TRAVEL),
+                ]),
+            ),
+        );
+    }
+    Scene {
+        // clear of the paddle
+        blocks: vec![common::planar_block([0.08, RADIUS], [0.3, 0.4])],
+        obstacles,
+        ..common::planar_scene(WIDTH, 10)
+    }
+}
+
+async fn run(scene: &Scene) -> anyhow::Result<Vec<Particle>> {
+    let mut sim = HeadlessSim::new(scene).await?;
+    for _ in 0..STEPS {
+        sim.step().await;
+    }
+    Ok(sim.read_particles().await)
+}
+
+fn mean_x(particles: &[Particle]) -> f32 {
+    particles.iter().map(|p| p.position.x).sum::<f32>() / particles.len() as f32
+}
+
+/// distances of `p` inside the ball and the paddle at the end of its travel, the planar
+/// solver cuts them in the plane of their centres
+fn penetrations(p: &Particle) -> [f32; 2] {
+    let ball = BALL_RADIUS - (p.position - BALL_CENTER).truncate().magnitude();
+    let paddle_center = PADDLE_START + Vector3::unit_x() * PADDLE_TRAVEL;
+    let d = (p.position - paddle_center).map(f32::abs) - PADDLE_HALF_EXTENTS;
+    let paddle = -d.x.max(d.y);
+    [ball, paddle]
+}
+
+#[test]
+fn obstacle_motion_follows_its_keyframes() {
+    let obstacle = Obstacle::sphere(Vector3::new(1.0, 1.0, 1.0), 0.5).with_motion(
+        ObstacleMotion::default().with_keyframes(vec![
+            ContainerKeyframe::new(0.0),
+            ContainerKeyframe::new(2.0).with_translation(Vector3::new(2.0, 0.0, 0.0)),
+        ]),
+    );
+    assert!(!obstacle.is_static());
+    let (position, _) = obstacle.pose(1.0);
+    assert!((position - Vector3::new(2.0, 1.0, 1.0)).magnitude() < EPSILON);
+    // three circles of line segments, around the centre at its current position
+    let lines = obstacle.wireframe(1.0);
+    assert_eq!(lines.len() % 2, 0);
+    assert!(lines
+        .iter()
+        .all(|p| ((p - position).magnitude() - 0.5).abs() < EPSILON));
+}
+
+#[test]
+fn fluid_stays_outside_the_obstacles() {
+    let Some(particles) = common::skip_without_adapter(pollster::block_on(run(&scene(true))))
+    else {
+        return;
+    };
+
+    let mut touching = [0; 2];
+    for p in particles.iter().filter(|p| p.ptype == 0) {
+        let [ball, paddle] = penetrations(p);
+        assert!(
+            ball < MAX_PENETRATION,
+            "particle at {:?} is {ball} inside the ball",
+            p.position
+        );
+        assert!(
+            paddle < MAX_PENETRATION,
+            "particle at {:?} is {paddle} inside the paddle",
+            p.position
+        );
+        touching[0] += (ball > -RADIUS) as usize;
+        touching[1] += (paddle > -RADIUS) as usize;
+    }
+    assert!(
+        touching.iter().all(|&n| n > 0),
+        "the fluid never reached the obstacles, particles touching the ball and the \
+         paddle: {touching:?}"
+    );
+
+    // the paddle swept the water along
+    let without_paddle = pollster::block_on(run(&scene(false))).unwrap();
+    assert!(
+        mean_x(&particles) > mean_x(&without_paddle) + 0.05,
+        "mean x {} with the paddle, {} without",
+        mean_x(&particles),
+        mean_x(&without_paddle)
+    );
+}
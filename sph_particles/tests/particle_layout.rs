@@ -0,0 +1,165 @@
+mod common;
+
+use cgmath::{InnerSpace, Vector3};
+use sph_particles::headless::{
+    FluidMaterial, HeadlessSim, Integrator, NeighborSearch, Particle, ParticleLayout,
+    ScalarChannel, Scene, SimParams, SpawnBlock,
+};
+
+const STEPS: usize = 30;
+const FEATURE_STEPS: usize = 15;
+
+/// a small collapsing column, enough motion for precision loss to show
+fn scene(layout: ParticleLayout) -> Scene {
+    Scene {
+        layout,
+        blocks: vec![SpawnBlock::fluid(
+            Vector3::new(0.1, 0.1, 0.1),
+            Vector3::new(0.7, 1.0, 0.7),
+        )],
+        ..common::coarse_scene(Vector3::new(10, 8, 4))
+    }
+}
+
+/// every solver pass at once: warm and cold blocks carrying scalars, a shear thinning and
+/// a granular material, a jelly, vorticity confinement, XSPH, the predictor-corrector and
+/// cached neighbour lists. each pass binds only its own fields in the split layouts
+fn feature_scene(layout: ParticleLayout) -> Scene {
+    let block = |lower: [f32; 3], upper: [f32; 3]| {
+        SpawnBlock::fluid(Vector3::from(lower), Vector3::from(upper))
+    };
+    let base = common::coarse_scene(Vector3::new(10, 8, 4));
+    Scene {
+        layout,
+        neighbor_search: NeighborSearch::CachedList,
+        blocks: vec![
+            block([0.1, 0.1, 0.1], [0.7, 0.7, 0.7])
+                .with_temperature(60.0)
+                .with_scalar(0, 1.0),
+            block([0.8, 0.1, 0.1], [1.2, 0.5, 0.7]).with_material(FluidMaterial::sand()),
+            block([1.3, 0.1, 0.1], [1.9, 0.6, 0.7])
+                .with_material(FluidMaterial::honey())
+                .with_temperature(10.0)
+                .with_scalar(1, 1.0),
+            block([0.3, 0.9, 0.2], [0.6, 1.2, 0.5]).with_material(FluidMaterial::jelly()),
+        ],
+        scalars: vec![ScalarChannel::new(1e-3), ScalarChannel::new(1e-3)],
+        params: SimParams {
+            integrator: Integrator::PredictorCorrector,
+            vorticity_enabled: true,
+            xsph_enabled: true,
+            ..base.params
+        },
+        ..base
+    }
+}
+
+async fn feature_particles(layout: ParticleLayout) -> anyhow::Result<Vec<Particle>> {
+    let mut sim = HeadlessSim::new(&feature_scene(layout)).await?;
+    for _ in 0..FEATURE_STEPS {
+        sim.step().await;
+    }
+    Ok(sim.read_particles().await)
+}
+
+/// largest difference of any field the solver writes
+fn max_field_difference(a: &[Particle], b: &[Particle]) -> f32 {
+    assert_eq!(a.len(), b.len());
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| {
+            let scalars = a
+                .scalars
+                .iter()
+                .zip(&b.scalars)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f32::max);
+            [
+                (a.position - b.position).magnitude(),
+                (a.velocity - b.velocity).magnitude(),
+                (a.density - b.density).abs(),
+                (a.pressure - b.pressure).abs(),
+                (a.temperature - b.temperature).abs(),
+                scalars,
+            ]
+            .into_iter()
+            .fold(0.0, f32::max)
+        })
+        .fold(0.0, f32::max)
+}
+
+async fn positions(layout: ParticleLayout) -> anyhow::Result<Vec<Vector3<f32>>> {
+    let mut sim = HeadlessSim::new(&scene(layout)).await?;
+    for _ in 0..STEPS {
+        sim.step().await;
+    }
+    Ok(sim
+        .read_particles()
+        .await
+        .iter()
+        .map(|p| p.position)
+        .collect())
+}
+
+fn max_distance(a: &[Vector3<f32>], b: &[Vector3<f32>]) -> f32 {
+    assert_eq!(a.len(), b.len());
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b).magnitude())
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn split_layouts_match_interleaved() {
+    let Some(interleaved) =
+        common::skip_without_adapter(pollster::block_on(positions(ParticleLayout::Interleaved)))
+    else {
+        return;
+    };
+    let split = pollster::block_on(positions(ParticleLayout::Split {
+        half_precision: false,
+    }))
+    .unwrap();
+    let half = pollster::block_on(positions(ParticleLayout::Split {
+        half_precision: true,
+    }))
+    .unwrap();
+
+    assert!(interleaved
+        .iter()
+        .all(|p| p.x.is_finite() && p.y.is_finite()));
+    // same arithmetic on the same values, only where they are stored differs
+    assert_eq!(
+        max_distance(&interleaved, &split),
+        0.0,
+        "split layout diverged from the interleaved one"
+    );
+    let half_error = max_distance(&interleaved, &half);
+    assert!(
+        half_error < 0.05,
+        "f16 velocity and pressure moved a particle {half_error} away after {STEPS} steps"
+    );
+}
+
+#[test]
+fn split_layout_matches_interleaved_in_every_pass() {
+    let Some(interleaved) = common::skip_without_adapter(pollster::block_on(feature_particles(
+        ParticleLayout::Interleaved,
+    ))) else {
+        return;
+    };
+    let split = pollster::block_on(feature_particles(ParticleLayout::Split {
+        half_precision: false,
+    }))
+    .unwrap();
+
+    assert!(interleaved
+        .iter()
+        .all(|p| p.position.x.is_finite() && p.temperature.is_finite()));
+    // a field missing from a pass's list would read as zero and show up here
+    let difference = max_field_difference(&interleaved, &split);
+    assert_eq!(
+        difference, 0.0,
+        "a solver pass of the split layout diverged from the interleaved one"
+    );
+}
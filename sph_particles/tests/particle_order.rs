@@ -0,0 +1,82 @@
+mod common;
+
+use cgmath::Vector3;
+use common::RADIUS;
+use sph_particles::headless::{
+    FluidMaterial, HeadlessSim, Particle, ParticleOrder, Scene, SimParams,
+};
+
+const WIDTH: f32 = 0.6;
+const STEPS: usize = 300;
+
+// current solver: the orders agree to about 1e-7 m, only the summation order differs
+const POSITION_TOLERANCE: f32 = 1e-3;
+
+/// a cube of jelly dropped next to a column of water
+fn scene(particle_order: ParticleOrder, reorder_interval: u32) -> Scene {
+    let scene = common::planar_scene(WIDTH, 10);
+    Scene {
+        params: SimParams {
+            particle_order,
+            reorder_interval,
+            ..scene.params
+        },
+        blocks: vec![
+            common::planar_block([RADIUS, RADIUS], [0.2, 0.4]),
+            common::planar_block([0.35, 0.2], [0.5, 0.35]).with_material(FluidMaterial::jelly()),
+        ],
+        ..scene
+    }
+}
+
+async fn run(scene: &Scene) -> anyhow::Result<Vec<Particle>> {
+    let mut sim = HeadlessSim::new(scene).await?;
+    for _ in 0..STEPS {
+        sim.step().await;
+    }
+    Ok(sim.read_particles().await)
+}
+
+/// mean position of the fluid and of the jelly
+fn centers(particles: &[Particle]) -> [Vector3<f32>; 2] {
+    [0, 1].map(|material| {
+        let block = particles.iter().filter(|p| p.material == material);
+        block.clone().map(|p| p.position).sum::<Vector3<f32>>() / block.count() as f32
+    })
+}
+
+#[test]
+fn reordered_particles_move_as_in_spawn_order() {
+    let reference = pollster::block_on(run(&scene(ParticleOrder::Linear, 0)));
+    let Some(reference) = common::skip_without_adapter(reference) else {
+        return;
+    };
+    let reference_centers = centers(&reference);
+
+    for order in ParticleOrder::ALL {
+        let particles = pollster::block_on(run(&scene(order, 7))).unwrap();
+        // sorted at the last reorder, the particles have moved a few steps since
+        let cell_ids = particles.iter().map(|p| p.cell_id).collect::<Vec<_>>();
+        assert!(
+            cell_ids.windows(2).all(|w| w[0] <= w[1]),
+            "{order:?}: particles not sorted by cell"
+        );
+        assert!(
+            particles
+                .iter()
+                .zip(&reference)
+                .any(|(p, q)| p.position != q.position),
+            "{order:?}: the particles were never reordered"
+        );
+
+        let centers = centers(&particles);
+        for (center, reference_center) in centers.iter().zip(&reference_centers) {
+            let distance = (center - reference_center).map(f32::abs);
+            assert!(
+                distance.x.max(distance.y) < POSITION_TOLERANCE,
+                "{order:?}: moved to {center:?} instead of {reference_center:?}, \
+                 all {centers:?}, spawn order {reference_centers:?}"
+            );
+        }
+    }
+}
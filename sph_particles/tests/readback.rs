@@ -0,0 +1,143 @@
+mod common;
+
+use cgmath::Vector3;
+use sph_particles::headless::{HeadlessSim, Particle, ParticleSnapshot, Scene, READBACK_RING_SIZE};
+
+/// steps the snapshots are requested in
+const STEPS: usize = 20;
+/// steps after the last request, enough for every snapshot to arrive
+const DRAIN_STEPS: usize = READBACK_RING_SIZE + 1;
+
+/// a falling block, so every step moves the particles
+fn scene() -> Scene {
+    Scene {
+        blocks: vec![common::planar_block([0.1, 0.2], [0.3, 0.4])],
+        ..common::planar_scene(0.5, 8)
+    }
+}
+
+fn positions(particles: &[Particle]) -> Vec<Vector3<f32>> {
+    particles.iter().map(|p| p.position).collect()
+}
+
+/// the particles after every step, read back blocking
+async fn blocking_frames() -> anyhow::Result<Vec<Vec<Particle>>> {
+    let mut sim = HeadlessSim::new(&scene()).await?;
+    let mut frames = vec![];
+    for _ in 0..STEPS + DRAIN_STEPS {
+        sim.step().await;
+        frames.push(sim.read_particles().await);
+    }
+    Ok(frames)
+}
+
+struct Arrival {
+    /// the frame the snapshot was requested in, counting from 1 like its own
+    requested: u64,
+    snapshot: ParticleSnapshot,
+    /// when it was taken
+    age: u64,
+}
+
+/// a snapshot requested by each consumer every `intervals[consumer]` steps, taken as soon
+/// as it arrives
+async fn snapshots(intervals: &[usize]) -> anyhow::Result<Vec<Vec<Arrival>>> {
+    let mut sim = HeadlessSim::new(&scene()).await?;
+    let mut tickets = vec![vec![]; intervals.len()];
+    let mut arrivals = intervals.iter().map(|_| vec![]).collect::<Vec<_>>();
+    for step in 0..STEPS + DRAIN_STEPS {
+        for (interval, tickets) in intervals.iter().zip(&mut tickets) {
+            if step < STEPS && step % interval == 0 {
+                tickets.push((step as u64 + 1, sim.request_snapshot()));
+            }
+        }
+        sim.step().await;
+        for (tickets, arrivals) in tickets.iter_mut().zip(&mut arrivals) {
+            tickets.retain(|&(requested, ticket)| {
+                let Some(snapshot) = sim.take_snapshot(ticket) else {
+                    return true;
+                };
+                arrivals.push(Arrival {
+                    requested,
+                    age: sim.snapshot_age(&snapshot),
+                    snapshot,
+                });
+                false
+            });
+        }
+    }
+    Ok(arrivals)
+}
+
+/// the snapshots are of a frame at or after the one they were requested in, hold that
+/// frame's particles and come in the order they were requested in
+fn assert_arrivals(arrivals: &[Arrival], frames: &[Vec<Particle>]) {
+    for arrival in arrivals {
+        let frame = arrival.snapshot.frame;
+        assert!(
+            frame >= arrival.requested,
+            "the snapshot requested in frame {} is of frame {frame}",
+            arrival.requested
+        );
+        // frame n ends with the nth step
+        assert_eq!(
+            positions(&arrival.snapshot.particles),
+            positions(&frames[frame as usize - 1])
+        );
+    }
+    assert!(arrivals
+        .windows(2)
+        .all(|pair| pair[0].snapshot.frame <= pair[1].snapshot.frame));
+}
+
+#[test]
+fn snapshots_arrive_without_blocking_and_report_their_age() {
+    let result = pollster::block_on(async {
+        anyhow::Ok((blocking_frames().await?, snapshots(&[5]).await?))
+    });
+    let Some((frames, arrivals)) = common::skip_without_adapter(result) else {
+        return;
+    };
+
+    // the runs are deterministic, so the snapshots match the blocking reads
+    let arrivals = &arrivals[0];
+    assert_eq!(arrivals.len(), STEPS / 5);
+    for arrival in arrivals {
+        assert!(
+            arrival.age < READBACK_RING_SIZE as u64,
+            "the snapshot of frame {} arrived {} frames later",
+            arrival.snapshot.frame,
+            arrival.age
+        );
+        assert_eq!(arrival.snapshot.frame, arrival.requested);
+    }
+    assert_arrivals(arrivals, &frames);
+}
+
+#[test]
+fn each_consumer_takes_its_own_snapshots() {
+    // one consumer asks every step, so the ring runs full and requests wait for a slot
+    let result = pollster::block_on(async {
+        anyhow::Ok((blocking_frames().await?, snapshots(&[1, 4]).await?))
+    });
+    let Some((frames, arrivals)) = common::skip_without_adapter(result) else {
+        return;
+    };
+
+    assert_eq!(arrivals[0].len(), STEPS);
+    assert_eq!(arrivals[1].len(), STEPS / 4);
+    for consumer in &arrivals {
+        assert_arrivals(consumer, &frames);
+    }
+    // requested in the same frame, they share the copy
+    for arrival in &arrivals[1] {
+        assert!(
+            arrivals[0]
+                .iter()
+                .any(|other| other.requested == arrival.requested
+                    && other.snapshot.frame == arrival.snapshot.frame),
+            "the consumers got different copies of frame {}",
+            arrival.requested
+        );
+    }
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use cgmath::{InnerSpace, Vector3};
+use common::RADIUS;
+use sph_particles::headless::{HeadlessSim, Particle, Scene, SimParams};
+
+const WIDTH: f32 = 0.6;
+const HEIGHT: f32 = 0.3;
+const STEPS: usize = 300;
+const RELAXATION_STEPS: u32 = 2000;
+
+/// a tank of still water on the spawn lattice
+fn scene(calibrate_rest_volume: bool, relaxation_steps: u32) -> Scene {
+    let scene = common::planar_scene(WIDTH, 8);
+    Scene {
+        params: SimParams {
+            calibrate_rest_volume,
+            relaxation_steps,
+            ..scene.params
+        },
+        blocks: vec![common::planar_block(
+            [RADIUS, RADIUS],
+            [WIDTH - RADIUS, HEIGHT],
+        )],
+        ..scene
+    }
+}
+
+/// how far the fluid sinks in `STEPS` steps from the start and the fastest particle on
+/// the way, sampled every 10 steps
+async fn settle(scene: &Scene) -> anyhow::Result<(f32, f32)> {
+    let mut sim = HeadlessSim::new(scene).await?;
+    let initial = sim.read_particles().await;
+    let mut max_speed = 0.0f32;
+    for step in 0..STEPS {
+        sim.step().await;
+        if step % 10 == 0 {
+            max_speed = sim
+                .read_particles()
+                .await
+                .iter()
+                .map(|p| p.velocity.magnitude())
+                .fold(max_speed, f32::max);
+        }
+    }
+    let sink = mean_height(&initial) - mean_height(&sim.read_particles().await);
+    Ok((sink, max_speed))
+}
+
+fn mean_height(particles: &[Particle]) -> f32 {
+    particles.iter().map(|p| p.position.y).sum::<f32>() / particles.len() as f32
+}
+
+#[test]
+fn calibrated_lattice_starts_at_rest_density() {
+    let result = pollster::block_on(async {
+        anyhow::Ok((
+            settle(&scene(false, 0)).await?,
+            settle(&scene(true, 0)).await?,
+        ))
+    });
+    let Some(((fixed_sink, _), (calibrated_sink, _))) = common::skip_without_adapter(result) else {
+        return;
+    };
+
+    // with the fixed volume the lattice is at less than half the rest density, the
+    // fluid collapses until it reaches it
+    assert!(
+        calibrated_sink < 0.5 * fixed_sink && calibrated_sink < 2.0 * RADIUS,
+        "the fluid sinks {fixed_sink} m, calibrated {calibrated_sink} m"
+    );
+}
+
+#[test]
+fn relaxation_settles_the_lattice_and_keeps_the_spawn_velocities() {
+    let velocity = Vector3::new(0.2, 0.0, 0.0);
+    let mut moving = scene(true, 10);
+    moving.blocks[0].velocity = velocity;
+    let result = pollster::block_on(async {
+        let initial = HeadlessSim::new(&moving).await?.read_particles().await;
+        anyhow::Ok((
+            initial,
+            settle(&scene(true, 0)).await?,
+            settle(&scene(true, RELAXATION_STEPS)).await?,
+        ))
+    });
+    let Some((initial, (sink, max_speed), (relaxed_sink, relaxed_max_speed))) =
+        common::skip_without_adapter(result)
+    else {
+        return;
+    };
+
+    assert!(
+        initial.iter().all(|p| p.velocity == velocity),
+        "the relaxation changed the spawn velocity {velocity:?}"
+    );
+
+    assert!(
+        relaxed_sink < 0.5 * sink && relaxed_max_speed < 0.75 * max_speed,
+        "the fluid sinks {sink} m at up to {max_speed} m/s, \
+         relaxed {relaxed_sink} m at up to {relaxed_max_speed} m/s"
+    );
+}
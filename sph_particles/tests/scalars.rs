@@ -0,0 +1,102 @@
+mod common;
+
+use cgmath::Vector3;
+use common::RADIUS;
+use sph_particles::headless::{
+    HeadlessSim, Particle, ParticleLayout, ScalarChannel, ScalarSource, Scene,
+};
+
+const WIDTH: f32 = 0.6;
+const STEPS: usize = 500;
+const DIFFUSIVITY: f32 = 0.01;
+/// where the dyed block meets the clear one
+const INTERFACE: f32 = 0.3;
+
+/// a tank of still water, dyed in its left half on channel 0. channel 1 doesn't diffuse
+/// and has a source in the bottom left corner
+fn scene(layout: ParticleLayout) -> Scene {
+    Scene {
+        layout,
+        blocks: vec![
+            common::planar_block([RADIUS, RADIUS], [INTERFACE, 0.15]).with_scalar(0, 1.0),
+            common::planar_block([INTERFACE, RADIUS], [WIDTH - RADIUS, 0.15]),
+        ],
+        scalars: vec![ScalarChannel::new(DIFFUSIVITY), ScalarChannel::new(0.0)],
+        scalar_sources: vec![ScalarSource::new(
+            1,
+            Vector3::new(0.0, 0.0, -1.0),
+            Vector3::new(0.1, 0.1, 1.0),
+            1.0,
+        )
+        .with_rate(20.0)],
+        ..common::planar_scene(WIDTH, 5)
+    }
+}
+
+async fn run(scene: &Scene) -> anyhow::Result<(Vec<Particle>, Vec<Particle>)> {
+    let mut sim = HeadlessSim::new(scene).await?;
+    let initial = sim.read_particles().await;
+    for _ in 0..STEPS {
+        sim.step().await;
+    }
+    Ok((initial, sim.read_particles().await))
+}
+
+fn total(particles: &[Particle], channel: usize) -> f32 {
+    particles.iter().map(|p| p.scalars[channel]).sum()
+}
+
+#[test]
+fn scalars_diffuse_and_follow_their_sources() {
+    for layout in [
+        ParticleLayout::Interleaved,
+        ParticleLayout::Split {
+            half_precision: false,
+        },
+    ] {
+        let Some((initial, particles)) =
+            common::skip_without_adapter(pollster::block_on(run(&scene(layout))))
+        else {
+            return;
+        };
+
+        // diffusion moves dye across the interface without creating or losing any
+        let (before, after) = (total(&initial, 0), total(&particles, 0));
+        let clear_side = particles
+            .iter()
+            .filter(|p| p.material == 1)
+            .map(|p| p.scalars[0])
+            .fold(0.0, f32::max);
+        assert!(
+            (after - before).abs() < 0.01 * before,
+            "{layout:?}: the total dye went from {before} to {after}"
+        );
+        assert!(
+            clear_side > 0.1,
+            "{layout:?}: at most {clear_side} dye diffused to the clear side"
+        );
+        assert!(
+            particles
+                .iter()
+                .all(|p| (-1e-3..=1.0 + 1e-3).contains(&p.scalars[0])),
+            "{layout:?}: a dye concentration left [0, 1]"
+        );
+
+        // the source fills its corner, channel 1 stays put everywhere else
+        let (inside, outside): (Vec<&Particle>, Vec<_>) = particles
+            .iter()
+            .partition(|p| p.position.x < 0.1 && p.position.y < 0.1);
+        let filled = inside.iter().map(|p| p.scalars[1]).fold(1.0, f32::min);
+        assert!(
+            !inside.is_empty() && filled > 0.5,
+            "{layout:?}: {} particles at the source, the least filled at {filled}",
+            inside.len()
+        );
+        assert!(
+            outside
+                .iter()
+                .all(|p| p.scalars[1] == 0.0 || p.position.x < 0.1 + 2.0 * RADIUS),
+            "{layout:?}: channel 1 spread away from its source"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use cgmath::{InnerSpace, Vector3, Zero};
+use common::RADIUS;
+use sph_particles::headless::{HeadlessSim, Particle, Scene, SimParams};
+
+const WIDTH: f32 = 1.0;
+/// columns of the noisy block
+const COLUMNS: usize = 16;
+/// vertical speed of every column or half block, up and down in turn
+const SPEED: f32 = 0.5;
+const XSPH_STEPS: usize = 20;
+const VORTICITY_STEPS: usize = 300;
+
+/// `count` blocks `block_width` wide side by side in the middle of a planar tank without
+/// gravity, moving up and down in turn
+fn weightless_scene(block_width: f32, count: usize) -> Scene {
+    let scene = common::planar_scene(WIDTH, 10);
+    let left = 0.5 * WIDTH - 0.5 * count as f32 * block_width;
+    Scene {
+        params: SimParams {
+            gravity: Vector3::zero(),
+            ..scene.params
+        },
+        blocks: (0..count)
+            .map(|i| {
+                let x = left + i as f32 * block_width;
+                let direction = if i % 2 == 0 { 1.0 } else { -1.0 };
+                // short of the next block, which starts its own lattice
+                common::planar_block([x, 0.15], [x + block_width - RADIUS, 0.45])
+                    .with_velocity(Vector3::new(0.0, direction * SPEED, 0.0))
+            })
+            .collect(),
+        ..scene
+    }
+}
+
+/// `scene` with `filters` applied to its parameters
+fn with_params(scene: Scene, filters: impl FnOnce(SimParams) -> SimParams) -> Scene {
+    Scene {
+        params: filters(scene.params),
+        ..scene
+    }
+}
+
+async fn run(scene: Scene, steps: usize) -> anyhow::Result<Vec<Particle>> {
+    let mut sim = HeadlessSim::new(&scene).await?;
+    for _ in 0..steps {
+        sim.step().await;
+    }
+    Ok(sim.read_particles().await)
+}
+
+/// mean squared deviation from the mean velocity, m²/s²
+fn velocity_variance(particles: &[Particle]) -> f32 {
+    let mean = particles
+        .iter()
+        .fold(Vector3::zero(), |sum, p| sum + p.velocity)
+        / particles.len() as f32;
+    particles
+        .iter()
+        .map(|p| (p.velocity - mean).magnitude2())
+        .sum::<f32>()
+        / particles.len() as f32
+}
+
+fn kinetic_energy(particles: &[Particle]) -> f32 {
+    particles
+        .iter()
+        .map(|p| 0.5 * p.velocity.magnitude2())
+        .sum::<f32>()
+        / particles.len() as f32
+}
+
+#[test]
+fn xsph_smooths_a_noisy_block() {
+    // columns one particle wide, without viscosity nothing else evens them out
+    let noisy = || {
+        with_params(weightless_scene(2.0 * RADIUS, COLUMNS), |params| {
+            SimParams {
+                viscosity: 0.0,
+                ..params
+            }
+        })
+    };
+    let Some(plain) = common::skip_without_adapter(pollster::block_on(run(noisy(), XSPH_STEPS)))
+    else {
+        return;
+    };
+    let smoothed = with_params(noisy(), |params| SimParams {
+        xsph_enabled: true,
+        xsph: 0.5,
+        ..params
+    });
+    let smoothed = pollster::block_on(run(smoothed, XSPH_STEPS)).unwrap();
+
+    let (plain, smoothed) = (velocity_variance(&plain), velocity_variance(&smoothed));
+    assert!(
+        smoothed < 0.5 * plain,
+        "velocity variance {smoothed} m²/s² with XSPH, {plain} m²/s² without"
+    );
+}
+
+#[test]
+fn confinement_keeps_a_stirred_block_moving() {
+    // two halves sliding past each other roll up into a vortex the viscosity slows down
+    let stirred = || weightless_scene(0.15, 2);
+    let Some(plain) =
+        common::skip_without_adapter(pollster::block_on(run(stirred(), VORTICITY_STEPS)))
+    else {
+        return;
+    };
+    let confined = with_params(stirred(), |params| SimParams {
+        vorticity_enabled: true,
+        vorticity: 0.2,
+        ..params
+    });
+    let confined = pollster::block_on(run(confined, VORTICITY_STEPS)).unwrap();
+
+    let (plain, confined) = (kinetic_energy(&plain), kinetic_energy(&confined));
+    assert!(
+        confined > 1.2 * plain,
+        "kinetic energy {confined} J/kg with confinement, {plain} J/kg without"
+    );
+}
@@ -288,6 +288,127 @@ pub async fn run() {
 
     //----------------------------------------------------------
 
+    // per pass uniforms, the way a simulation step passes its parameters: a staging belt
+    // write and a submit per pass, against one buffer of slots bound with dynamic offsets
+
+    let step_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+    let step_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[&bind_group_layout, &step_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let step_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Step Pipeline"),
+        layout: Some(&step_pipeline_layout),
+        module: &shader,
+        entry_point: "main_step",
+    });
+
+    const PASSES: u64 = 200;
+    let step_size = std::mem::size_of::<[i32; 4]>() as u64;
+    let step_stride =
+        step_size.next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+    let step_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: step_stride * PASSES,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let step_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &step_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &step_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(step_size),
+            }),
+        }],
+    });
+    // alternating +1 and -1, so a and b end where they started
+    let step_delta = |pass: u64| if pass % 2 == 0 { [1i32; 4] } else { [-1i32; 4] };
+
+    let timer = Instant::now();
+
+    let mut staging_belt = wgpu::util::StagingBelt::new(0x1000);
+    for pass in 0..PASSES {
+        let mut command_encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        staging_belt
+            .write_buffer(
+                &mut command_encoder,
+                &step_buffer,
+                0,
+                wgpu::BufferSize::new(step_size).unwrap(),
+                &device,
+            )
+            .copy_from_slice(bytemuck::cast_slice(&step_delta(pass)));
+        staging_belt.finish();
+        {
+            let mut compute_pass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: None,
+                    timestamp_writes: None,
+                });
+            compute_pass.set_pipeline(&step_pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.set_bind_group(1, &step_bind_group, &[0]);
+            compute_pass.dispatch_workgroups(local_a.len() as u32, 1, 1);
+        }
+        queue.submit(Some(command_encoder.finish()));
+        staging_belt.recall();
+    }
+    device.poll(wgpu::Maintain::Wait);
+
+    let elapsed_time = timer.elapsed().as_secs_f32();
+    log::info!("{PASSES} passes, staging belt and submit per pass cost: {elapsed_time}");
+
+    //----------------------------------------------------------
+
+    let timer = Instant::now();
+
+    let mut step_data = vec![0u8; (step_stride * PASSES) as usize];
+    for (pass, slot) in step_data.chunks_exact_mut(step_stride as usize).enumerate() {
+        slot[..step_size as usize].copy_from_slice(bytemuck::cast_slice(&step_delta(pass as u64)));
+    }
+    queue.write_buffer(&step_buffer, 0, &step_data);
+
+    let mut command_encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    {
+        let mut compute_pass = command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: None,
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&step_pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        for pass in 0..PASSES {
+            compute_pass.set_bind_group(1, &step_bind_group, &[(pass * step_stride) as u32]);
+            compute_pass.dispatch_workgroups(local_a.len() as u32, 1, 1);
+        }
+    }
+    queue.submit(Some(command_encoder.finish()));
+    device.poll(wgpu::Maintain::Wait);
+
+    let elapsed_time = timer.elapsed().as_secs_f32();
+    log::info!("{PASSES} passes, dynamic offsets and one submit cost: {elapsed_time}");
+
+    //----------------------------------------------------------
+
     get_data(
         &mut local_a[..],
         &storage_buffer_a,
@@ -33,3 +33,22 @@ fn main2(@builtin(local_invocation_id) lid: vec3<u32>, @builtin(workgroup_id) wi
         b[wid.x] -= 1;
     }
 }
+
+// per pass amounts, bound with a dynamic offset
+struct Step {
+    delta: vec4<i32>,
+}
+
+@group(1)
+@binding(0)
+var<uniform> step: Step;
+
+@compute
+@workgroup_size(2, 1, 1)
+fn main_step(@builtin(local_invocation_id) lid: vec3<u32>, @builtin(workgroup_id) wid: vec3<u32>) {
+    if lid.x == 0u {
+        a[wid.x] += step.delta.x;
+    } else if lid.x == 1u {
+        b[wid.x] += step.delta.y;
+    }
+}